@@ -1 +1,39 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
 struct GCContext {}
+/// A managed `System.String`. Strings are immutable, so `chars` is never written to after creation.
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct ManagedString {
+    length: usize,
+    chars: *const u16,
+}
+impl ManagedString {
+    /// Contents of this string, as UTF-16 code units.
+    pub(crate) fn chars(&self) -> &[u16] {
+        unsafe { std::slice::from_raw_parts(self.chars, self.length) }
+    }
+}
+// Interned strings are neither freed nor mutated, so sharing them between threads is fine.
+unsafe impl Send for ManagedString {}
+unsafe impl Sync for ManagedString {}
+/// String literals. They are never collected, so pointers to them stay valid for as long as the process runs.
+static INTERNED_STRINGS: OnceLock<Mutex<HashMap<&'static [u16], &'static ManagedString>>> =
+    OnceLock::new();
+/// Gets the interned string object with contents `chars`, creating it if it does not exist yet.
+pub(crate) fn intern_string(chars: &[u16]) -> &'static ManagedString {
+    let mut strings = INTERNED_STRINGS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("String pool poisoned!");
+    if let Some(string) = strings.get(chars) {
+        return string;
+    }
+    let chars: &'static [u16] = Box::leak(chars.into());
+    let string = Box::leak(Box::new(ManagedString {
+        length: chars.len(),
+        chars: chars.as_ptr(),
+    }));
+    strings.insert(chars, string);
+    string
+}
@@ -1,217 +1,866 @@
+use super::attribute::{self, AttributeResolver};
+use super::cil::{self, Instruction, TokenResolver};
+use super::metadata::Metadata;
+use super::method_body::{ClauseKind, EhClause, MethodBody};
+use super::pe::Image;
+use super::signature::{decode_field, local_types, ElementType, MethodSig};
+use super::slice_reader::SliceReader;
+use super::tables::{get_row, BlobIndex, RowIndex, StringIndex, TableKind, Tables, Token};
+use crate::ir::method::{Handler, ProtectedRegion};
+use crate::ir::{MethodIRError, Signature};
+use crate::type_system::attribute::{AttributeValue, CustomAttribute};
+use crate::type_system::paths::{ClassPath, MethodPath};
 use crate::type_system::runtime::Runtime;
-use std::io::Read;
-struct SmartReader<R: Read> {
-    offset: usize,
-    src: R,
+use crate::type_system::statics::ClassStatics;
+use crate::{Method, OpKind, Type};
+use std::io::{Read, Seek};
+const CLI_HEADER_SIZE: u32 = 0x48;
+const COMIMAGE_FLAGS_ILONLY: u32 = 0x1;
+/// Largest static field without initial data. Its size comes from the assembly, so a malformed one could otherwise allocate gigabytes.
+const MAX_STATIC_FIELD_SIZE: u32 = 0x0100_0000;
+/// Names of the assemblies defining `System.Object`, depending on the framework an assembly targets.
+const CORE_LIBRARIES: [&str; 4] = [
+    "System.Runtime",
+    "System.Private.CoreLib",
+    "mscorlib",
+    "netstandard",
+];
+/// An error encountered while importing an assembly. Offsets are file offsets, unless named otherwise.
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    UnexpectedEndOfData {
+        offset: usize,
+    },
+    InvalidUtf8 {
+        offset: usize,
+    },
+    InvalidCompressedInteger {
+        offset: usize,
+    },
+    InvalidMagic {
+        offset: usize,
+        expected: u32,
+        found: u32,
+    },
+    /// A header field had a value the importer does not support.
+    UnexpectedValue {
+        offset: usize,
+        field: &'static str,
+        expected: u32,
+        found: u32,
+    },
+    /// A header field had a value outside of the set the importer supports.
+    UnsupportedValue {
+        offset: usize,
+        field: &'static str,
+        found: u32,
+    },
+    /// The image has no CLI header, so it is not a managed assembly.
+    NotManaged,
+    StreamOutOfBounds {
+        offset: usize,
+        name: String,
+    },
+    MissingStream(&'static str),
+    UnsupportedStream(&'static str),
+    UnknownTable {
+        offset: usize,
+        table: u8,
+    },
+    InvalidCodedIndex {
+        offset: usize,
+        value: u32,
+    },
+    InvalidRowIndex {
+        table: u8,
+        row: u32,
+    },
+    InvalidRva(u32),
+    InvalidMethodHeader {
+        offset: usize,
+        found: u8,
+    },
+    /// An exception handling clause of unknown kind.
+    InvalidEhClause {
+        offset: usize,
+        flags: u32,
+    },
+    /// An exception handling clause boundary does not fall on an instruction.
+    InvalidRegionOffset {
+        il_offset: u32,
+    },
+    UnknownOpcode {
+        il_offset: u32,
+        opcode: u16,
+    },
+    UnsupportedOpcode {
+        il_offset: u32,
+        opcode: &'static str,
+    },
+    InvalidBranchTarget {
+        il_offset: u32,
+        target: i64,
+    },
+    UnresolvedToken(u32),
+    InvalidBlobIndex(u32),
+    InvalidUserStringIndex(u32),
+    InvalidSignature {
+        offset: usize,
+        found: u8,
+    },
+    UnsupportedCallingConvention {
+        offset: usize,
+        found: u8,
+    },
+    UnsupportedType(&'static str),
+    MissingAssemblyManifest,
+    /// An assembly referenced by a call could not be found by the runtime's resolver.
+    UnresolvedAssembly(String),
+    /// A call targets a method missing from the assembly it references.
+    UnresolvedMethod(MethodPath),
+    /// A dependency of the assembly being imported could not be imported.
+    InvalidDependency {
+        name: String,
+        error: Box<ImportError>,
+    },
+    /// The code of a method, beginning at `offset`, could not be imported.
+    InvalidMethodBody {
+        method: MethodPath,
+        offset: usize,
+        error: Box<ImportError>,
+    },
+    InvalidMethod(MethodPath, MethodIRError),
+    /// A static field without initial data is larger than the runtime allows.
+    StaticFieldTooLarge {
+        name: String,
+        size: u32,
+    },
 }
-impl<R: Read> SmartReader<R> {
-    fn new(src: R) -> Self {
-        Self { offset: 0, src }
-    }
-    fn skip_to(&mut self, offset: usize) -> std::io::Result<()> {
-        let mut by = offset - self.offset;
-        self.skip(by)
-    }
-    fn skip(&mut self, mut ammount: usize) -> std::io::Result<()> {
-        let mut discard = [0; 8];
-        //println!("skipping {} bytes beginig at offset 0x{:x}, and ending at offset 0x{:x}!",by,self.offset,offset);
-        while ammount > 0 {
-            let curr = if ammount >= 8 { 8 } else { ammount };
-            ammount -= curr;
-            self.read_exact(&mut discard[..curr])?;
+impl ImportError {
+    /// Checks if this error only affects the method it was found in: it uses a feature the runtime does not support yet,
+    /// or calls a method the runtime can't find. Malformed assemblies give other errors.
+    pub(crate) fn is_unsupported(&self) -> bool {
+        match self {
+            Self::UnsupportedType(_)
+            | Self::UnsupportedOpcode { .. }
+            | Self::UnsupportedCallingConvention { .. }
+            | Self::UnresolvedAssembly(_)
+            | Self::UnresolvedMethod(_)
+            | Self::InvalidMethod(..) => true,
+            Self::InvalidMethodBody { error, .. } => error.is_unsupported(),
+            _ => false,
         }
-        Ok(())
-    }
-    fn curr_offset(&self)->usize{
-        self.offset
-    }
-}
-impl<R: Read> Read for SmartReader<R> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let size = self.src.read(buf)?;
-        self.offset += size;
-        Ok(size)
     }
 }
+/// A static method left out of an imported assembly, because it uses something the runtime does not support yet,
+/// or calls a method the runtime can't find.
 #[derive(Debug)]
-pub enum ImportError {}
-#[derive(Debug)]
-struct SectionHeader {
+pub struct SkippedMethod {
+    class: ClassPath,
     name: String,
-    offset: u32,
-    size: u32,
-    virtual_adress:u32,
-}
-fn load_headers<R: Read>(
-    data: &mut SmartReader<R>,
-    count: usize,
-) -> Result<Vec<SectionHeader>, ImportError> {
-    let mut res = Vec::with_capacity(count);
-    for _ in 0..count {
-        let mut name = [0; 8];
-        data.read_exact(&mut name);
-        let name = std::str::from_utf8(&name).unwrap().to_owned();
-        //Skip virtual size
-        data.skip(4);
-        let mut virtual_adress = [0; 4];
-        data.read_exact(&mut virtual_adress);
-        let virtual_adress = u32::from_le_bytes(virtual_adress);
-        let mut raw_size = [0; 4];
-        data.read_exact(&mut raw_size);
-        let raw_size = u32::from_le_bytes(raw_size);
-        let mut raw_offset = [0; 4];
-        data.read_exact(&mut raw_offset);
-        let raw_offset = u32::from_le_bytes(raw_offset);
-        //Skip pointer to relocations
-        data.skip(4);
-        //Skip pointer to line numbers
-        data.skip(4);
-        //Skip number of relocations
-        data.skip(2);
-        //Skip number of line numbers
-        data.skip(2);
-        //Skip characteristics
-        data.skip(4);
-        res.push(SectionHeader {
-            name,
-            offset: raw_offset,
-            size: raw_size,
-            virtual_adress:virtual_adress
+    error: ImportError,
+}
+impl SkippedMethod {
+    /// Class declaring the method.
+    pub fn class(&self) -> &ClassPath {
+        &self.class
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Why the method could not be imported.
+    pub fn error(&self) -> &ImportError {
+        &self.error
+    }
+}
+fn expect_value(
+    offset: usize,
+    field: &'static str,
+    expected: u32,
+    found: u32,
+) -> Result<(), ImportError> {
+    if expected != found {
+        return Err(ImportError::UnexpectedValue {
+            offset,
+            field,
+            expected,
+            found,
         });
     }
-    Ok(res)
-}
-fn load_header<R: Read>(asm: &mut SmartReader<R>) -> Result<(u32, u32,u32), ImportError> {
-    let mut dos_magic = [0; 2];
-    asm.read_exact(&mut dos_magic).unwrap();
-    //Is dos executable
-    assert_eq!(dos_magic, *b"MZ");
-    //DOS header, irrelewant
-    let mut dos_header = [0; 0x3C - 2];
-    asm.read_exact(&mut dos_header).unwrap();
-    //PE offset, ought to be 0x80
-    let mut pe_offset = [0; 4];
-    asm.read_exact(&mut pe_offset).unwrap();
-    let pe_offset = u32::from_le_bytes(pe_offset);
-    assert_eq!(pe_offset, 0x80);
-    //DOS stub, irrelevant
-    asm.skip_to(0x80).unwrap();
-    //PE magic, letters PE and 0x00 0x00
-    let mut pe_magic = [0; 4];
-    asm.read_exact(&mut pe_magic).unwrap();
-    assert_eq!(pe_magic, *b"PE\0\0");
-    asm.skip_to(0x84).unwrap();
-    let mut section_count = [0; 2];
-    asm.read_exact(&mut section_count).unwrap();
-    let section_count = u16::from_le_bytes(section_count);
-    asm.skip_to(0x96).unwrap();
-    //PE file type, ends at x97
-    let mut file_type = [0; 2];
-    asm.read_exact(&mut file_type).unwrap();
-    let file_type = u16::from_le_bytes(file_type);
-    assert_eq!(
-        file_type & 0x2000,
-        0x2000,
-        "Only dynamic libraries may be imported now!"
-    );
-    //Architecture, only AnyCPU supported.
-    let mut architecture = [0; 2];
-    asm.read_exact(&mut architecture).unwrap();
-    let architecture = u16::from_le_bytes(architecture);
-    assert_eq!(architecture, 0x10b, "Only AnyCPU assemblies are supported!");
-    asm.skip_to(0xDB);
-    //Subsystem field
-    let mut subsystem = [0; 2];
-    asm.read_exact(&mut subsystem).unwrap();
-    let subsystem = u16::from_le_bytes(subsystem);
-    assert_eq!(subsystem, 0x300, "Only console programs supported!");
-    //Irrelevant NT fields
-    //asm.skip_to(0xD4B);
-    asm.skip_to(0xF7);
-    //Data directories
-    let mut data_dirs = [0; 9 * 14];
-    asm.read_exact(&mut data_dirs).unwrap();
-    let cil_rva = &data_dirs[8 * 14..8 * 14 + 4];
-    let cil_rva = u32::from_le_bytes(cil_rva.try_into().unwrap());
-    let cil_size = &data_dirs[8 * 14 + 4..8 * 14 + 8];
-    let cil_size = u32::from_le_bytes(cil_size.try_into().unwrap());
-    asm.skip_to(0x178);
-    //TODO: Get header count
-    let headers = load_headers(asm, 3)?;
-    for header in &headers {
-        if header.name == ".text\0\0\0" {
-            return Ok((header.size, header.offset,header.virtual_adress));
-        }
-    }
-    panic!("No .txt header, headers:{headers:?}");
-}
-pub(crate) fn import_assembly<R: Read>(
+    Ok(())
+}
+pub(crate) fn import_assembly<R: Read + Seek>(
     asm: &mut R,
     runtime: &mut Runtime,
 ) -> Result<(), ImportError> {
-    let mut asm = SmartReader::new(asm);
-    let mut asm = &mut asm;
-    let (text_size, text_offset,virtual_adress) = load_header(asm)?;
-    asm.skip_to(text_offset as usize).unwrap();
-    let mut cil_data = vec![0; text_size as usize];
-    asm.read_exact(&mut cil_data).unwrap();
-    load_managed_data(&mut SmartReader::new(cil_data.as_slice()), runtime,text_offset,virtual_adress)
-}
-fn load_managed_data<R: Read>(
-    cil_data: &mut SmartReader<R>,
+    load_managed_data(&Image::read(asm)?, runtime)
+}
+/// Imports an assembly already in memory, without copying it.
+pub(crate) fn import_assembly_bytes(asm: &[u8], runtime: &mut Runtime) -> Result<(), ImportError> {
+    load_managed_data(&Image::parse(asm)?, runtime)
+}
+/// Reads the metadata of `image`, checking that it is a managed assembly the runtime can load.
+pub(crate) fn load_metadata<'a>(image: &'a Image) -> Result<Metadata<'a>, ImportError> {
+    let (cli_header_rva, _) = image.headers().cli_header();
+    let (cli_header, header_offset) = image.data_at(cli_header_rva)?;
+    let mut cli_header = SliceReader::at(cli_header, header_offset);
+    let cil_header_size = cli_header.read_u32()?;
+    expect_value(header_offset, "cb", CLI_HEADER_SIZE, cil_header_size)?;
+    let clr_major = cli_header.read_u16()?;
+    expect_value(
+        header_offset + 4,
+        "MajorRuntimeVersion",
+        2,
+        u32::from(clr_major),
+    )?;
+    let clr_minor = cli_header.read_u16()?;
+    expect_value(
+        header_offset + 6,
+        "MinorRuntimeVersion",
+        5,
+        u32::from(clr_minor),
+    )?;
+    let metadata_rva = cli_header.read_u32()?;
+    let metadata_size = cli_header.read_u32()?;
+    let flags = cli_header.read_u32()?;
+    //Mixed-mode images contain native code, which can't be run.
+    if flags & COMIMAGE_FLAGS_ILONLY == 0 {
+        return Err(ImportError::UnsupportedValue {
+            offset: header_offset + 16,
+            field: "Flags",
+            found: flags,
+        });
+    }
+    //TODO: handle entry
+    let _entry = cli_header.read_u32()?;
+    let (metadata, metadata_offset) = image.read_rva(metadata_rva, metadata_size)?;
+    Metadata::parse(metadata, metadata_offset)
+}
+fn load_managed_data(image: &Image, runtime: &mut Runtime) -> Result<(), ImportError> {
+    let metadata = load_metadata(image)?;
+    let tables = Tables::parse(metadata.tables(), metadata.tables_offset())?;
+    let resolver = MetadataResolver::new(&metadata, &tables)?;
+    if !runtime.begin_assembly_load(resolver.name) {
+        return Ok(());
+    }
+    let loaded = load_dependencies(&resolver, runtime).and_then(|_| {
+        let methods = load_methods(image, &resolver, runtime)?;
+        let statics = load_statics(image, &resolver)?;
+        let enums = load_enums(&resolver)?;
+        Ok((
+            methods,
+            statics,
+            enums,
+            load_attributes(&resolver, runtime)?,
+        ))
+    });
+    match loaded {
+        Ok(((methods, skipped), statics, enums, attributes)) => {
+            for (method, path) in methods {
+                runtime.add_method(method, path);
+            }
+            for method in skipped {
+                runtime.add_skipped_method(method);
+            }
+            for (class, statics) in statics {
+                runtime.add_class_statics(class, statics);
+            }
+            for (class, underlying) in enums {
+                runtime.add_enum_type(class, underlying);
+            }
+            for (parent, attribute) in attributes {
+                match parent {
+                    AttributeParent::Class(class) => runtime.add_class_attribute(class, attribute),
+                    AttributeParent::Method(method) => {
+                        runtime.add_method_attribute(method, attribute)
+                    }
+                }
+            }
+            Ok(())
+        }
+        Err(error) => {
+            //Nothing was added, so the assembly can be loaded again once the error is fixed.
+            runtime.abort_assembly_load(resolver.name);
+            Err(error)
+        }
+    }
+}
+/// Methods imported from an assembly, and the ones skipped while importing it.
+type LoadedMethods = (Vec<(Method, MethodPath)>, Vec<SkippedMethod>);
+/// Imports all static methods with bodies. They are only added to the runtime once all of them are imported.
+/// Methods the runtime can't represent yet, or that call methods it can't find, are skipped,
+/// together with the methods of this assembly calling them.
+fn load_methods(
+    image: &Image,
+    resolver: &MetadataResolver,
+    runtime: &Runtime,
+) -> Result<LoadedMethods, ImportError> {
+    let tables = resolver.tables;
+    let mut methods = Vec::new();
+    let mut skipped = Vec::new();
+    for method_def in 1..=tables.method_defs.len() as RowIndex {
+        let method = get_row(&tables.method_defs, method_def)?;
+        //Abstract, extern and runtime-implemented methods have no body. Only static methods are supported for now.
+        if method.rva == 0 || !method.is_static() {
+            continue;
+        }
+        let body = load_method_body(image, method.rva)?;
+        let (class, name) = resolver.method_def_name(method_def)?;
+        match load_method(&body, method_def, resolver, runtime) {
+            Ok((method, ops, path)) => methods.push((method, ops, path)),
+            Err(error) if error.is_unsupported() => skipped.push(SkippedMethod {
+                class,
+                name: name.to_owned(),
+                error,
+            }),
+            Err(error) => return Err(error),
+        }
+    }
+    //Calls to skipped methods can't be compiled, so their callers are skipped too, until no such calls are left.
+    let mut skipped_paths: Vec<MethodPath> = skipped
+        .iter()
+        .filter_map(|method| match &method.error {
+            ImportError::InvalidMethodBody { method, .. }
+            | ImportError::InvalidMethod(method, _) => Some(method.clone()),
+            _ => None,
+        })
+        .collect();
+    while let Some((caller, callee)) =
+        methods.iter().enumerate().find_map(|(index, (_, ops, _))| {
+            ops.iter().find_map(|op| match op {
+                OpKind::Call(callee, _) if skipped_paths.contains(callee) => {
+                    Some((index, callee.clone()))
+                }
+                _ => None,
+            })
+        })
+    {
+        let (_, _, path) = methods.remove(caller);
+        skipped.push(SkippedMethod {
+            class: ClassPath::new(path.assembly_name(), path.namespace(), path.class_name()),
+            name: path.method_name().to_owned(),
+            error: ImportError::UnresolvedMethod(callee),
+        });
+        skipped_paths.push(path);
+    }
+    let methods = methods
+        .into_iter()
+        .map(|(method, _, path)| (method, path))
+        .collect();
+    Ok((methods, skipped))
+}
+/// Imports the body of static method `method_def`, returning it together with its ops, which are used to find its calls.
+fn load_method(
+    body: &MethodBody,
+    method_def: RowIndex,
+    resolver: &MetadataResolver,
+    runtime: &Runtime,
+) -> Result<(Method, Vec<OpKind>, MethodPath), ImportError> {
+    let (path, sig) = resolver.method_def(method_def)?;
+    let (ops, regions, locals) =
+        load_locals(resolver.metadata, resolver.tables, body.local_var_sig())
+            .and_then(|locals| {
+                let (ops, regions) = load_ops(body, resolver)?;
+                check_calls(&ops, resolver.name, runtime)?;
+                Ok((ops, regions, locals))
+            })
+            .map_err(|error| ImportError::InvalidMethodBody {
+                method: path.clone(),
+                offset: body.code_offset(),
+                error: Box::new(error),
+            })?;
+    let method = Method::from_ops_with_regions(sig, &ops, &locals, regions)
+        .map_err(|err| ImportError::InvalidMethod(path.clone(), err))?;
+    Ok((method, ops, path))
+}
+/// Allocates the static fields of all types, copying the initial data of fields mapped onto the image.
+fn load_statics(
+    image: &Image,
+    resolver: &MetadataResolver,
+) -> Result<Vec<(ClassPath, ClassStatics)>, ImportError> {
+    let tables = resolver.tables;
+    let mut classes = Vec::new();
+    for type_def in 1..=tables.type_defs.len() as RowIndex {
+        let mut statics = ClassStatics::new();
+        for field in tables.type_fields(type_def) {
+            let row = get_row(&tables.fields, field)?;
+            if !row.is_static() || row.is_literal() {
+                continue;
+            }
+            let (blob, offset) = load_blob(resolver.metadata, row.signature)?;
+            let size = resolver.type_size(&decode_field(blob, offset)?);
+            let data = match (tables.field_rva(field), size) {
+                (Some(rva), Some(size)) => image.read_rva(rva, size)?.0.into(),
+                (Some(_), None) => {
+                    return Err(ImportError::UnsupportedType("static data of unknown size"))
+                }
+                (None, Some(size)) => zeroed_static(resolver.string(row.name), size)?,
+                //Fields of types without a known layout can't be accessed by the IR yet.
+                (None, None) => continue,
+            };
+            statics.add_field(resolver.string(row.name), data);
+        }
+        if !statics.is_empty() {
+            let (namespace, class_name) = resolver.type_def_name(type_def)?;
+            classes.push((
+                ClassPath::new(resolver.name, namespace, class_name),
+                statics,
+            ));
+        }
+    }
+    Ok(classes)
+}
+/// Allocates the zeroed storage of static field `name`.
+fn zeroed_static(name: &str, size: u32) -> Result<Box<[u8]>, ImportError> {
+    let too_large = || ImportError::StaticFieldTooLarge {
+        name: name.to_owned(),
+        size,
+    };
+    if size > MAX_STATIC_FIELD_SIZE {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    data.try_reserve_exact(size as usize)
+        .map_err(|_| too_large())?;
+    data.resize(size as usize, 0);
+    Ok(data.into())
+}
+/// Finds the enums defined by the assembly, together with their underlying types.
+fn load_enums(resolver: &MetadataResolver) -> Result<Vec<(ClassPath, ElementType)>, ImportError> {
+    let tables = resolver.tables;
+    let mut enums = Vec::new();
+    for type_def in 1..=tables.type_defs.len() as RowIndex {
+        let extends = get_row(&tables.type_defs, type_def)?.extends;
+        //Interfaces and `System.Object` extend nothing.
+        if extends.is_null() {
+            continue;
+        }
+        let base = resolver.resolve_type(extends)?;
+        if base.namespace() != "System" || base.class_name() != "Enum" {
+            continue;
+        }
+        if let Some(underlying) = resolver.enum_underlying_type(type_def) {
+            let (namespace, class_name) = resolver.type_def_name(type_def)?;
+            enums.push((
+                ClassPath::new(resolver.name, namespace, class_name),
+                underlying,
+            ));
+        }
+    }
+    Ok(enums)
+}
+/// A type or method an attribute is applied to.
+enum AttributeParent {
+    Class(ClassPath),
+    Method(MethodPath),
+}
+/// Decodes the custom attributes applied to types and methods. Attributes of other metadata, such as the assembly itself, are skipped,
+/// and so are attributes using something the runtime does not support yet.
+fn load_attributes(
+    resolver: &MetadataResolver,
+    runtime: &Runtime,
+) -> Result<Vec<(AttributeParent, CustomAttribute)>, ImportError> {
+    let types = AttributeTypes { resolver, runtime };
+    let mut attributes = Vec::new();
+    for row in &resolver.tables.custom_attributes {
+        let parent = match row.parent.table() {
+            Some(TableKind::TypeDef) => AttributeParent::Class(resolver.resolve_type(row.parent)?),
+            Some(TableKind::MethodDef) => match resolver.method_def(row.parent.row()) {
+                Ok((path, _)) => AttributeParent::Method(path),
+                //Methods the IR can't represent can't be looked up either.
+                Err(ImportError::UnsupportedType(_)) => continue,
+                Err(error) => return Err(error),
+            },
+            _ => continue,
+        };
+        let attribute =
+            resolver
+                .attribute_constructor(row.kind)
+                .and_then(|(class, constructor)| {
+                    let (blob, offset) = load_blob(resolver.metadata, row.value)?;
+                    attribute::decode(blob, offset, class, constructor.params(), &types)
+                });
+        match attribute {
+            Ok(attribute) => attributes.push((parent, attribute)),
+            Err(error) if error.is_unsupported() => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    //`[MethodImpl]` is stored in the flags of a method rather than in the CustomAttribute table, so it is recreated from them.
+    for method_def in 1..=resolver.tables.method_defs.len() as RowIndex {
+        let options = get_row(&resolver.tables.method_defs, method_def)?.impl_options();
+        if options == 0 {
+            continue;
+        }
+        let path = match resolver.method_def(method_def) {
+            Ok((path, _)) => path,
+            Err(ImportError::UnsupportedType(_)) => continue,
+            Err(error) => return Err(error),
+        };
+        let namespace = "System.Runtime.CompilerServices";
+        let options = AttributeValue::Enum(
+            ClassPath::new(types.core_library(), namespace, "MethodImplOptions"),
+            i64::from(options),
+        );
+        let class = ClassPath::new(types.core_library(), namespace, "MethodImplAttribute");
+        attributes.push((
+            AttributeParent::Method(path),
+            CustomAttribute::new(class, vec![options], Vec::new()),
+        ));
+    }
+    Ok(attributes)
+}
+/// Loads the assemblies referenced by the one being imported, if they are not loaded yet.
+/// Dependencies the runtime can't find are skipped, since most references are only used by attributes.
+fn load_dependencies(
+    resolver: &MetadataResolver,
     runtime: &mut Runtime,
-    text_offset:u32,
-    text_rva:u32,
 ) -> Result<(), ImportError> {
-    //clr loader stub
-    cil_data.skip(8).unwrap();
-    let mut clr_header_size = [0; 4];
-    cil_data.read_exact(&mut clr_header_size).unwrap();
-    let cil_header_size = u32::from_le_bytes(clr_header_size);
-    assert_eq!(cil_header_size, 0x48);
-    let mut clr_major = [0; 2];
-    cil_data.read_exact(&mut clr_major).unwrap();
-    let clr_major = u16::from_le_bytes(clr_major);
-    assert_eq!(clr_major, 2);
-    let mut clr_minor = [0; 2];
-    cil_data.read_exact(&mut clr_minor).unwrap();
-    let clr_minor = u16::from_le_bytes(clr_minor);
-    assert_eq!(clr_minor, 5);
-    let mut rva = [0; 4];
-    cil_data.read_exact(&mut rva).unwrap();
-    let rva = u32::from_le_bytes(rva);
-    let mut metadata_header_size = [0; 4];
-    cil_data.read_exact(&mut metadata_header_size).unwrap();
-    let metadata_header_size = u32::from_le_bytes(metadata_header_size);
-    //TODO:Check flags!
-    let mut flags = [0; 4];
-    cil_data.read_exact(&mut flags).unwrap();
-    //TODO: handle entry
-    let mut entry = [0; 4];
-    cil_data.read_exact(&mut entry).unwrap();
-    //Unused CIL header data
-    cil_data.skip_to(0x50);
-    //Strong name hash
-    cil_data.skip_to(0xC0);
-    println!("metatdata_rva:{rva}, text_rva:{text_rva}");
-    let metadata_start = rva - text_rva + text_offset;
-    println!("Metadata start {metadata_start} curr_offset:{}",cil_data.curr_offset());
-    let mut method_bodys_len = metadata_start as usize - cil_data.curr_offset();
-    let mut method_bodys = vec![0;method_bodys_len];
-    cil_data.read_exact(&mut method_bodys);
-    load_ops(&method_bodys);
-    assert_eq!(method_bodys[method_bodys.len() - 1],0x2A);
-    println!("Methods take {} bytes!",method_bodys_len);
-    todo!();
-}
-fn load_ops(data:&[u8]){
-    let mut index = 0;
-    while index < data.len(){
-        let curr = data[index];
-        let op = match curr{
-            _=>todo!("Unhandled op:0x{curr:x}"),
+    for assembly_ref in &resolver.tables.assembly_refs {
+        let name = resolver.string(assembly_ref.name);
+        if runtime.is_assembly_loaded(name) {
+            continue;
+        }
+        let Some(image) = runtime.resolve_assembly(name).map_err(ImportError::Io)? else {
+            continue;
+        };
+        import_assembly_bytes(&image, runtime).map_err(|error| ImportError::InvalidDependency {
+            name: name.to_owned(),
+            error: Box::new(error),
+        })?;
+    }
+    Ok(())
+}
+/// Checks that calls into other assemblies target methods the runtime knows about.
+fn check_calls(ops: &[OpKind], assembly: &str, runtime: &Runtime) -> Result<(), ImportError> {
+    for op in ops {
+        let OpKind::Call(path, _) = op else {
+            continue;
+        };
+        if path.assembly_name() == assembly {
+            continue;
+        }
+        if !runtime.is_assembly_loaded(path.assembly_name()) {
+            return Err(ImportError::UnresolvedAssembly(
+                path.assembly_name().to_owned(),
+            ));
+        }
+        if !runtime.has_method(path) {
+            return Err(ImportError::UnresolvedMethod(path.clone()));
+        }
+    }
+    Ok(())
+}
+pub(crate) fn load_method_body<'a>(
+    image: &'a Image,
+    rva: u32,
+) -> Result<MethodBody<'a>, ImportError> {
+    let (data, offset) = image.data_at(rva)?;
+    MethodBody::parse(data, offset)
+}
+/// Gets the blob at `index`, together with its file offset.
+fn load_blob<'a>(
+    metadata: &Metadata<'a>,
+    index: BlobIndex,
+) -> Result<(&'a [u8], usize), ImportError> {
+    metadata
+        .blobs()
+        .locate(index)
+        .ok_or(ImportError::InvalidBlobIndex(index))
+}
+fn load_signature(metadata: &Metadata, signature: BlobIndex) -> Result<Signature, ImportError> {
+    let (blob, offset) = load_blob(metadata, signature)?;
+    MethodSig::decode(blob, offset)?.to_signature()
+}
+/// Loads types of locals described by the StandAloneSig `local_var_sig`.
+pub(crate) fn load_locals(
+    metadata: &Metadata,
+    tables: &Tables,
+    local_var_sig: Option<Token>,
+) -> Result<Vec<Type>, ImportError> {
+    let Some(token) = local_var_sig else {
+        return Ok(Vec::new());
+    };
+    if token.table() != Some(TableKind::StandAloneSig) {
+        return Err(ImportError::UnresolvedToken(token.raw()));
+    }
+    let sig = get_row(&tables.stand_alone_sigs, token.row())?;
+    let (blob, offset) = load_blob(metadata, sig.signature)?;
+    local_types(blob, offset)
+}
+/// Converts the code and exception handling clauses of `body` to IR.
+fn load_ops(
+    body: &MethodBody,
+    resolver: &impl TokenResolver,
+) -> Result<(Vec<OpKind>, Vec<ProtectedRegion>), ImportError> {
+    let instructions = cil::decode(body.code(), body.code_offset())?;
+    let ops = instructions
+        .iter()
+        .map(|instruction| instruction.to_op_kind(resolver))
+        .collect::<Result<_, _>>()?;
+    let regions = body
+        .eh_clauses()
+        .iter()
+        .map(|clause| load_region(clause, &instructions, body.code().len(), resolver))
+        .collect::<Result<_, _>>()?;
+    Ok((ops, regions))
+}
+fn load_region(
+    clause: &EhClause,
+    instructions: &[Instruction],
+    code_size: usize,
+    resolver: &impl TokenResolver,
+) -> Result<ProtectedRegion, ImportError> {
+    let index = |il_offset: u32| {
+        //Ranges may end at the end of the code.
+        if il_offset as usize == code_size {
+            return Ok(instructions.len());
+        }
+        cil::index_of(instructions, il_offset).ok_or(ImportError::InvalidRegionOffset { il_offset })
+    };
+    let range = |(start, end)| Ok::<_, ImportError>(index(start)?..index(end)?);
+    let handler = match clause.kind() {
+        ClauseKind::Catch(token) => Handler::Catch(resolver.resolve_type(token)?),
+        ClauseKind::Filter(filter) => Handler::Filter(index(filter)?),
+        ClauseKind::Finally => Handler::Finally,
+        ClauseKind::Fault => Handler::Fault,
+    };
+    Ok(ProtectedRegion::new(
+        range(clause.try_range())?,
+        range(clause.handler_range())?,
+        handler,
+    ))
+}
+/// Resolves tokens against the metadata of the assembly being imported.
+pub(crate) struct MetadataResolver<'a> {
+    name: &'a str,
+    metadata: &'a Metadata<'a>,
+    tables: &'a Tables,
+}
+impl<'a> MetadataResolver<'a> {
+    pub(crate) fn new(metadata: &'a Metadata<'a>, tables: &'a Tables) -> Result<Self, ImportError> {
+        let assembly = tables
+            .assemblies
+            .first()
+            .ok_or(ImportError::MissingAssemblyManifest)?;
+        Ok(Self {
+            name: metadata.strings().get(assembly.name).unwrap_or_default(),
+            metadata,
+            tables,
+        })
+    }
+    fn string(&self, index: StringIndex) -> &'a str {
+        self.metadata.strings().get(index).unwrap_or_default()
+    }
+    /// Underlying type of enum `type_def`, which is the type of its only instance field.
+    fn enum_underlying_type(&self, type_def: RowIndex) -> Option<ElementType> {
+        self.tables.type_fields(type_def).find_map(|field| {
+            let field = get_row(&self.tables.fields, field).ok()?;
+            if field.is_static() {
+                return None;
+            }
+            let (blob, offset) = load_blob(self.metadata, field.signature).ok()?;
+            decode_field(blob, offset).ok()
+        })
+    }
+    /// TypeDef of the class `name` in `namespace`, if this assembly defines one.
+    fn find_type_def(&self, namespace: &str, name: &str) -> Option<RowIndex> {
+        (1..=self.tables.type_defs.len() as RowIndex)
+            .find(|type_def| self.type_def_name(*type_def).ok() == Some((namespace, name)))
+    }
+    /// Namespace and name of TypeDef `type_def`.
+    fn type_def_name(&self, type_def: RowIndex) -> Result<(&'a str, &'a str), ImportError> {
+        let class = get_row(&self.tables.type_defs, type_def)?;
+        Ok((self.string(class.namespace), self.string(class.name)))
+    }
+    /// Assembly, namespace and name of TypeRef `type_ref`.
+    fn type_ref_name(
+        &self,
+        type_ref: RowIndex,
+    ) -> Result<(&'a str, &'a str, &'a str), ImportError> {
+        let class = get_row(&self.tables.type_refs, type_ref)?;
+        let mut scope = class.resolution_scope;
+        //Nested types live in the assembly of the outermost type. Bounded, so that cyclic scopes can't hang the importer.
+        for _ in 0..=self.tables.type_refs.len() {
+            let assembly = match scope.table() {
+                Some(TableKind::TypeRef) => {
+                    scope = get_row(&self.tables.type_refs, scope.row())?.resolution_scope;
+                    continue;
+                }
+                Some(TableKind::AssemblyRef) => {
+                    self.string(get_row(&self.tables.assembly_refs, scope.row())?.name)
+                }
+                Some(TableKind::Module) => self.name,
+                _ => break,
+            };
+            return Ok((
+                assembly,
+                self.string(class.namespace),
+                self.string(class.name),
+            ));
+        }
+        Err(ImportError::UnresolvedToken(scope.raw()))
+    }
+    /// Size of a value of type `element`. Valuetypes only have a known size if they have an explicit layout.
+    fn type_size(&self, element: &ElementType) -> Option<u32> {
+        match element {
+            ElementType::ValueType(token) if token.table() == Some(TableKind::TypeDef) => self
+                .tables
+                .class_layout(token.row())
+                .map(|layout| layout.class_size)
+                .filter(|size| *size != 0),
+            _ => element.size(),
+        }
+    }
+    /// Owning class and name of MethodDef `method_def`.
+    pub(crate) fn method_def_name(
+        &self,
+        method_def: RowIndex,
+    ) -> Result<(ClassPath, &'a str), ImportError> {
+        let method = get_row(&self.tables.method_defs, method_def)?;
+        let owner = self
+            .tables
+            .method_owner(method_def)
+            .ok_or(ImportError::InvalidRowIndex {
+                table: TableKind::MethodDef as u8,
+                row: method_def,
+            })?;
+        let (namespace, class_name) = self.type_def_name(owner)?;
+        Ok((
+            ClassPath::new(self.name, namespace, class_name),
+            self.string(method.name),
+        ))
+    }
+    pub(crate) fn method_def(
+        &self,
+        method_def: RowIndex,
+    ) -> Result<(MethodPath, Signature), ImportError> {
+        let method = get_row(&self.tables.method_defs, method_def)?;
+        let (class, name) = self.method_def_name(method_def)?;
+        let sig = load_signature(self.metadata, method.signature)?;
+        let path = MethodPath::new(self.name, class.namespace(), class.class_name(), name, &sig);
+        Ok((path, sig))
+    }
+    /// Class and signature of attribute constructor `token`.
+    fn attribute_constructor(&self, token: Token) -> Result<(ClassPath, MethodSig), ImportError> {
+        let (class, signature) = match token.table() {
+            Some(TableKind::MethodDef) => {
+                let owner =
+                    self.tables
+                        .method_owner(token.row())
+                        .ok_or(ImportError::InvalidRowIndex {
+                            table: TableKind::MethodDef as u8,
+                            row: token.row(),
+                        })?;
+                let method = get_row(&self.tables.method_defs, token.row())?;
+                (
+                    self.resolve_type(Token::new(TableKind::TypeDef, owner))?,
+                    method.signature,
+                )
+            }
+            Some(TableKind::MemberRef) => {
+                let member = get_row(&self.tables.member_refs, token.row())?;
+                (self.resolve_type(member.class)?, member.signature)
+            }
+            _ => return Err(ImportError::UnresolvedToken(token.raw())),
         };
-        println!("op:{op:?}");
-        index += 1;
+        let (blob, offset) = load_blob(self.metadata, signature)?;
+        Ok((class, MethodSig::decode(blob, offset)?))
+    }
+    fn member_ref(&self, member_ref: RowIndex) -> Result<(MethodPath, Signature), ImportError> {
+        let member = get_row(&self.tables.member_refs, member_ref)?;
+        let (assembly, namespace, class_name) = match member.class.table() {
+            Some(TableKind::TypeDef) => {
+                let (namespace, class_name) = self.type_def_name(member.class.row())?;
+                (self.name, namespace, class_name)
+            }
+            Some(TableKind::TypeRef) => self.type_ref_name(member.class.row())?,
+            _ => return Err(ImportError::UnresolvedToken(member.class.raw())),
+        };
+        let sig = load_signature(self.metadata, member.signature)?;
+        let path = MethodPath::new(
+            assembly,
+            namespace,
+            class_name,
+            self.string(member.name),
+            &sig,
+        );
+        Ok((path, sig))
+    }
+}
+impl TokenResolver for MetadataResolver<'_> {
+    fn resolve_method(&self, token: Token) -> Result<(MethodPath, Signature), ImportError> {
+        match token.table() {
+            Some(TableKind::MethodDef) => self.method_def(token.row()),
+            Some(TableKind::MemberRef) => self.member_ref(token.row()),
+            _ => Err(ImportError::UnresolvedToken(token.raw())),
+        }
+    }
+    fn resolve_type(&self, token: Token) -> Result<ClassPath, ImportError> {
+        match token.table() {
+            Some(TableKind::TypeDef) => {
+                let (namespace, class_name) = self.type_def_name(token.row())?;
+                Ok(ClassPath::new(self.name, namespace, class_name))
+            }
+            Some(TableKind::TypeRef) => {
+                let (assembly, namespace, class_name) = self.type_ref_name(token.row())?;
+                Ok(ClassPath::new(assembly, namespace, class_name))
+            }
+            _ => Err(ImportError::UnresolvedToken(token.raw())),
+        }
+    }
+    fn resolve_string(&self, token: Token) -> Result<Box<[u16]>, ImportError> {
+        if !token.is_user_string() {
+            return Err(ImportError::UnresolvedToken(token.raw()));
+        }
+        self.metadata
+            .user_strings()
+            .get(token.row())
+            .ok_or(ImportError::InvalidUserStringIndex(token.row()))
+    }
+}
+/// Resolves the types used by attributes of an assembly, looking up enums of its dependencies among the loaded ones.
+struct AttributeTypes<'r, 'a> {
+    resolver: &'r MetadataResolver<'a>,
+    runtime: &'r Runtime,
+}
+impl TokenResolver for AttributeTypes<'_, '_> {
+    fn resolve_method(&self, token: Token) -> Result<(MethodPath, Signature), ImportError> {
+        self.resolver.resolve_method(token)
+    }
+    fn resolve_type(&self, token: Token) -> Result<ClassPath, ImportError> {
+        self.resolver.resolve_type(token)
+    }
+    fn resolve_string(&self, token: Token) -> Result<Box<[u16]>, ImportError> {
+        self.resolver.resolve_string(token)
+    }
+}
+impl AttributeResolver for AttributeTypes<'_, '_> {
+    fn assembly_name(&self) -> &str {
+        self.resolver.name
+    }
+    fn core_library(&self) -> &str {
+        let resolver = self.resolver;
+        resolver
+            .tables
+            .assembly_refs
+            .iter()
+            .map(|assembly_ref| resolver.string(assembly_ref.name))
+            .find(|name| CORE_LIBRARIES.contains(name))
+            //An assembly referencing no core library is the core library itself.
+            .unwrap_or(resolver.name)
+    }
+    fn defines_type(&self, namespace: &str, name: &str) -> bool {
+        self.resolver.find_type_def(namespace, name).is_some()
+    }
+    fn enum_type(&self, class: &ClassPath) -> Option<ElementType> {
+        if class.assembly_name() != self.resolver.name {
+            return self.runtime.enum_type(class).cloned();
+        }
+        let type_def = self
+            .resolver
+            .find_type_def(class.namespace(), class.class_name())?;
+        self.resolver.enum_underlying_type(type_def)
     }
 }
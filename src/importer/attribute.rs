@@ -0,0 +1,473 @@
+use super::assembly::ImportError;
+use super::cil::TokenResolver;
+use super::signature::{primitive, ElementType};
+use super::slice_reader::SliceReader;
+use crate::type_system::attribute::{AttributeValue, CustomAttribute, NamedArgument};
+use crate::type_system::paths::ClassPath;
+// Custom attribute blobs, as described in ECMA-335 II.23.3
+const PROLOG: u16 = 0x0001;
+const NAMED_FIELD: u8 = 0x53;
+const NAMED_PROPERTY: u8 = 0x54;
+const SERIALIZATION_TYPE_STRING: u8 = 0x0E;
+const SERIALIZATION_TYPE_SZARRAY: u8 = 0x1D;
+const SERIALIZATION_TYPE_TYPE: u8 = 0x50;
+const SERIALIZATION_TYPE_TAGGED_OBJECT: u8 = 0x51;
+const SERIALIZATION_TYPE_ENUM: u8 = 0x55;
+const NULL_STRING: u8 = 0xFF;
+const NULL_ARRAY: u32 = u32::MAX;
+/// Boxed values may contain arrays of boxed values, so nesting must be bounded.
+const MAX_NESTING: u32 = 16;
+/// Resolves the types referenced by attribute blobs.
+pub(crate) trait AttributeResolver: TokenResolver {
+    /// Name of the assembly being imported.
+    fn assembly_name(&self) -> &str;
+    /// Name of the core library referenced by the assembly being imported, which defines types like `System.Char`.
+    fn core_library(&self) -> &str;
+    /// Checks if the assembly being imported defines class `name` in `namespace`.
+    fn defines_type(&self, namespace: &str, name: &str) -> bool;
+    /// Underlying type of enum `class`. Returns `None` for enums of assemblies that are not loaded.
+    fn enum_type(&self, class: &ClassPath) -> Option<ElementType>;
+}
+/// Type of an attribute argument.
+#[derive(Debug, Clone, PartialEq)]
+enum ArgType {
+    Primitive(ElementType),
+    String,
+    Type,
+    Object,
+    Enum(ClassPath, ElementType),
+    SzArray(Box<ArgType>),
+}
+impl ArgType {
+    /// Converts the type of a constructor parameter.
+    fn from_param(
+        param: &ElementType,
+        resolver: &impl AttributeResolver,
+    ) -> Result<Self, ImportError> {
+        Ok(match param {
+            ElementType::String => Self::String,
+            ElementType::Object => Self::Object,
+            ElementType::Class(token) => {
+                let class = resolver.resolve_type(*token)?;
+                if class.namespace() != "System" || class.class_name() != "Type" {
+                    return Err(ImportError::UnsupportedType("attribute argument"));
+                }
+                Self::Type
+            }
+            ElementType::ValueType(token) => enum_type(resolver.resolve_type(*token)?, resolver)?,
+            ElementType::SzArray(element) => {
+                Self::SzArray(Box::new(Self::from_param(element, resolver)?))
+            }
+            //Native integers can't be attribute arguments.
+            ElementType::Bool
+            | ElementType::Char
+            | ElementType::I1
+            | ElementType::U1
+            | ElementType::I2
+            | ElementType::U2
+            | ElementType::I4
+            | ElementType::U4
+            | ElementType::I8
+            | ElementType::U8
+            | ElementType::R4
+            | ElementType::R8 => Self::Primitive(param.clone()),
+            _ => return Err(ImportError::UnsupportedType("attribute argument")),
+        })
+    }
+}
+fn enum_type(class: ClassPath, resolver: &impl AttributeResolver) -> Result<ArgType, ImportError> {
+    //Guessing the underlying type would misread every argument after the enum.
+    let underlying = resolver
+        .enum_type(&class)
+        .ok_or(ImportError::UnsupportedType(
+            "enum of unknown underlying type",
+        ))?;
+    Ok(ArgType::Enum(class, underlying))
+}
+/// Decodes an attribute blob, which begins at file offset `base`. `params` are the parameters of the constructor of `class`.
+pub(crate) fn decode(
+    blob: &[u8],
+    base: usize,
+    class: ClassPath,
+    params: &[ElementType],
+    resolver: &impl AttributeResolver,
+) -> Result<CustomAttribute, ImportError> {
+    //Attributes without arguments may have no blob at all.
+    if blob.is_empty() && params.is_empty() {
+        return Ok(CustomAttribute::new(class, Vec::new(), Vec::new()));
+    }
+    let mut reader = SliceReader::at(blob, base);
+    let prolog = reader.read_u16()?;
+    if prolog != PROLOG {
+        return Err(ImportError::UnexpectedValue {
+            offset: base,
+            field: "Prolog",
+            expected: u32::from(PROLOG),
+            found: u32::from(prolog),
+        });
+    }
+    let mut fixed_args = Vec::with_capacity(params.len());
+    for param in params {
+        let arg_type = ArgType::from_param(param, resolver)?;
+        fixed_args.push(read_value(&mut reader, &arg_type, resolver, 0)?);
+    }
+    let named_count = reader.read_u16()?;
+    let mut named_args = Vec::new();
+    for _ in 0..named_count {
+        let offset = reader.file_offset();
+        let is_property = match reader.read_u8()? {
+            NAMED_FIELD => false,
+            NAMED_PROPERTY => true,
+            found => return Err(ImportError::InvalidSignature { offset, found }),
+        };
+        let arg_type = read_arg_type(&mut reader, resolver, 0)?;
+        let offset = reader.file_offset();
+        let name = read_ser_string(&mut reader)?.ok_or(ImportError::InvalidSignature {
+            offset,
+            found: NULL_STRING,
+        })?;
+        let value = read_value(&mut reader, &arg_type, resolver, 0)?;
+        named_args.push(NamedArgument::new(name, is_property, value));
+    }
+    Ok(CustomAttribute::new(class, fixed_args, named_args))
+}
+/// Reads a FieldOrPropType, which describes the type of named and boxed arguments.
+fn read_arg_type(
+    reader: &mut SliceReader,
+    resolver: &impl AttributeResolver,
+    depth: u32,
+) -> Result<ArgType, ImportError> {
+    if depth > MAX_NESTING {
+        return Err(ImportError::UnsupportedType(
+            "deeply nested attribute argument",
+        ));
+    }
+    let offset = reader.file_offset();
+    let element = reader.read_u8()?;
+    if let Some(primitive) = primitive(element) {
+        return Ok(ArgType::Primitive(primitive));
+    }
+    Ok(match element {
+        SERIALIZATION_TYPE_STRING => ArgType::String,
+        SERIALIZATION_TYPE_TYPE => ArgType::Type,
+        SERIALIZATION_TYPE_TAGGED_OBJECT => ArgType::Object,
+        SERIALIZATION_TYPE_SZARRAY => {
+            ArgType::SzArray(Box::new(read_arg_type(reader, resolver, depth + 1)?))
+        }
+        SERIALIZATION_TYPE_ENUM => {
+            let offset = reader.file_offset();
+            let name = read_ser_string(reader)?.ok_or(ImportError::InvalidSignature {
+                offset,
+                found: NULL_STRING,
+            })?;
+            enum_type(parse_type_name(&name, resolver), resolver)?
+        }
+        _ => {
+            return Err(ImportError::InvalidSignature {
+                offset,
+                found: element,
+            })
+        }
+    })
+}
+fn read_value(
+    reader: &mut SliceReader,
+    arg_type: &ArgType,
+    resolver: &impl AttributeResolver,
+    depth: u32,
+) -> Result<AttributeValue, ImportError> {
+    Ok(match arg_type {
+        ArgType::Primitive(element) => read_primitive(reader, element)?,
+        ArgType::String => AttributeValue::String(read_ser_string(reader)?),
+        ArgType::Type => AttributeValue::Type(
+            read_ser_string(reader)?.map(|name| parse_type_name(&name, resolver)),
+        ),
+        ArgType::Object => {
+            let boxed = read_arg_type(reader, resolver, depth + 1)?;
+            read_value(reader, &boxed, resolver, depth + 1)?
+        }
+        ArgType::Enum(class, underlying) => {
+            let value = match read_primitive(reader, underlying)? {
+                AttributeValue::I8(value) => i64::from(value),
+                AttributeValue::U8(value) => i64::from(value),
+                AttributeValue::I16(value) => i64::from(value),
+                AttributeValue::U16(value) => i64::from(value),
+                AttributeValue::I32(value) => i64::from(value),
+                AttributeValue::U32(value) => i64::from(value),
+                AttributeValue::I64(value) => value,
+                AttributeValue::U64(value) => value as i64,
+                _ => return Err(ImportError::UnsupportedType("enum underlying type")),
+            };
+            AttributeValue::Enum(class.clone(), value)
+        }
+        ArgType::SzArray(element) => {
+            let offset = reader.file_offset();
+            let count = reader.read_u32()?;
+            if count == NULL_ARRAY {
+                return Ok(AttributeValue::Array(None));
+            }
+            //Each element takes at least one byte, so this can't allocate more than the blob holds.
+            if count as usize > reader.remaining() {
+                return Err(ImportError::UnexpectedEndOfData {
+                    offset: offset + 4 + reader.remaining(),
+                });
+            }
+            let mut elements = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                elements.push(read_value(reader, element, resolver, depth + 1)?);
+            }
+            AttributeValue::Array(Some(elements))
+        }
+    })
+}
+fn read_primitive(
+    reader: &mut SliceReader,
+    element: &ElementType,
+) -> Result<AttributeValue, ImportError> {
+    Ok(match element {
+        ElementType::Bool => AttributeValue::Bool(reader.read_u8()? != 0),
+        ElementType::Char => AttributeValue::Char(reader.read_u16()?),
+        ElementType::I1 => AttributeValue::I8(reader.read_u8()? as i8),
+        ElementType::U1 => AttributeValue::U8(reader.read_u8()?),
+        ElementType::I2 => AttributeValue::I16(reader.read_u16()? as i16),
+        ElementType::U2 => AttributeValue::U16(reader.read_u16()?),
+        ElementType::I4 => AttributeValue::I32(reader.read_u32()? as i32),
+        ElementType::U4 => AttributeValue::U32(reader.read_u32()?),
+        ElementType::I8 => AttributeValue::I64(reader.read_u64()? as i64),
+        ElementType::U8 => AttributeValue::U64(reader.read_u64()?),
+        ElementType::R4 => AttributeValue::F32(f32::from_bits(reader.read_u32()?)),
+        ElementType::R8 => AttributeValue::F64(f64::from_bits(reader.read_u64()?)),
+        _ => return Err(ImportError::UnsupportedType("attribute argument")),
+    })
+}
+/// Reads a SerString: a UTF-8 string prefixed with its compressed length, or `0xFF` for null.
+fn read_ser_string(reader: &mut SliceReader) -> Result<Option<String>, ImportError> {
+    if reader.peek_u8()? == NULL_STRING {
+        reader.skip(1)?;
+        return Ok(None);
+    }
+    let length = reader.read_compressed_u32()?;
+    let offset = reader.file_offset();
+    let bytes = reader.read_bytes(length as usize)?;
+    let string = std::str::from_utf8(bytes).map_err(|_| ImportError::InvalidUtf8 { offset })?;
+    Ok(Some(string.to_owned()))
+}
+/// Converts an assembly-qualified type name, such as `System.String, System.Runtime, Version=7.0.0.0`, to a class path.
+/// Types named without an assembly live in the assembly being imported if it defines them, and in the core library otherwise (ECMA-335 II.23.3).
+fn parse_type_name(name: &str, resolver: &impl AttributeResolver) -> ClassPath {
+    //Commas inside of brackets separate the arguments of generic types.
+    let mut depth = 0_u32;
+    let split = name.char_indices().find_map(|(index, c)| {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => return Some(index),
+            _ => (),
+        }
+        None
+    });
+    let (type_name, assembly) = match split {
+        Some(split) => {
+            let assembly_name = name[split + 1..].split(',').next().unwrap_or_default();
+            (&name[..split], Some(assembly_name.trim()))
+        }
+        None => (name, None),
+    };
+    let type_name = type_name.trim();
+    //Generic arguments stay a part of the class name.
+    let name_end = type_name.find('[').unwrap_or(type_name.len());
+    let assembly = assembly.unwrap_or_else(|| {
+        //Nested types are defined wherever their outermost type is.
+        let outer = type_name[..name_end].split('+').next().unwrap_or_default();
+        let (namespace, class_name) = outer.rsplit_once('.').unwrap_or(("", outer));
+        if resolver.defines_type(namespace, class_name) {
+            resolver.assembly_name()
+        } else {
+            resolver.core_library()
+        }
+    });
+    //Nested types have no namespace of their own.
+    let (namespace, class_start) = match type_name[..name_end].rfind('+') {
+        Some(nested) => ("", nested + 1),
+        None => match type_name[..name_end].rfind('.') {
+            Some(dot) => (&type_name[..dot], dot + 1),
+            None => ("", 0),
+        },
+    };
+    ClassPath::new(assembly, namespace, &type_name[class_start..])
+}
+#[cfg(test)]
+struct TestTypes;
+#[cfg(test)]
+impl TokenResolver for TestTypes {
+    fn resolve_method(
+        &self,
+        token: super::tables::Token,
+    ) -> Result<(crate::MethodPath, crate::ir::Signature), ImportError> {
+        Err(ImportError::UnresolvedToken(token.raw()))
+    }
+    fn resolve_string(&self, token: super::tables::Token) -> Result<Box<[u16]>, ImportError> {
+        Err(ImportError::UnresolvedToken(token.raw()))
+    }
+    fn resolve_type(&self, token: super::tables::Token) -> Result<ClassPath, ImportError> {
+        match token.raw() {
+            0x0100_0001 => Ok(ClassPath::new("System.Runtime", "System", "Type")),
+            0x0200_0002 => Ok(ClassPath::new("Exports", "Exports", "ExportKind")),
+            0x0100_0003 => Ok(ClassPath::new("Plugins", "Plugins", "PluginKind")),
+            _ => Err(ImportError::UnresolvedToken(token.raw())),
+        }
+    }
+}
+#[cfg(test)]
+impl AttributeResolver for TestTypes {
+    fn assembly_name(&self) -> &str {
+        "Exports"
+    }
+    fn core_library(&self) -> &str {
+        "System.Runtime"
+    }
+    fn defines_type(&self, namespace: &str, name: &str) -> bool {
+        matches!((namespace, name), ("Exports", _) | ("", "Global"))
+    }
+    fn enum_type(&self, class: &ClassPath) -> Option<ElementType> {
+        (class == &ClassPath::new("Exports", "Exports", "ExportKind")).then_some(ElementType::U2)
+    }
+}
+#[test]
+fn fixed_args() {
+    use super::tables::Token;
+    let class = ClassPath::new("Exports", "Exports", "HostExportAttribute");
+    //(string, ExportKind, System.Type, int32[], object)
+    let params = [
+        ElementType::String,
+        ElementType::ValueType(Token::from_raw(0x0200_0002)),
+        ElementType::Class(Token::from_raw(0x0100_0001)),
+        ElementType::SzArray(Box::new(ElementType::I4)),
+        ElementType::Object,
+    ];
+    #[rustfmt::skip]
+    let blob = [
+        0x01, 0x00,
+        0x03, b'a', b'd', b'd',
+        0x02, 0x00,
+        0x0B, b'S', b'y', b's', b't', b'e', b'm', b'.', b'C', b'h', b'a', b'r',
+        0xFF, 0xFF, 0xFF, 0xFF,
+        0x0C, 0x00, 0x00, 0x20, 0x40,
+        0x00, 0x00,
+    ];
+    let attribute =
+        decode(&blob, 0, class.clone(), &params, &TestTypes).expect("Could not decode attribute");
+    assert_eq!(attribute.class(), &class);
+    assert_eq!(
+        attribute.fixed_args(),
+        &[
+            AttributeValue::String(Some("add".into())),
+            AttributeValue::Enum(ClassPath::new("Exports", "Exports", "ExportKind"), 2),
+            AttributeValue::Type(Some(ClassPath::new("System.Runtime", "System", "Char"))),
+            AttributeValue::Array(None),
+            AttributeValue::F32(2.5),
+        ]
+    );
+    assert!(attribute.named_args().is_empty());
+    assert!(matches!(
+        decode(&blob[1..], 0x400, class.clone(), &params, &TestTypes),
+        Err(ImportError::UnexpectedValue {
+            offset: 0x400,
+            field: "Prolog",
+            ..
+        })
+    ));
+    assert!(matches!(
+        decode(&blob[..20], 0, class.clone(), &params, &TestTypes),
+        Err(ImportError::UnexpectedEndOfData { .. })
+    ));
+    //The size of enums of assemblies that are not loaded is unknown.
+    let params = [ElementType::ValueType(Token::from_raw(0x0100_0003))];
+    assert!(matches!(
+        decode(
+            &[0x01, 0x00, 0x01, 0x00, 0x00, 0x00],
+            0,
+            class,
+            &params,
+            &TestTypes
+        ),
+        Err(ImportError::UnsupportedType(
+            "enum of unknown underlying type"
+        ))
+    ));
+}
+#[test]
+fn named_args() {
+    let class = ClassPath::new("System.Runtime", "System", "AttributeUsageAttribute");
+    #[rustfmt::skip]
+    let blob = [
+        0x01, 0x00,
+        0x03, 0x00,
+        // Property AllowMultiple = true
+        0x54, 0x02, 0x0D, b'A', b'l', b'l', b'o', b'w', b'M', b'u', b'l', b't', b'i', b'p', b'l', b'e',
+        0x01,
+        // Field Kinds = new[] { ExportKind.Callback }
+        0x53, 0x1D, 0x55,
+        0x3D, b'E', b'x', b'p', b'o', b'r', b't', b's', b'.', b'E', b'x', b'p', b'o', b'r', b't', b'K', b'i', b'n', b'd',
+        b',', b' ', b'E', b'x', b'p', b'o', b'r', b't', b's', b',', b' ', b'V', b'e', b'r', b's', b'i', b'o', b'n',
+        b'=', b'1', b'.', b'0', b'.', b'0', b'.', b'0', b',', b' ', b'C', b'u', b'l', b't', b'u', b'r', b'e', b'=',
+        b'n', b'e', b'u', b't', b'r', b'a', b'l',
+        0x05, b'K', b'i', b'n', b'd', b's',
+        0x01, 0x00, 0x00, 0x00, 0x02, 0x00,
+        // Property Name = null
+        0x54, 0x0E, 0x04, b'N', b'a', b'm', b'e', 0xFF,
+    ];
+    let attribute = decode(&blob, 0, class, &[], &TestTypes).expect("Could not decode attribute");
+    assert_eq!(attribute.named_args().len(), 3);
+    assert!(attribute.named_args()[0].is_property());
+    assert_eq!(
+        attribute.named_arg("AllowMultiple"),
+        Some(&AttributeValue::Bool(true))
+    );
+    assert!(!attribute.named_args()[1].is_property());
+    assert_eq!(
+        attribute.named_arg("Kinds"),
+        Some(&AttributeValue::Array(Some(vec![AttributeValue::Enum(
+            ClassPath::new("Exports", "Exports", "ExportKind"),
+            2
+        )])))
+    );
+    assert_eq!(
+        attribute.named_arg("Name"),
+        Some(&AttributeValue::String(None))
+    );
+    assert!(attribute.is("System", "AttributeUsageAttribute"));
+    //Named arguments must be tagged as fields or properties.
+    let mut blob = blob;
+    blob[4] = 0x52;
+    assert!(matches!(
+        decode(&blob, 0, ClassPath::new("", "", ""), &[], &TestTypes),
+        Err(ImportError::InvalidSignature {
+            offset: 4,
+            found: 0x52
+        })
+    ));
+}
+#[test]
+fn type_names() {
+    assert_eq!(
+        parse_type_name(
+            "System.Collections.Generic.List`1[[System.Int32, System.Runtime]], System.Collections, Version=7.0.0.0",
+            &TestTypes
+        ),
+        ClassPath::new("System.Collections", "System.Collections.Generic", "List`1[[System.Int32, System.Runtime]]")
+    );
+    assert_eq!(
+        parse_type_name("Exports.Outer+Inner", &TestTypes),
+        ClassPath::new("Exports", "", "Inner")
+    );
+    assert_eq!(
+        parse_type_name("Global", &TestTypes),
+        ClassPath::new("Exports", "", "Global")
+    );
+    //Types the assembly does not define come from the core library.
+    assert_eq!(
+        parse_type_name("System.Int32[]", &TestTypes),
+        ClassPath::new("System.Runtime", "System", "Int32[]")
+    );
+}
@@ -0,0 +1,712 @@
+use super::assembly::ImportError;
+use super::slice_reader::SliceReader;
+use super::tables::Token;
+use crate::ir::{InstructionIndex, Signature};
+use crate::type_system::paths::{ClassPath, MethodPath};
+use crate::OpKind;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OperandKind {
+    None,
+    ShortVar,
+    Var,
+    ShortInt,
+    UInt8,
+    Int,
+    Int64,
+    ShortFloat,
+    Float,
+    ShortBranch,
+    Branch,
+    Switch,
+    Token,
+}
+macro_rules! opcodes {
+    ($($name:ident = $code:literal, $mnemonic:literal, $operand:ident;)*) => {
+        /// All opcodes defined in ECMA-335 III. Two byte opcodes are stored with their 0xFE prefix.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub(crate) enum Opcode {
+            $($name,)*
+        }
+        impl Opcode {
+            pub(crate) fn from_code(code: u16) -> Option<Self> {
+                match code {
+                    $($code => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+            pub(crate) fn code(self) -> u16 {
+                match self {
+                    $(Self::$name => $code,)*
+                }
+            }
+            pub(crate) fn mnemonic(self) -> &'static str {
+                match self {
+                    $(Self::$name => $mnemonic,)*
+                }
+            }
+            fn operand_kind(self) -> OperandKind {
+                match self {
+                    $(Self::$name => OperandKind::$operand,)*
+                }
+            }
+        }
+    };
+}
+opcodes! {
+    Nop = 0x00, "nop", None;
+    Break = 0x01, "break", None;
+    LdArg0 = 0x02, "ldarg.0", None;
+    LdArg1 = 0x03, "ldarg.1", None;
+    LdArg2 = 0x04, "ldarg.2", None;
+    LdArg3 = 0x05, "ldarg.3", None;
+    LdLoc0 = 0x06, "ldloc.0", None;
+    LdLoc1 = 0x07, "ldloc.1", None;
+    LdLoc2 = 0x08, "ldloc.2", None;
+    LdLoc3 = 0x09, "ldloc.3", None;
+    StLoc0 = 0x0A, "stloc.0", None;
+    StLoc1 = 0x0B, "stloc.1", None;
+    StLoc2 = 0x0C, "stloc.2", None;
+    StLoc3 = 0x0D, "stloc.3", None;
+    LdArgS = 0x0E, "ldarg.s", ShortVar;
+    LdArgAS = 0x0F, "ldarga.s", ShortVar;
+    StArgS = 0x10, "starg.s", ShortVar;
+    LdLocS = 0x11, "ldloc.s", ShortVar;
+    LdLocAS = 0x12, "ldloca.s", ShortVar;
+    StLocS = 0x13, "stloc.s", ShortVar;
+    LdNull = 0x14, "ldnull", None;
+    LdcI4M1 = 0x15, "ldc.i4.m1", None;
+    LdcI40 = 0x16, "ldc.i4.0", None;
+    LdcI41 = 0x17, "ldc.i4.1", None;
+    LdcI42 = 0x18, "ldc.i4.2", None;
+    LdcI43 = 0x19, "ldc.i4.3", None;
+    LdcI44 = 0x1A, "ldc.i4.4", None;
+    LdcI45 = 0x1B, "ldc.i4.5", None;
+    LdcI46 = 0x1C, "ldc.i4.6", None;
+    LdcI47 = 0x1D, "ldc.i4.7", None;
+    LdcI48 = 0x1E, "ldc.i4.8", None;
+    LdcI4S = 0x1F, "ldc.i4.s", ShortInt;
+    LdcI4 = 0x20, "ldc.i4", Int;
+    LdcI8 = 0x21, "ldc.i8", Int64;
+    LdcR4 = 0x22, "ldc.r4", ShortFloat;
+    LdcR8 = 0x23, "ldc.r8", Float;
+    Dup = 0x25, "dup", None;
+    Pop = 0x26, "pop", None;
+    Jmp = 0x27, "jmp", Token;
+    Call = 0x28, "call", Token;
+    CallI = 0x29, "calli", Token;
+    Ret = 0x2A, "ret", None;
+    BrS = 0x2B, "br.s", ShortBranch;
+    BrFalseS = 0x2C, "brfalse.s", ShortBranch;
+    BrTrueS = 0x2D, "brtrue.s", ShortBranch;
+    BeqS = 0x2E, "beq.s", ShortBranch;
+    BgeS = 0x2F, "bge.s", ShortBranch;
+    BgtS = 0x30, "bgt.s", ShortBranch;
+    BleS = 0x31, "ble.s", ShortBranch;
+    BltS = 0x32, "blt.s", ShortBranch;
+    BneUnS = 0x33, "bne.un.s", ShortBranch;
+    BgeUnS = 0x34, "bge.un.s", ShortBranch;
+    BgtUnS = 0x35, "bgt.un.s", ShortBranch;
+    BleUnS = 0x36, "ble.un.s", ShortBranch;
+    BltUnS = 0x37, "blt.un.s", ShortBranch;
+    Br = 0x38, "br", Branch;
+    BrFalse = 0x39, "brfalse", Branch;
+    BrTrue = 0x3A, "brtrue", Branch;
+    Beq = 0x3B, "beq", Branch;
+    Bge = 0x3C, "bge", Branch;
+    Bgt = 0x3D, "bgt", Branch;
+    Ble = 0x3E, "ble", Branch;
+    Blt = 0x3F, "blt", Branch;
+    BneUn = 0x40, "bne.un", Branch;
+    BgeUn = 0x41, "bge.un", Branch;
+    BgtUn = 0x42, "bgt.un", Branch;
+    BleUn = 0x43, "ble.un", Branch;
+    BltUn = 0x44, "blt.un", Branch;
+    Switch = 0x45, "switch", Switch;
+    LdIndI1 = 0x46, "ldind.i1", None;
+    LdIndU1 = 0x47, "ldind.u1", None;
+    LdIndI2 = 0x48, "ldind.i2", None;
+    LdIndU2 = 0x49, "ldind.u2", None;
+    LdIndI4 = 0x4A, "ldind.i4", None;
+    LdIndU4 = 0x4B, "ldind.u4", None;
+    LdIndI8 = 0x4C, "ldind.i8", None;
+    LdIndI = 0x4D, "ldind.i", None;
+    LdIndR4 = 0x4E, "ldind.r4", None;
+    LdIndR8 = 0x4F, "ldind.r8", None;
+    LdIndRef = 0x50, "ldind.ref", None;
+    StIndRef = 0x51, "stind.ref", None;
+    StIndI1 = 0x52, "stind.i1", None;
+    StIndI2 = 0x53, "stind.i2", None;
+    StIndI4 = 0x54, "stind.i4", None;
+    StIndI8 = 0x55, "stind.i8", None;
+    StIndR4 = 0x56, "stind.r4", None;
+    StIndR8 = 0x57, "stind.r8", None;
+    Add = 0x58, "add", None;
+    Sub = 0x59, "sub", None;
+    Mul = 0x5A, "mul", None;
+    Div = 0x5B, "div", None;
+    DivUn = 0x5C, "div.un", None;
+    Rem = 0x5D, "rem", None;
+    RemUn = 0x5E, "rem.un", None;
+    And = 0x5F, "and", None;
+    Or = 0x60, "or", None;
+    Xor = 0x61, "xor", None;
+    Shl = 0x62, "shl", None;
+    Shr = 0x63, "shr", None;
+    ShrUn = 0x64, "shr.un", None;
+    Neg = 0x65, "neg", None;
+    Not = 0x66, "not", None;
+    ConvI1 = 0x67, "conv.i1", None;
+    ConvI2 = 0x68, "conv.i2", None;
+    ConvI4 = 0x69, "conv.i4", None;
+    ConvI8 = 0x6A, "conv.i8", None;
+    ConvR4 = 0x6B, "conv.r4", None;
+    ConvR8 = 0x6C, "conv.r8", None;
+    ConvU4 = 0x6D, "conv.u4", None;
+    ConvU8 = 0x6E, "conv.u8", None;
+    CallVirt = 0x6F, "callvirt", Token;
+    CpObj = 0x70, "cpobj", Token;
+    LdObj = 0x71, "ldobj", Token;
+    LdStr = 0x72, "ldstr", Token;
+    NewObj = 0x73, "newobj", Token;
+    CastClass = 0x74, "castclass", Token;
+    IsInst = 0x75, "isinst", Token;
+    ConvRUn = 0x76, "conv.r.un", None;
+    Unbox = 0x79, "unbox", Token;
+    Throw = 0x7A, "throw", None;
+    LdFld = 0x7B, "ldfld", Token;
+    LdFldA = 0x7C, "ldflda", Token;
+    StFld = 0x7D, "stfld", Token;
+    LdSFld = 0x7E, "ldsfld", Token;
+    LdSFldA = 0x7F, "ldsflda", Token;
+    StSFld = 0x80, "stsfld", Token;
+    StObj = 0x81, "stobj", Token;
+    ConvOvfI1Un = 0x82, "conv.ovf.i1.un", None;
+    ConvOvfI2Un = 0x83, "conv.ovf.i2.un", None;
+    ConvOvfI4Un = 0x84, "conv.ovf.i4.un", None;
+    ConvOvfI8Un = 0x85, "conv.ovf.i8.un", None;
+    ConvOvfU1Un = 0x86, "conv.ovf.u1.un", None;
+    ConvOvfU2Un = 0x87, "conv.ovf.u2.un", None;
+    ConvOvfU4Un = 0x88, "conv.ovf.u4.un", None;
+    ConvOvfU8Un = 0x89, "conv.ovf.u8.un", None;
+    ConvOvfIUn = 0x8A, "conv.ovf.i.un", None;
+    ConvOvfUUn = 0x8B, "conv.ovf.u.un", None;
+    Box = 0x8C, "box", Token;
+    NewArr = 0x8D, "newarr", Token;
+    LdLen = 0x8E, "ldlen", None;
+    LdElemA = 0x8F, "ldelema", Token;
+    LdElemI1 = 0x90, "ldelem.i1", None;
+    LdElemU1 = 0x91, "ldelem.u1", None;
+    LdElemI2 = 0x92, "ldelem.i2", None;
+    LdElemU2 = 0x93, "ldelem.u2", None;
+    LdElemI4 = 0x94, "ldelem.i4", None;
+    LdElemU4 = 0x95, "ldelem.u4", None;
+    LdElemI8 = 0x96, "ldelem.i8", None;
+    LdElemI = 0x97, "ldelem.i", None;
+    LdElemR4 = 0x98, "ldelem.r4", None;
+    LdElemR8 = 0x99, "ldelem.r8", None;
+    LdElemRef = 0x9A, "ldelem.ref", None;
+    StElemI = 0x9B, "stelem.i", None;
+    StElemI1 = 0x9C, "stelem.i1", None;
+    StElemI2 = 0x9D, "stelem.i2", None;
+    StElemI4 = 0x9E, "stelem.i4", None;
+    StElemI8 = 0x9F, "stelem.i8", None;
+    StElemR4 = 0xA0, "stelem.r4", None;
+    StElemR8 = 0xA1, "stelem.r8", None;
+    StElemRef = 0xA2, "stelem.ref", None;
+    LdElem = 0xA3, "ldelem", Token;
+    StElem = 0xA4, "stelem", Token;
+    UnboxAny = 0xA5, "unbox.any", Token;
+    ConvOvfI1 = 0xB3, "conv.ovf.i1", None;
+    ConvOvfU1 = 0xB4, "conv.ovf.u1", None;
+    ConvOvfI2 = 0xB5, "conv.ovf.i2", None;
+    ConvOvfU2 = 0xB6, "conv.ovf.u2", None;
+    ConvOvfI4 = 0xB7, "conv.ovf.i4", None;
+    ConvOvfU4 = 0xB8, "conv.ovf.u4", None;
+    ConvOvfI8 = 0xB9, "conv.ovf.i8", None;
+    ConvOvfU8 = 0xBA, "conv.ovf.u8", None;
+    RefAnyVal = 0xC2, "refanyval", Token;
+    CkFinite = 0xC3, "ckfinite", None;
+    MkRefAny = 0xC6, "mkrefany", Token;
+    LdToken = 0xD0, "ldtoken", Token;
+    ConvU2 = 0xD1, "conv.u2", None;
+    ConvU1 = 0xD2, "conv.u1", None;
+    ConvI = 0xD3, "conv.i", None;
+    ConvOvfI = 0xD4, "conv.ovf.i", None;
+    ConvOvfU = 0xD5, "conv.ovf.u", None;
+    AddOvf = 0xD6, "add.ovf", None;
+    AddOvfUn = 0xD7, "add.ovf.un", None;
+    MulOvf = 0xD8, "mul.ovf", None;
+    MulOvfUn = 0xD9, "mul.ovf.un", None;
+    SubOvf = 0xDA, "sub.ovf", None;
+    SubOvfUn = 0xDB, "sub.ovf.un", None;
+    EndFinally = 0xDC, "endfinally", None;
+    Leave = 0xDD, "leave", Branch;
+    LeaveS = 0xDE, "leave.s", ShortBranch;
+    StIndI = 0xDF, "stind.i", None;
+    ConvU = 0xE0, "conv.u", None;
+    ArgList = 0xFE00, "arglist", None;
+    Ceq = 0xFE01, "ceq", None;
+    Cgt = 0xFE02, "cgt", None;
+    CgtUn = 0xFE03, "cgt.un", None;
+    Clt = 0xFE04, "clt", None;
+    CltUn = 0xFE05, "clt.un", None;
+    LdFtn = 0xFE06, "ldftn", Token;
+    LdVirtFtn = 0xFE07, "ldvirtftn", Token;
+    LdArg = 0xFE09, "ldarg", Var;
+    LdArgA = 0xFE0A, "ldarga", Var;
+    StArg = 0xFE0B, "starg", Var;
+    LdLoc = 0xFE0C, "ldloc", Var;
+    LdLocA = 0xFE0D, "ldloca", Var;
+    StLoc = 0xFE0E, "stloc", Var;
+    LocAlloc = 0xFE0F, "localloc", None;
+    EndFilter = 0xFE11, "endfilter", None;
+    Unaligned = 0xFE12, "unaligned.", UInt8;
+    Volatile = 0xFE13, "volatile.", None;
+    Tail = 0xFE14, "tail.", None;
+    InitObj = 0xFE15, "initobj", Token;
+    Constrained = 0xFE16, "constrained.", Token;
+    CpBlk = 0xFE17, "cpblk", None;
+    InitBlk = 0xFE18, "initblk", None;
+    No = 0xFE19, "no.", UInt8;
+    Rethrow = 0xFE1A, "rethrow", None;
+    SizeOf = 0xFE1C, "sizeof", Token;
+    RefAnyType = 0xFE1D, "refanytype", None;
+    ReadOnly = 0xFE1E, "readonly.", None;
+}
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Operand {
+    None,
+    Var(u16),
+    Int32(i32),
+    Int64(i64),
+    Float32(f32),
+    Float64(f64),
+    Target(InstructionIndex),
+    Switch(Vec<InstructionIndex>),
+    Token(Token),
+}
+/// A single decoded CIL instruction.
+#[derive(Debug, Clone)]
+pub(crate) struct Instruction {
+    offset: u32,
+    opcode: Opcode,
+    operand: Operand,
+}
+/// Resolves metadata tokens embedded in the instruction stream.
+pub(crate) trait TokenResolver {
+    fn resolve_method(&self, token: Token) -> Result<(MethodPath, Signature), ImportError>;
+    /// Gets the UTF-16 contents of the `#US` heap entry referenced by `token`.
+    fn resolve_string(&self, token: Token) -> Result<Box<[u16]>, ImportError>;
+    fn resolve_type(&self, token: Token) -> Result<ClassPath, ImportError>;
+}
+impl Instruction {
+    /// Byte offset of this instruction within the method body.
+    pub(crate) fn offset(&self) -> u32 {
+        self.offset
+    }
+    pub(crate) fn opcode(&self) -> Opcode {
+        self.opcode
+    }
+    pub(crate) fn operand(&self) -> &Operand {
+        &self.operand
+    }
+    fn unsupported(&self) -> ImportError {
+        ImportError::UnsupportedOpcode {
+            il_offset: self.offset,
+            opcode: self.opcode.mnemonic(),
+        }
+    }
+    fn var(&self) -> Result<usize, ImportError> {
+        match self.operand {
+            Operand::Var(var) => Ok(usize::from(var)),
+            _ => Err(self.unsupported()),
+        }
+    }
+    fn target(&self) -> Result<InstructionIndex, ImportError> {
+        match self.operand {
+            Operand::Target(target) => Ok(target),
+            _ => Err(self.unsupported()),
+        }
+    }
+    /// Converts this instruction into its IR counterpart.
+    pub(crate) fn to_op_kind(&self, resolver: &impl TokenResolver) -> Result<OpKind, ImportError> {
+        use Opcode as O;
+        Ok(match self.opcode {
+            O::Nop => OpKind::Nop,
+            O::LdArg0 => OpKind::LDArg(0),
+            O::LdArg1 => OpKind::LDArg(1),
+            O::LdArg2 => OpKind::LDArg(2),
+            O::LdArg3 => OpKind::LDArg(3),
+            O::LdArgS | O::LdArg => OpKind::LDArg(self.var()?),
+            O::LdLoc0 => OpKind::LDLoc(0),
+            O::LdLoc1 => OpKind::LDLoc(1),
+            O::LdLoc2 => OpKind::LDLoc(2),
+            O::LdLoc3 => OpKind::LDLoc(3),
+            O::LdLocS | O::LdLoc => OpKind::LDLoc(self.var()?),
+            O::StLoc0 => OpKind::STLoc(0),
+            O::StLoc1 => OpKind::STLoc(1),
+            O::StLoc2 => OpKind::STLoc(2),
+            O::StLoc3 => OpKind::STLoc(3),
+            O::StLocS | O::StLoc => OpKind::STLoc(self.var()?),
+            O::LdNull => OpKind::LDNull,
+            O::LdStr => match self.operand {
+                Operand::Token(token) => OpKind::LDStr(resolver.resolve_string(token)?),
+                _ => return Err(self.unsupported()),
+            },
+            O::LdcI4M1 => OpKind::LDCI32(-1),
+            O::LdcI40 => OpKind::LDCI32(0),
+            O::LdcI41 => OpKind::LDCI32(1),
+            O::LdcI42 => OpKind::LDCI32(2),
+            O::LdcI43 => OpKind::LDCI32(3),
+            O::LdcI44 => OpKind::LDCI32(4),
+            O::LdcI45 => OpKind::LDCI32(5),
+            O::LdcI46 => OpKind::LDCI32(6),
+            O::LdcI47 => OpKind::LDCI32(7),
+            O::LdcI48 => OpKind::LDCI32(8),
+            O::LdcI4S | O::LdcI4 => match self.operand {
+                Operand::Int32(val) => OpKind::LDCI32(val),
+                _ => return Err(self.unsupported()),
+            },
+            O::LdcI8 => match self.operand {
+                Operand::Int64(val) => OpKind::LDCI64(val),
+                _ => return Err(self.unsupported()),
+            },
+            O::LdcR4 => match self.operand {
+                Operand::Float32(val) => OpKind::LDCF32(val),
+                _ => return Err(self.unsupported()),
+            },
+            O::LdcR8 => match self.operand {
+                Operand::Float64(val) => OpKind::LDCF64(val),
+                _ => return Err(self.unsupported()),
+            },
+            O::Dup => OpKind::Dup,
+            O::Pop => OpKind::Pop,
+            O::Call => match self.operand {
+                Operand::Token(token) => {
+                    let (path, sig) = resolver.resolve_method(token)?;
+                    OpKind::Call(path, sig)
+                }
+                _ => return Err(self.unsupported()),
+            },
+            O::Ret => OpKind::Ret,
+            O::BrS | O::Br => OpKind::BR(self.target()?),
+            O::BeqS | O::Beq => OpKind::BEQ(self.target()?),
+            O::BgeS | O::Bge => OpKind::BGE(self.target()?),
+            O::BgtS | O::Bgt => OpKind::BGT(self.target()?),
+            O::BleS | O::Ble => OpKind::BLE(self.target()?),
+            O::BltS | O::Blt => OpKind::BLT(self.target()?),
+            O::BneUnS | O::BneUn => OpKind::BNE(self.target()?),
+            O::Add => OpKind::Add,
+            O::Sub => OpKind::Sub,
+            O::Mul => OpKind::Mul,
+            O::Div => OpKind::Div,
+            O::Rem => OpKind::Rem,
+            O::And => OpKind::And,
+            O::Or => OpKind::Or,
+            O::Xor => OpKind::XOr,
+            O::Shl => OpKind::SHL,
+            O::Shr => OpKind::SHR,
+            O::Neg => OpKind::Neg,
+            O::Not => OpKind::Not,
+            O::ConvI1 => OpKind::ConvI8,
+            O::ConvU1 => OpKind::ConvU8,
+            O::ConvI2 => OpKind::ConvI16,
+            O::ConvU2 => OpKind::ConvU16,
+            O::ConvI4 => OpKind::ConvI32,
+            O::ConvU4 => OpKind::ConvU32,
+            O::ConvI8 => OpKind::ConvI64,
+            O::ConvU8 => OpKind::ConvU64,
+            O::ConvR4 => OpKind::ConvF32,
+            O::ConvR8 => OpKind::ConvF64,
+            O::ConvRUn => OpKind::ConvRUn,
+            O::ConvOvfI1 => OpKind::ConvOvfI8,
+            O::ConvOvfU1 => OpKind::ConvOvfU8,
+            O::ConvOvfI2 => OpKind::ConvOvfI16,
+            O::ConvOvfU2 => OpKind::ConvOvfU16,
+            O::ConvOvfI4 => OpKind::ConvOvfI32,
+            O::ConvOvfU4 => OpKind::ConvOvfU32,
+            O::ConvOvfI8 => OpKind::ConvOvfI64,
+            O::ConvOvfU8 => OpKind::ConvOvfU64,
+            O::ConvOvfI1Un => OpKind::ConvOvfI8Un,
+            O::ConvOvfU1Un => OpKind::ConvOvfU8Un,
+            O::ConvOvfI2Un => OpKind::ConvOvfI16Un,
+            O::ConvOvfU2Un => OpKind::ConvOvfU16Un,
+            O::ConvOvfI4Un => OpKind::ConvOvfI32Un,
+            O::ConvOvfU4Un => OpKind::ConvOvfU32Un,
+            O::ConvOvfI8Un => OpKind::ConvOvfI64Un,
+            O::ConvOvfU8Un => OpKind::ConvOvfU64Un,
+            O::AddOvf => OpKind::AddOvf,
+            O::AddOvfUn => OpKind::AddOvfUn,
+            O::SubOvf => OpKind::SubOvf,
+            O::SubOvfUn => OpKind::SubOvfUn,
+            O::MulOvf => OpKind::MulOvf,
+            O::MulOvfUn => OpKind::MulOvfUn,
+            _ => return Err(self.unsupported()),
+        })
+    }
+}
+fn read_opcode(reader: &mut SliceReader) -> Result<Opcode, ImportError> {
+    let il_offset = reader.offset() as u32;
+    let first = reader.read_u8()?;
+    let code = if first == 0xFE {
+        0xFE00 | u16::from(reader.read_u8()?)
+    } else {
+        u16::from(first)
+    };
+    Opcode::from_code(code).ok_or(ImportError::UnknownOpcode {
+        il_offset,
+        opcode: code,
+    })
+}
+/// Index of the instruction beginning at `il_offset`, if there is one.
+pub(crate) fn index_of(instructions: &[Instruction], il_offset: u32) -> Option<InstructionIndex> {
+    instructions
+        .binary_search_by_key(&il_offset, |instruction| instruction.offset)
+        .ok()
+}
+/// Decodes `code`, which begins at file offset `base`, into instructions.
+/// Branch targets are converted from IL offsets to instruction indices.
+pub(crate) fn decode(code: &[u8], base: usize) -> Result<Vec<Instruction>, ImportError> {
+    let mut reader = SliceReader::at(code, base);
+    let mut instructions = Vec::new();
+    //Branch targets, as byte offsets. Kept separately until all instruction offsets are known.
+    let mut raw_targets: Vec<(usize, Vec<i64>)> = Vec::new();
+    while reader.remaining() > 0 {
+        let offset = reader.offset() as u32;
+        let opcode = read_opcode(&mut reader)?;
+        let operand = match opcode.operand_kind() {
+            OperandKind::None => Operand::None,
+            OperandKind::ShortVar => Operand::Var(u16::from(reader.read_u8()?)),
+            OperandKind::Var => Operand::Var(reader.read_u16()?),
+            OperandKind::ShortInt => Operand::Int32(i32::from(reader.read_u8()? as i8)),
+            OperandKind::UInt8 => Operand::Int32(i32::from(reader.read_u8()?)),
+            OperandKind::Int => Operand::Int32(reader.read_u32()? as i32),
+            OperandKind::Int64 => Operand::Int64(reader.read_u64()? as i64),
+            OperandKind::ShortFloat => Operand::Float32(f32::from_bits(reader.read_u32()?)),
+            OperandKind::Float => Operand::Float64(f64::from_bits(reader.read_u64()?)),
+            OperandKind::ShortBranch => {
+                let delta = i64::from(reader.read_u8()? as i8);
+                let target = reader.offset() as i64 + delta;
+                raw_targets.push((instructions.len(), vec![target]));
+                Operand::Target(0)
+            }
+            OperandKind::Branch => {
+                let delta = i64::from(reader.read_u32()? as i32);
+                let target = reader.offset() as i64 + delta;
+                raw_targets.push((instructions.len(), vec![target]));
+                Operand::Target(0)
+            }
+            OperandKind::Switch => {
+                let count = reader.read_u32()? as usize;
+                if count > reader.remaining() / 4 {
+                    return Err(ImportError::UnexpectedEndOfData {
+                        offset: base + code.len(),
+                    });
+                }
+                let mut deltas = Vec::with_capacity(count);
+                for _ in 0..count {
+                    deltas.push(i64::from(reader.read_u32()? as i32));
+                }
+                //Switch targets are relative to the end of the whole instruction.
+                let base = reader.offset() as i64;
+                raw_targets.push((
+                    instructions.len(),
+                    deltas.iter().map(|delta| base + delta).collect(),
+                ));
+                Operand::Switch(Vec::new())
+            }
+            OperandKind::Token => Operand::Token(Token::from_raw(reader.read_u32()?)),
+        };
+        instructions.push(Instruction {
+            offset,
+            opcode,
+            operand,
+        });
+    }
+    for (index, targets) in raw_targets {
+        let offset = instructions[index].offset;
+        let mut resolved = Vec::with_capacity(targets.len());
+        for target in targets {
+            let target_index = u32::try_from(target)
+                .ok()
+                .and_then(|target| index_of(&instructions, target))
+                .ok_or(ImportError::InvalidBranchTarget {
+                    il_offset: offset,
+                    target,
+                })?;
+            resolved.push(target_index);
+        }
+        instructions[index].operand = match instructions[index].operand {
+            Operand::Switch(_) => Operand::Switch(resolved),
+            _ => Operand::Target(resolved[0]),
+        };
+    }
+    Ok(instructions)
+}
+#[cfg(test)]
+pub(crate) struct NoTokens;
+#[cfg(test)]
+impl TokenResolver for NoTokens {
+    fn resolve_method(&self, token: Token) -> Result<(MethodPath, Signature), ImportError> {
+        Err(ImportError::UnresolvedToken(token.raw()))
+    }
+    fn resolve_string(&self, token: Token) -> Result<Box<[u16]>, ImportError> {
+        Err(ImportError::UnresolvedToken(token.raw()))
+    }
+    fn resolve_type(&self, token: Token) -> Result<ClassPath, ImportError> {
+        Err(ImportError::UnresolvedToken(token.raw()))
+    }
+}
+#[cfg(test)]
+fn decode_ops(code: &[u8]) -> Result<Vec<OpKind>, ImportError> {
+    decode(code, 0)?
+        .iter()
+        .map(|instruction| instruction.to_op_kind(&NoTokens))
+        .collect()
+}
+#[test]
+fn opcode_table() {
+    for code in (0..=0xFF).chain(0xFE00..=0xFEFF) {
+        if let Some(opcode) = Opcode::from_code(code) {
+            assert_eq!(opcode.code(), code, "{}", opcode.mnemonic());
+        }
+    }
+    assert_eq!(Opcode::from_code(0x24), None);
+    assert_eq!(Opcode::from_code(0xFE1C), Some(Opcode::SizeOf));
+}
+#[test]
+fn decode_abs() {
+    //ldarg.0; ldc.i4.0; bge.s POSITIVE; ldarg.0; neg; ret; POSITIVE: ldarg.0; ret
+    let code = [0x02, 0x16, 0x2F, 0x03, 0x02, 0x65, 0x2A, 0x02, 0x2A];
+    let ops = decode_ops(&code).expect("Could not decode `abs`");
+    let expected = [
+        OpKind::LDArg(0),
+        OpKind::LDCI32(0),
+        OpKind::BGE(6),
+        OpKind::LDArg(0),
+        OpKind::Neg,
+        OpKind::Ret,
+        OpKind::LDArg(0),
+        OpKind::Ret,
+    ];
+    assert_eq!(format!("{ops:?}"), format!("{expected:?}"));
+}
+#[test]
+fn decode_long_forms() {
+    #[rustfmt::skip]
+    let code = [
+        0x20, 0x78, 0x56, 0x34, 0x12, //ldc.i4 0x12345678
+        0x1F, 0xFE, //ldc.i4.s -2
+        0x15, //ldc.i4.m1
+        0xFE, 0x0E, 0x01, 0x01, //stloc 0x101
+        0xFE, 0x0C, 0x01, 0x01, //ldloc 0x101
+        0x38, 0x00, 0x00, 0x00, 0x00, //br +0
+        0x3F, 0xEB, 0xFF, 0xFF, 0xFF, //blt -21 (back to ldc.i4.s)
+        0x2A, //ret
+    ];
+    let instructions = decode(&code, 0).expect("Could not decode long forms");
+    assert_eq!(instructions.len(), 8);
+    assert_eq!(instructions[0].operand(), &Operand::Int32(0x1234_5678));
+    assert_eq!(instructions[1].operand(), &Operand::Int32(-2));
+    assert_eq!(instructions[3].opcode(), Opcode::StLoc);
+    assert_eq!(instructions[3].offset(), 8);
+    assert_eq!(instructions[5].operand(), &Operand::Target(6));
+    assert_eq!(instructions[6].operand(), &Operand::Target(1));
+    let ops = decode_ops(&code).expect("Could not convert long forms");
+    assert!(matches!(ops[2], OpKind::LDCI32(-1)));
+    assert!(matches!(ops[3], OpKind::STLoc(0x101)));
+    assert!(matches!(ops[6], OpKind::BLT(1)));
+}
+#[test]
+fn decode_switch() {
+    #[rustfmt::skip]
+    let code = [
+        0x02, //ldarg.0
+        0x45, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //switch (+2, +0)
+        0x17, 0x2A, //ldc.i4.1; ret
+        0x16, 0x2A, //ldc.i4.0; ret
+    ];
+    let instructions = decode(&code, 0).expect("Could not decode switch");
+    assert_eq!(instructions[1].operand(), &Operand::Switch(vec![4, 2]));
+    assert!(matches!(
+        instructions[1].to_op_kind(&NoTokens),
+        Err(ImportError::UnsupportedOpcode {
+            il_offset: 1,
+            opcode: "switch"
+        })
+    ));
+}
+#[test]
+fn decode_errors() {
+    //Branch into the middle of ldc.i4
+    let code = [0x2B, 0x01, 0x20, 0x00, 0x00, 0x00, 0x00, 0x2A];
+    assert!(matches!(
+        decode(&code, 0),
+        Err(ImportError::InvalidBranchTarget {
+            il_offset: 0,
+            target: 3
+        })
+    ));
+    assert!(matches!(
+        decode(&[0x00, 0x24], 0),
+        Err(ImportError::UnknownOpcode {
+            il_offset: 1,
+            opcode: 0x24
+        })
+    ));
+    assert!(matches!(
+        decode(&[0x20, 0x00], 0x260),
+        Err(ImportError::UnexpectedEndOfData { offset: 0x261 })
+    ));
+    assert!(matches!(
+        decode_ops(&[0x00, 0x7A]),
+        Err(ImportError::UnsupportedOpcode {
+            il_offset: 1,
+            opcode: "throw"
+        })
+    ));
+    assert!(matches!(
+        decode_ops(&[0x28, 0x01, 0x00, 0x00, 0x06, 0x2A]),
+        Err(ImportError::UnresolvedToken(0x0600_0001))
+    ));
+}
+#[test]
+fn decode_constants() {
+    #[rustfmt::skip]
+    let code = [
+        0x21, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, //ldc.i8 0x1122334455667788
+        0x22, 0x00, 0x00, 0xC0, 0x3F, //ldc.r4 1.5
+        0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xD0, 0xBF, //ldc.r8 -0.25
+        0x1F, 0x80, //ldc.i4.s -128
+    ];
+    let ops = decode_ops(&code).expect("Could not decode constants");
+    let expected = [
+        OpKind::LDCI64(0x1122_3344_5566_7788),
+        OpKind::LDCF32(1.5),
+        OpKind::LDCF64(-0.25),
+        OpKind::LDCI32(-128),
+    ];
+    assert_eq!(format!("{ops:?}"), format!("{expected:?}"));
+}
+#[test]
+fn decode_checked() {
+    #[rustfmt::skip]
+    let code = [
+        0xD6, //add.ovf
+        0xD9, //mul.ovf.un
+        0xDA, //sub.ovf
+        0xB3, //conv.ovf.i1
+        0xBA, //conv.ovf.u8
+        0x88, //conv.ovf.u4.un
+    ];
+    let ops = decode_ops(&code).expect("Could not decode checked ops");
+    let expected = [
+        OpKind::AddOvf,
+        OpKind::MulOvfUn,
+        OpKind::SubOvf,
+        OpKind::ConvOvfI8,
+        OpKind::ConvOvfU64,
+        OpKind::ConvOvfU32Un,
+    ];
+    assert_eq!(format!("{ops:?}"), format!("{expected:?}"));
+}
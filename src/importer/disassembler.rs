@@ -0,0 +1,275 @@
+use super::assembly::{
+    load_locals, load_metadata, load_method_body, ImportError, MetadataResolver,
+};
+use super::cil::{self, Instruction, Operand, TokenResolver};
+use super::method_body::{ClauseKind, MethodBody};
+use super::pe::Image;
+use super::tables::{get_row, RowIndex, Tables};
+use crate::ir::text::{call_target, type_list};
+use crate::type_system::paths::ClassPath;
+use crate::{OpKind, Type};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+/// Disassembles all methods with bodies in assembly `asm`, in a syntax close to ILDasm's.
+pub fn disassemble(asm: &[u8]) -> Result<String, ImportError> {
+    disassemble_methods(asm, |_, _| true)
+}
+/// Disassembles all overloads of method `method_name` of class `class_name`, in a syntax close to ILDasm's.
+pub fn disassemble_method(
+    asm: &[u8],
+    namespace: &str,
+    class_name: &str,
+    method_name: &str,
+) -> Result<String, ImportError> {
+    disassemble_methods(asm, |class, name| {
+        class.namespace() == namespace && class.class_name() == class_name && name == method_name
+    })
+}
+fn disassemble_methods(
+    asm: &[u8],
+    filter: impl Fn(&ClassPath, &str) -> bool,
+) -> Result<String, ImportError> {
+    let image = Image::parse(asm)?;
+    let metadata = load_metadata(&image)?;
+    let tables = Tables::parse(metadata.tables(), metadata.tables_offset())?;
+    let resolver = MetadataResolver::new(&metadata, &tables)?;
+    let mut out = String::new();
+    for method_def in 1..=tables.method_defs.len() as RowIndex {
+        let method = get_row(&tables.method_defs, method_def)?;
+        let (class, name) = resolver.method_def_name(method_def)?;
+        if method.rva == 0 || !filter(&class, name) {
+            continue;
+        }
+        let kind = if method.is_static() {
+            "static"
+        } else {
+            "instance"
+        };
+        let header = match resolver.method_def(method_def) {
+            Ok((path, sig)) => {
+                //The implicit `this` is not a part of the signature in IL.
+                let args = match method.is_static() {
+                    true => sig.args(),
+                    false => &sig.args()[1.min(sig.argc())..],
+                };
+                format!(
+                    ".method {kind} {} {path}({})",
+                    sig.ret().to_mangle_string(),
+                    type_list(args)
+                )
+            }
+            //Methods the IR can't describe are still worth looking at when debugging imports.
+            Err(error) => format!(".method {kind} {class}::{name} // {error:?}"),
+        };
+        let body = load_method_body(&image, method.rva);
+        let locals = match &body {
+            Ok(body) => load_locals(&metadata, &tables, body.local_var_sig()).ok(),
+            Err(_) => None,
+        };
+        write_method(&mut out, &header, body.as_ref(), locals, &resolver)
+            .expect("Writing to a String can't fail");
+    }
+    Ok(out)
+}
+fn write_method(
+    out: &mut String,
+    header: &str,
+    body: Result<&MethodBody, &ImportError>,
+    locals: Option<Vec<Type>>,
+    resolver: &impl TokenResolver,
+) -> std::fmt::Result {
+    writeln!(out, "{header}")?;
+    writeln!(out, "{{")?;
+    match body {
+        Ok(body) => write_body(out, body, locals, resolver)?,
+        Err(error) => writeln!(out, "    // {error:?}")?,
+    }
+    writeln!(out, "}}")
+}
+/// Writes the contents of a method body: its header, locals, instructions and exception handling clauses.
+fn write_body(
+    out: &mut String,
+    body: &MethodBody,
+    locals: Option<Vec<Type>>,
+    resolver: &impl TokenResolver,
+) -> std::fmt::Result {
+    writeln!(out, "    .maxstack {}", body.max_stack())?;
+    match (locals, body.local_var_sig()) {
+        (Some(locals), _) if !locals.is_empty() => {
+            let locals: Vec<_> = locals
+                .iter()
+                .enumerate()
+                .map(|(index, local)| format!("{} V_{index}", local.to_mangle_string()))
+                .collect();
+            let init = if body.init_locals() { "init " } else { "" };
+            writeln!(out, "    .locals {init}({})", locals.join(", "))?;
+        }
+        (None, Some(token)) => {
+            writeln!(out, "    // .locals 0x{:08X} can't be decoded", token.raw())?
+        }
+        _ => (),
+    }
+    let instructions = match cil::decode(body.code(), body.code_offset()) {
+        Ok(instructions) => instructions,
+        Err(error) => return writeln!(out, "    // {error:?}"),
+    };
+    //Only instructions something jumps to get a label.
+    let mut labels = BTreeSet::new();
+    for instruction in &instructions {
+        match instruction.operand() {
+            Operand::Target(target) => {
+                labels.insert(instructions[*target].offset());
+            }
+            Operand::Switch(targets) => {
+                labels.extend(targets.iter().map(|target| instructions[*target].offset()));
+            }
+            _ => (),
+        }
+    }
+    for clause in body.eh_clauses() {
+        labels.insert(clause.try_range().0);
+        labels.insert(clause.handler_range().0);
+        if let ClauseKind::Filter(filter) = clause.kind() {
+            labels.insert(filter);
+        }
+    }
+    for instruction in &instructions {
+        if labels.contains(&instruction.offset()) {
+            write!(out, "    {}: ", label(instruction.offset()))?;
+        } else {
+            write!(out, "             ")?;
+        }
+        write_instruction(out, instruction, &instructions, resolver)?;
+        writeln!(out)?;
+    }
+    for clause in body.eh_clauses() {
+        let (try_start, try_end) = clause.try_range();
+        let (handler_start, handler_end) = clause.handler_range();
+        write!(out, "    .try {} to {} ", label(try_start), label(try_end))?;
+        match clause.kind() {
+            ClauseKind::Catch(token) => match resolver.resolve_type(token) {
+                Ok(class) => write!(out, "catch {class} ")?,
+                Err(_) => write!(out, "catch 0x{:08X} ", token.raw())?,
+            },
+            ClauseKind::Filter(filter) => write!(out, "filter {} ", label(filter))?,
+            ClauseKind::Finally => write!(out, "finally ")?,
+            ClauseKind::Fault => write!(out, "fault ")?,
+        }
+        writeln!(
+            out,
+            "handler {} to {}",
+            label(handler_start),
+            label(handler_end)
+        )?;
+    }
+    Ok(())
+}
+fn write_instruction(
+    out: &mut String,
+    instruction: &Instruction,
+    instructions: &[Instruction],
+    resolver: &impl TokenResolver,
+) -> std::fmt::Result {
+    write!(out, "{}", instruction.opcode().mnemonic())?;
+    //Operands the IR understands are shown the way the IR sees them.
+    match instruction.to_op_kind(resolver) {
+        Ok(OpKind::Call(path, sig)) => return write!(out, " {}", call_target(&path, &sig)),
+        Ok(OpKind::LDStr(chars)) => {
+            return write!(out, " {:?}", String::from_utf16_lossy(&chars));
+        }
+        _ => (),
+    }
+    match instruction.operand() {
+        Operand::None => Ok(()),
+        Operand::Var(var) => write!(out, " {var}"),
+        Operand::Int32(value) => write!(out, " {value}"),
+        Operand::Int64(value) => write!(out, " {value}"),
+        Operand::Float32(value) => write!(out, " {value:?}"),
+        Operand::Float64(value) => write!(out, " {value:?}"),
+        Operand::Target(target) => write!(out, " {}", label(instructions[*target].offset())),
+        Operand::Switch(targets) => {
+            let targets: Vec<_> = targets
+                .iter()
+                .map(|target| label(instructions[*target].offset()))
+                .collect();
+            write!(out, " ({})", targets.join(", "))
+        }
+        Operand::Token(token) => {
+            if let Ok((path, sig)) = resolver.resolve_method(*token) {
+                write!(out, " {}", call_target(&path, &sig))
+            } else if let Ok(class) = resolver.resolve_type(*token) {
+                write!(out, " {class}")
+            } else {
+                write!(out, " 0x{:08X}", token.raw())
+            }
+        }
+    }
+}
+fn label(il_offset: u32) -> String {
+    format!("IL_{il_offset:04x}")
+}
+#[test]
+fn disassemble_assembly() {
+    let asm =
+        std::fs::read("test_asm/prebuilt/SimpleFunctions.dll").expect("Could not open test file!");
+    let il = disassemble(&asm).expect("Could not disassemble assembly!");
+    assert!(il.contains(
+        ".method static i32 [SimpleFunctions]SimpleFunctions.Class1::Sum3(i32, i32, i32)"
+    ));
+    assert!(il.contains("call i32 [SimpleFunctions]SimpleFunctions.Class1::Add(i32, i32)"));
+    assert!(il.contains("ldstr \"Hello, World!\""));
+    //Methods with signatures the IR can't represent are still disassembled.
+    let exports =
+        std::fs::read("test_asm/prebuilt/Exports.dll").expect("Could not open test file!");
+    let il = disassemble(&exports).expect("Could not disassemble assembly!");
+    assert!(il.contains(
+        ".method instance [Exports]Exports.HostExportAttribute::.ctor // UnsupportedType"
+    ));
+    assert!(il.contains("stfld 0x04000004"));
+    let add = disassemble_method(&asm, "SimpleFunctions", "Class1", "Add")
+        .expect("Could not disassemble method!");
+    assert_eq!(
+        add,
+        ".method static i32 [SimpleFunctions]SimpleFunctions.Class1::Add(i32, i32)\n\
+         {\n    .maxstack 8\n             ldarg.0\n             ldarg.1\n             add\n             ret\n}\n"
+    );
+}
+#[test]
+fn disassemble_branches() {
+    #[rustfmt::skip]
+    let data = [
+        // Fat header, with an EH section
+        0x1B, 0x30, 0x02, 0x00, 0x11, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x11,
+        // ldarg.0; brtrue.s IL_0008
+        0x02, 0x2D, 0x05,
+        // ldc.i4.s -3; stloc.0; br.s IL_000a
+        0x1F, 0xFD, 0x0A, 0x2B, 0x02,
+        // ldc.i4.1; stloc.0
+        0x17, 0x0A,
+        // ldloc.0; ldfld 0x04000001; ret
+        0x06, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x2A,
+        0x00, 0x00, 0x00,
+        // EH section: finally, try IL_0000 to IL_0008, handler IL_0008 to IL_000a
+        0x01, 0x10, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x08, 0x08, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let body = MethodBody::parse(&data, 0).expect("Could not parse method body!");
+    let mut out = String::new();
+    write_body(&mut out, &body, Some(vec![Type::I32]), &cil::NoTokens).unwrap();
+    assert_eq!(
+        out,
+        "    .maxstack 2\n\
+         \x20   .locals init (i32 V_0)\n\
+         \x20   IL_0000: ldarg.0\n\
+         \x20            brtrue.s IL_0008\n\
+         \x20            ldc.i4.s -3\n\
+         \x20            stloc.0\n\
+         \x20            br.s IL_000a\n\
+         \x20   IL_0008: ldc.i4.1\n\
+         \x20            stloc.0\n\
+         \x20   IL_000a: ldloc.0\n\
+         \x20            ldfld 0x04000001\n\
+         \x20            ret\n\
+         \x20   .try IL_0000 to IL_0008 finally handler IL_0008 to IL_000a\n"
+    );
+}
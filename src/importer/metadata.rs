@@ -0,0 +1,319 @@
+use super::assembly::ImportError;
+use super::slice_reader::SliceReader;
+const METADATA_SIGNATURE: u32 = 0x424A_5342;
+#[derive(Debug)]
+pub(crate) struct StreamHeader<'a> {
+    name: &'a str,
+    data: &'a [u8],
+    offset: usize,
+}
+impl<'a> StreamHeader<'a> {
+    pub(crate) fn name(&self) -> &'a str {
+        self.name
+    }
+    /// File offset of the stream's data.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+}
+/// The metadata root(ECMA-335 II.24.2.1), with the stream headers it contains.
+#[derive(Debug)]
+pub(crate) struct MetadataRoot<'a> {
+    version: &'a str,
+    streams: Vec<StreamHeader<'a>>,
+}
+impl<'a> MetadataRoot<'a> {
+    /// Parses the metadata root in `data`, which begins at file offset `base`.
+    pub(crate) fn parse(data: &'a [u8], base: usize) -> Result<Self, ImportError> {
+        let mut reader = SliceReader::at(data, base);
+        let signature = reader.read_u32()?;
+        if signature != METADATA_SIGNATURE {
+            return Err(ImportError::InvalidMagic {
+                offset: base,
+                expected: METADATA_SIGNATURE,
+                found: signature,
+            });
+        }
+        //Major version, minor version, reserved
+        reader.skip(8)?;
+        let version_len = reader.read_u32()? as usize;
+        let version_offset = reader.file_offset();
+        let version = reader.read_bytes(version_len)?;
+        let version = &version[..version.iter().position(|b| *b == 0).unwrap_or(version_len)];
+        let version = std::str::from_utf8(version).map_err(|_| ImportError::InvalidUtf8 {
+            offset: version_offset,
+        })?;
+        //Flags, reserved
+        reader.skip(2)?;
+        let stream_count = reader.read_u16()?;
+        let mut streams = Vec::with_capacity(stream_count as usize);
+        for _ in 0..stream_count {
+            let header_offset = reader.file_offset();
+            let offset = reader.read_u32()? as usize;
+            let size = reader.read_u32()? as usize;
+            let name_beg = reader.offset();
+            let name = reader.read_cstr()?;
+            //Names are padded to the next 4 byte boundary.
+            let name_len = reader.offset() - name_beg;
+            reader.skip((4 - name_len % 4) % 4)?;
+            let Some(data) = offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+            else {
+                return Err(ImportError::StreamOutOfBounds {
+                    offset: header_offset,
+                    name: name.to_owned(),
+                });
+            };
+            streams.push(StreamHeader {
+                name,
+                data,
+                offset: base + offset,
+            });
+        }
+        Ok(Self { version, streams })
+    }
+    pub(crate) fn version(&self) -> &'a str {
+        self.version
+    }
+    pub(crate) fn streams(&self) -> &[StreamHeader<'a>] {
+        &self.streams
+    }
+    pub(crate) fn stream(&self, name: &str) -> Option<&'a [u8]> {
+        self.stream_header(name).map(|stream| stream.data)
+    }
+    pub(crate) fn stream_header(&self, name: &str) -> Option<&StreamHeader<'a>> {
+        self.streams.iter().find(|stream| stream.name == name)
+    }
+}
+/// The `#Strings` heap: null-terminated UTF-8 identifiers.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct StringHeap<'a> {
+    data: &'a [u8],
+}
+impl<'a> StringHeap<'a> {
+    pub(crate) fn get(&self, index: u32) -> Option<&'a str> {
+        let rest = self.data.get(index as usize..)?;
+        let len = rest.iter().position(|b| *b == 0)?;
+        std::str::from_utf8(&rest[..len]).ok()
+    }
+}
+/// The `#Blob` heap: byte arrays prefixed with their compressed length.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BlobHeap<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+impl<'a> BlobHeap<'a> {
+    pub(crate) fn get(&self, index: u32) -> Option<&'a [u8]> {
+        self.locate(index).map(|(blob, _)| blob)
+    }
+    /// Gets the blob at `index`, together with its file offset.
+    pub(crate) fn locate(&self, index: u32) -> Option<(&'a [u8], usize)> {
+        let mut reader = SliceReader::at(self.data, self.offset);
+        reader.seek(index as usize).ok()?;
+        let len = reader.read_compressed_u32().ok()?;
+        let offset = reader.file_offset();
+        Some((reader.read_bytes(len as usize).ok()?, offset))
+    }
+}
+/// The `#US` heap. Has the same layout as `#Blob`, but each entry is a UTF-16 string followed by a flag byte.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct UserStringHeap<'a> {
+    blobs: BlobHeap<'a>,
+}
+impl<'a> UserStringHeap<'a> {
+    /// Gets the UTF-16 code units of the string at `index`, without the flag byte.
+    pub(crate) fn get(&self, index: u32) -> Option<Box<[u16]>> {
+        let blob = self.blobs.get(index)?;
+        //Every non-empty entry has an odd length, due to the flag byte.
+        let chars = match blob.len() {
+            0 => blob,
+            len if len % 2 == 1 => &blob[..len - 1],
+            _ => return None,
+        };
+        Some(
+            chars
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect(),
+        )
+    }
+}
+/// The `#GUID` heap. Indices are 1-based, 0 means "no GUID".
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct GuidHeap<'a> {
+    data: &'a [u8],
+}
+impl<'a> GuidHeap<'a> {
+    pub(crate) fn get(&self, index: u32) -> Option<[u8; 16]> {
+        let beg = (index as usize).checked_sub(1)? * 16;
+        self.data.get(beg..beg + 16)?.try_into().ok()
+    }
+}
+/// Parsed metadata, with typed views of all the streams the importer needs.
+#[derive(Debug)]
+pub(crate) struct Metadata<'a> {
+    version: &'a str,
+    tables: &'a [u8],
+    tables_offset: usize,
+    strings: StringHeap<'a>,
+    user_strings: UserStringHeap<'a>,
+    blobs: BlobHeap<'a>,
+    guids: GuidHeap<'a>,
+}
+impl<'a> Metadata<'a> {
+    /// Parses the metadata in `data`, which begins at file offset `base`.
+    pub(crate) fn parse(data: &'a [u8], base: usize) -> Result<Self, ImportError> {
+        let root = MetadataRoot::parse(data, base)?;
+        if root.stream("#-").is_some() {
+            return Err(ImportError::UnsupportedStream("#-"));
+        }
+        let tables = root
+            .stream_header("#~")
+            .ok_or(ImportError::MissingStream("#~"))?;
+        let heap = |name| root.stream(name).unwrap_or(&[]);
+        let blob_heap = |name| {
+            root.stream_header(name)
+                .map(|stream| BlobHeap {
+                    data: stream.data,
+                    offset: stream.offset,
+                })
+                .unwrap_or_default()
+        };
+        Ok(Self {
+            version: root.version(),
+            tables: tables.data,
+            tables_offset: tables.offset,
+            strings: StringHeap {
+                data: heap("#Strings"),
+            },
+            user_strings: UserStringHeap {
+                blobs: blob_heap("#US"),
+            },
+            blobs: blob_heap("#Blob"),
+            guids: GuidHeap {
+                data: heap("#GUID"),
+            },
+        })
+    }
+    pub(crate) fn version(&self) -> &'a str {
+        self.version
+    }
+    pub(crate) fn tables(&self) -> &'a [u8] {
+        self.tables
+    }
+    /// File offset of the `#~` stream.
+    pub(crate) fn tables_offset(&self) -> usize {
+        self.tables_offset
+    }
+    pub(crate) fn strings(&self) -> StringHeap<'a> {
+        self.strings
+    }
+    pub(crate) fn user_strings(&self) -> UserStringHeap<'a> {
+        self.user_strings
+    }
+    pub(crate) fn blobs(&self) -> BlobHeap<'a> {
+        self.blobs
+    }
+    pub(crate) fn guids(&self) -> GuidHeap<'a> {
+        self.guids
+    }
+}
+#[cfg(test)]
+fn build_root(streams: &[(&str, &[u8])]) -> Vec<u8> {
+    let version = b"v4.0.30319\0\0";
+    let mut res = Vec::new();
+    res.extend(METADATA_SIGNATURE.to_le_bytes());
+    res.extend(1_u16.to_le_bytes());
+    res.extend(1_u16.to_le_bytes());
+    res.extend(0_u32.to_le_bytes());
+    res.extend((version.len() as u32).to_le_bytes());
+    res.extend(version);
+    res.extend(0_u16.to_le_bytes());
+    res.extend((streams.len() as u16).to_le_bytes());
+    let padded_name = |name: &str| (name.len() + 4) / 4 * 4;
+    let mut offset = res.len()
+        + streams
+            .iter()
+            .map(|(name, _)| 8 + padded_name(name))
+            .sum::<usize>();
+    for (name, data) in streams {
+        res.extend((offset as u32).to_le_bytes());
+        res.extend((data.len() as u32).to_le_bytes());
+        let mut name = name.as_bytes().to_vec();
+        name.resize(padded_name(std::str::from_utf8(&name).unwrap()), 0);
+        res.extend(name);
+        offset += data.len();
+    }
+    for (_, data) in streams {
+        res.extend(*data);
+    }
+    res
+}
+#[test]
+fn metadata_root() {
+    let strings = b"\0<Module>\0Add\0\0\0";
+    let blobs = b"\0\x05\x00\x02\x08\x08\x08\0\0";
+    let guids = [0xAB; 16];
+    let user_strings = b"\0\x0BH\0e\0l\0l\0o\0\0\x02A\0";
+    let data = build_root(&[
+        ("#~", &[0; 24]),
+        ("#Strings", strings),
+        ("#US", user_strings),
+        ("#GUID", &guids),
+        ("#Blob", blobs),
+    ]);
+    let root = MetadataRoot::parse(&data, 0).expect("Could not parse the metadata root!");
+    assert_eq!(root.version(), "v4.0.30319");
+    let names: Vec<&str> = root.streams().iter().map(StreamHeader::name).collect();
+    assert_eq!(names, ["#~", "#Strings", "#US", "#GUID", "#Blob"]);
+    let metadata = Metadata::parse(&data, 0x400).expect("Could not parse metadata!");
+    assert_eq!(metadata.tables().len(), 24);
+    assert_eq!(metadata.tables_offset(), 0x400 + root.streams()[0].offset());
+    assert_eq!(metadata.strings().get(1), Some("<Module>"));
+    assert_eq!(metadata.strings().get(10), Some("Add"));
+    assert_eq!(metadata.strings().get(0), Some(""));
+    assert_eq!(metadata.strings().get(100), None);
+    assert_eq!(
+        metadata.blobs().get(1),
+        Some(&[0x00, 0x02, 0x08, 0x08, 0x08][..])
+    );
+    let blob_offset = root.stream_header("#Blob").unwrap().offset();
+    assert_eq!(
+        metadata.blobs().locate(1).unwrap().1,
+        0x400 + blob_offset + 2
+    );
+    assert_eq!(metadata.guids().get(1), Some([0xAB; 16]));
+    assert_eq!(metadata.guids().get(0), None);
+    let hello: Vec<u16> = "Hello".encode_utf16().collect();
+    assert_eq!(metadata.user_strings().get(1).as_deref(), Some(&hello[..]));
+    assert_eq!(metadata.user_strings().get(0).as_deref(), Some(&[][..]));
+    //Missing the flag byte
+    assert_eq!(metadata.user_strings().get(13), None);
+}
+#[test]
+fn metadata_bad_signature() {
+    let mut data = build_root(&[("#~", &[0; 24])]);
+    data[0] = b'X';
+    assert!(matches!(
+        MetadataRoot::parse(&data, 0x200),
+        Err(ImportError::InvalidMagic {
+            offset: 0x200,
+            expected: METADATA_SIGNATURE,
+            found: 0x424A_5358,
+        })
+    ));
+    let data = build_root(&[("#Strings", b"\0")]);
+    assert!(matches!(
+        Metadata::parse(&data, 0),
+        Err(ImportError::MissingStream("#~"))
+    ));
+    let mut data = build_root(&[("#~", &[0; 24])]);
+    let len = data.len();
+    data.truncate(len - 1);
+    assert!(matches!(
+        Metadata::parse(&data, 0x200),
+        Err(ImportError::StreamOutOfBounds { offset: 0x220, .. })
+    ));
+}
@@ -0,0 +1,288 @@
+use super::assembly::ImportError;
+use super::slice_reader::SliceReader;
+use super::tables::Token;
+const TINY_FORMAT: u8 = 0x2;
+const FAT_FORMAT: u8 = 0x3;
+const MORE_SECTS: u16 = 0x8;
+const INIT_LOCALS: u16 = 0x10;
+const SECT_EH_TABLE: u8 = 0x1;
+const SECT_FAT_FORMAT: u8 = 0x40;
+const SECT_MORE_SECTS: u8 = 0x80;
+const CLAUSE_EXCEPTION: u32 = 0x0;
+const CLAUSE_FILTER: u32 = 0x1;
+const CLAUSE_FINALLY: u32 = 0x2;
+const CLAUSE_FAULT: u32 = 0x4;
+/// What handles exceptions leaving the `try` block of an [`EhClause`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClauseKind {
+    /// Catches exceptions of the class referenced by the token.
+    Catch(Token),
+    /// Filter block starting at the IL offset decides whether the handler runs.
+    Filter(u32),
+    Finally,
+    Fault,
+}
+/// An exception handling clause(ECMA-335 II.25.4.6). Offsets are IL offsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EhClause {
+    kind: ClauseKind,
+    try_offset: u32,
+    try_length: u32,
+    handler_offset: u32,
+    handler_length: u32,
+}
+impl EhClause {
+    pub(crate) fn kind(&self) -> ClauseKind {
+        self.kind
+    }
+    /// IL offsets of the first byte of, and the first byte after, the `try` block.
+    pub(crate) fn try_range(&self) -> (u32, u32) {
+        (
+            self.try_offset,
+            self.try_offset.saturating_add(self.try_length),
+        )
+    }
+    /// IL offsets of the first byte of, and the first byte after, the handler.
+    pub(crate) fn handler_range(&self) -> (u32, u32) {
+        (
+            self.handler_offset,
+            self.handler_offset.saturating_add(self.handler_length),
+        )
+    }
+}
+/// A method body(ECMA-335 II.25.4), with either a tiny or a fat header.
+#[derive(Debug)]
+pub(crate) struct MethodBody<'a> {
+    max_stack: u16,
+    code: &'a [u8],
+    code_offset: usize,
+    local_var_sig: Option<Token>,
+    init_locals: bool,
+    more_sects: bool,
+    eh_clauses: Vec<EhClause>,
+}
+impl<'a> MethodBody<'a> {
+    /// Parses a method body beginning at the start of `data`, which is at file offset `base`.
+    pub(crate) fn parse(data: &'a [u8], base: usize) -> Result<Self, ImportError> {
+        let mut reader = SliceReader::at(data, base);
+        let first = reader.read_u8()?;
+        match first & 0x3 {
+            TINY_FORMAT => {
+                let code_size = usize::from(first >> 2);
+                Ok(Self {
+                    max_stack: 8,
+                    code_offset: reader.file_offset(),
+                    code: reader.read_bytes(code_size)?,
+                    local_var_sig: None,
+                    init_locals: false,
+                    more_sects: false,
+                    eh_clauses: Vec::new(),
+                })
+            }
+            FAT_FORMAT => {
+                reader.seek(0)?;
+                let flags_and_size = reader.read_u16()?;
+                let flags = flags_and_size & 0x0FFF;
+                //Size of the header, in 4 byte units.
+                let header_size = usize::from(flags_and_size >> 12) * 4;
+                let max_stack = reader.read_u16()?;
+                let code_size = reader.read_u32()? as usize;
+                let local_var_sig = reader.read_u32()?;
+                reader.seek(header_size)?;
+                let code_offset = reader.file_offset();
+                let code = reader.read_bytes(code_size)?;
+                let more_sects = flags & MORE_SECTS != 0;
+                let eh_clauses = if more_sects {
+                    read_sections(&mut reader)?
+                } else {
+                    Vec::new()
+                };
+                Ok(Self {
+                    max_stack,
+                    code_offset,
+                    code,
+                    local_var_sig: (local_var_sig != 0).then(|| Token::from_raw(local_var_sig)),
+                    init_locals: flags & INIT_LOCALS != 0,
+                    more_sects,
+                    eh_clauses,
+                })
+            }
+            _ => Err(ImportError::InvalidMethodHeader {
+                offset: base,
+                found: first,
+            }),
+        }
+    }
+    pub(crate) fn max_stack(&self) -> u16 {
+        self.max_stack
+    }
+    pub(crate) fn code(&self) -> &'a [u8] {
+        self.code
+    }
+    /// File offset of the first instruction.
+    pub(crate) fn code_offset(&self) -> usize {
+        self.code_offset
+    }
+    /// Token of the StandAloneSig describing the locals, if the method has any.
+    pub(crate) fn local_var_sig(&self) -> Option<Token> {
+        self.local_var_sig
+    }
+    pub(crate) fn init_locals(&self) -> bool {
+        self.init_locals
+    }
+    /// Whether extra data sections follow the code.
+    pub(crate) fn more_sects(&self) -> bool {
+        self.more_sects
+    }
+    /// Exception handling clauses from the data sections, innermost first.
+    pub(crate) fn eh_clauses(&self) -> &[EhClause] {
+        &self.eh_clauses
+    }
+}
+/// Reads the data sections following the code of a fat method body, keeping the exception handling clauses.
+fn read_sections(reader: &mut SliceReader) -> Result<Vec<EhClause>, ImportError> {
+    let mut clauses = Vec::new();
+    loop {
+        //Sections are 4 byte aligned, relative to the start of the method body.
+        reader.seek((reader.offset() + 3) & !3)?;
+        let section_offset = reader.offset();
+        let kind = reader.read_u8()?;
+        let fat = kind & SECT_FAT_FORMAT != 0;
+        //Size includes the 4 byte section header.
+        let data_size = if fat {
+            let size = reader.read_bytes(3)?;
+            u32::from_le_bytes([size[0], size[1], size[2], 0]) as usize
+        } else {
+            let size = usize::from(reader.read_u8()?);
+            reader.skip(2)?;
+            size
+        };
+        //Sections are at least as big as their header, which guarantees progress.
+        let section_end = section_offset + data_size.max(4);
+        if kind & SECT_EH_TABLE != 0 {
+            let clause_size = if fat { 24 } else { 12 };
+            for _ in 0..data_size.saturating_sub(4) / clause_size {
+                clauses.push(read_clause(reader, fat)?);
+            }
+        }
+        reader.seek(section_end)?;
+        if kind & SECT_MORE_SECTS == 0 {
+            return Ok(clauses);
+        }
+    }
+}
+fn read_clause(reader: &mut SliceReader, fat: bool) -> Result<EhClause, ImportError> {
+    let offset = reader.file_offset();
+    let (flags, try_offset, try_length, handler_offset, handler_length) = if fat {
+        (
+            reader.read_u32()?,
+            reader.read_u32()?,
+            reader.read_u32()?,
+            reader.read_u32()?,
+            reader.read_u32()?,
+        )
+    } else {
+        (
+            u32::from(reader.read_u16()?),
+            u32::from(reader.read_u16()?),
+            u32::from(reader.read_u8()?),
+            u32::from(reader.read_u16()?),
+            u32::from(reader.read_u8()?),
+        )
+    };
+    let class_token_or_filter = reader.read_u32()?;
+    let kind = match flags {
+        CLAUSE_EXCEPTION => ClauseKind::Catch(Token::from_raw(class_token_or_filter)),
+        CLAUSE_FILTER => ClauseKind::Filter(class_token_or_filter),
+        CLAUSE_FINALLY => ClauseKind::Finally,
+        CLAUSE_FAULT => ClauseKind::Fault,
+        _ => return Err(ImportError::InvalidEhClause { offset, flags }),
+    };
+    Ok(EhClause {
+        kind,
+        try_offset,
+        try_length,
+        handler_offset,
+        handler_length,
+    })
+}
+#[test]
+fn tiny_header() {
+    let data = [0x12, 0x02, 0x03, 0x58, 0x2A, 0xFF];
+    let body = MethodBody::parse(&data, 0x250).expect("Could not parse tiny header!");
+    assert_eq!(body.max_stack(), 8);
+    assert_eq!(body.code_offset(), 0x251);
+    assert_eq!(body.code(), &[0x02, 0x03, 0x58, 0x2A]);
+    assert_eq!(body.local_var_sig(), None);
+    assert!(!body.more_sects());
+}
+#[test]
+fn fat_header() {
+    #[rustfmt::skip]
+    let data = [
+        0x1B, 0x30, 0x02, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x11,
+        0x02, 0x0A, 0x2A, 0x00,
+        //Empty EH section
+        0x01, 0x04, 0x00, 0x00,
+    ];
+    let body = MethodBody::parse(&data, 0).expect("Could not parse fat header!");
+    assert_eq!(body.max_stack(), 2);
+    assert_eq!(body.code(), &[0x02, 0x0A, 0x2A]);
+    assert_eq!(body.local_var_sig(), Some(Token::from_raw(0x1100_0001)));
+    assert!(body.init_locals());
+    assert!(body.more_sects());
+    assert!(body.eh_clauses().is_empty());
+    assert!(matches!(
+        MethodBody::parse(&[0x00], 0x250),
+        Err(ImportError::InvalidMethodHeader {
+            offset: 0x250,
+            found: 0x00
+        })
+    ));
+    assert!(matches!(
+        MethodBody::parse(&data[..8], 0x250),
+        Err(ImportError::UnexpectedEndOfData { offset: 0x258 })
+    ));
+}
+#[test]
+fn eh_sections() {
+    #[rustfmt::skip]
+    let data = [
+        //Fat header, MoreSects set, 5 bytes of code.
+        0x1B, 0x30, 0x02, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x2A, 0x00, 0x00, 0x00,
+        //Small EH section with MoreSects set, one catch clause.
+        0x81, 0x10, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x02, 0x05, 0x00, 0x00, 0x01,
+        //Fat EH section, one finally clause.
+        0x41, 0x1C, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
+        0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let body = MethodBody::parse(&data, 0).expect("Could not parse EH sections!");
+    assert_eq!(body.code().len(), 5);
+    let clauses = body.eh_clauses();
+    assert_eq!(clauses.len(), 2);
+    assert_eq!(
+        clauses[0].kind(),
+        ClauseKind::Catch(Token::from_raw(0x0100_0005))
+    );
+    assert_eq!(clauses[0].try_range(), (0, 2));
+    assert_eq!(clauses[0].handler_range(), (2, 4));
+    assert_eq!(clauses[1].kind(), ClauseKind::Finally);
+    assert_eq!(clauses[1].try_range(), (0, 4));
+    assert_eq!(clauses[1].handler_range(), (4, 5));
+    let mut bad = data;
+    bad[24] = 0x08;
+    assert!(matches!(
+        MethodBody::parse(&bad, 0x300),
+        Err(ImportError::InvalidEhClause {
+            offset: 0x318,
+            flags: 0x08
+        })
+    ));
+    assert!(matches!(
+        MethodBody::parse(&data[..40], 0x300),
+        Err(ImportError::UnexpectedEndOfData { .. })
+    ));
+}
@@ -1 +1,10 @@
 pub(crate) mod assembly;
+mod attribute;
+mod cil;
+pub(crate) mod disassembler;
+mod metadata;
+mod method_body;
+mod pe;
+pub(crate) mod signature;
+mod slice_reader;
+mod tables;
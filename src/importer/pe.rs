@@ -0,0 +1,465 @@
+use super::assembly::ImportError;
+use std::borrow::Cow;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+const DOS_MAGIC: u16 = u16::from_le_bytes(*b"MZ");
+const PE_MAGIC: u32 = u32::from_le_bytes(*b"PE\0\0");
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const E_LFANEW_OFFSET: usize = 0x3C;
+const IMAGE_FILE_EXECUTABLE_IMAGE: u16 = 0x0002;
+const IMAGE_FILE_DLL: u16 = 0x2000;
+pub(crate) const MACHINE_I386: u16 = 0x14c;
+pub(crate) const MACHINE_AMD64: u16 = 0x8664;
+pub(crate) const MACHINE_ARM64: u16 = 0xAA64;
+const CLI_HEADER_DIRECTORY: usize = 14;
+const SECTION_HEADER_SIZE: usize = 40;
+/// Reads little-endian values at arbitrary file offsets, keeping track of the current one for error reporting.
+struct SmartReader<R: Read + Seek> {
+    offset: usize,
+    src: R,
+}
+impl<R: Read + Seek> SmartReader<R> {
+    fn new(src: R) -> Self {
+        Self { offset: 0, src }
+    }
+    fn offset(&self) -> usize {
+        self.offset
+    }
+    fn seek(&mut self, offset: usize) -> Result<(), ImportError> {
+        self.src
+            .seek(SeekFrom::Start(offset as u64))
+            .map_err(ImportError::Io)?;
+        self.offset = offset;
+        Ok(())
+    }
+    fn skip(&mut self, ammount: usize) -> Result<(), ImportError> {
+        self.seek(self.offset + ammount)
+    }
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ImportError> {
+        let offset = self.offset;
+        self.src.read_exact(buf).map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => ImportError::UnexpectedEndOfData { offset },
+            _ => ImportError::Io(err),
+        })?;
+        self.offset += buf.len();
+        Ok(())
+    }
+    /// Reads `len` bytes. Does not trust `len` for allocation, since it comes from the file.
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, ImportError> {
+        let offset = self.offset;
+        let mut res = Vec::new();
+        (&mut self.src)
+            .take(len as u64)
+            .read_to_end(&mut res)
+            .map_err(ImportError::Io)?;
+        self.offset += res.len();
+        if res.len() != len {
+            return Err(ImportError::UnexpectedEndOfData {
+                offset: offset + res.len(),
+            });
+        }
+        Ok(res)
+    }
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ImportError> {
+        let mut res = [0; N];
+        self.read_exact(&mut res)?;
+        Ok(res)
+    }
+    fn read_u16(&mut self) -> Result<u16, ImportError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+    fn read_u32(&mut self) -> Result<u32, ImportError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+}
+#[derive(Debug)]
+pub(crate) struct SectionHeader {
+    name: [u8; 8],
+    virtual_adress: u32,
+    offset: u32,
+    size: u32,
+}
+impl SectionHeader {
+    /// Name, without the null padding.
+    pub(crate) fn name(&self) -> &[u8] {
+        let len = self.name.iter().position(|b| *b == 0).unwrap_or(8);
+        &self.name[..len]
+    }
+    pub(crate) fn virtual_adress(&self) -> u32 {
+        self.virtual_adress
+    }
+    /// File offset of the section's raw data.
+    pub(crate) fn offset(&self) -> u32 {
+        self.offset
+    }
+    /// Size of the section's raw data.
+    pub(crate) fn size(&self) -> u32 {
+        self.size
+    }
+    /// Converts `rva` to a file offset, if it lies within the raw data of this section.
+    pub(crate) fn file_offset(&self, rva: u32) -> Option<usize> {
+        let offset = rva.checked_sub(self.virtual_adress)?;
+        (offset < self.size).then(|| self.offset as usize + offset as usize)
+    }
+}
+/// The parts of the PE/COFF headers(ECMA-335 II.25.2) the importer needs.
+#[derive(Debug)]
+pub(crate) struct PeHeaders {
+    machine: u16,
+    characteristics: u16,
+    pe32_plus: bool,
+    subsystem: u16,
+    cli_header_rva: u32,
+    cli_header_size: u32,
+    sections: Vec<SectionHeader>,
+}
+impl PeHeaders {
+    /// Parses the headers of the image in `src`, regardless of its current position.
+    pub(crate) fn parse<R: Read + Seek>(src: R) -> Result<Self, ImportError> {
+        Self::parse_from(&mut SmartReader::new(src))
+    }
+    fn parse_from<R: Read + Seek>(asm: &mut SmartReader<R>) -> Result<Self, ImportError> {
+        asm.seek(0)?;
+        let dos_magic = asm.read_u16()?;
+        if dos_magic != DOS_MAGIC {
+            return Err(ImportError::InvalidMagic {
+                offset: 0,
+                expected: u32::from(DOS_MAGIC),
+                found: u32::from(dos_magic),
+            });
+        }
+        //The rest of the DOS header and the DOS stub are irrelevant.
+        asm.seek(E_LFANEW_OFFSET)?;
+        let pe_offset = asm.read_u32()? as usize;
+        asm.seek(pe_offset)?;
+        let pe_magic = asm.read_u32()?;
+        if pe_magic != PE_MAGIC {
+            return Err(ImportError::InvalidMagic {
+                offset: pe_offset,
+                expected: PE_MAGIC,
+                found: pe_magic,
+            });
+        }
+        let coff_offset = asm.offset();
+        let machine = asm.read_u16()?;
+        if !matches!(machine, MACHINE_I386 | MACHINE_AMD64 | MACHINE_ARM64) {
+            return Err(ImportError::UnsupportedValue {
+                offset: coff_offset,
+                field: "Machine",
+                found: u32::from(machine),
+            });
+        }
+        let section_count = asm.read_u16()?;
+        //Time stamp, pointer to symbol table, number of symbols
+        asm.skip(12)?;
+        let optional_header_size = usize::from(asm.read_u16()?);
+        let characteristics = asm.read_u16()?;
+        if characteristics & IMAGE_FILE_EXECUTABLE_IMAGE == 0 {
+            return Err(ImportError::UnsupportedValue {
+                offset: coff_offset + 18,
+                field: "Characteristics",
+                found: u32::from(characteristics),
+            });
+        }
+        let optional_offset = asm.offset();
+        let optional_magic = asm.read_u16()?;
+        let pe32_plus = match optional_magic {
+            PE32_MAGIC => false,
+            PE32_PLUS_MAGIC => true,
+            _ => {
+                return Err(ImportError::UnsupportedValue {
+                    offset: optional_offset,
+                    field: "optional header Magic",
+                    found: u32::from(optional_magic),
+                })
+            }
+        };
+        asm.seek(optional_offset + 68)?;
+        let subsystem = asm.read_u16()?;
+        //PE32+ uses 64 bit stack and heap sizes, which moves the data directories back.
+        let dir_count_offset = optional_offset + if pe32_plus { 108 } else { 92 };
+        asm.seek(dir_count_offset)?;
+        let dir_count = asm.read_u32()? as usize;
+        let cli_dir_offset = dir_count_offset + 4 + CLI_HEADER_DIRECTORY * 8;
+        if dir_count <= CLI_HEADER_DIRECTORY
+            || cli_dir_offset + 8 > optional_offset + optional_header_size
+        {
+            return Err(ImportError::NotManaged);
+        }
+        asm.seek(cli_dir_offset)?;
+        let cli_header_rva = asm.read_u32()?;
+        let cli_header_size = asm.read_u32()?;
+        if cli_header_rva == 0 {
+            return Err(ImportError::NotManaged);
+        }
+        asm.seek(optional_offset + optional_header_size)?;
+        let mut sections = Vec::with_capacity(usize::from(section_count));
+        for _ in 0..section_count {
+            let section_offset = asm.offset();
+            let name = asm.read_array()?;
+            //Skip virtual size
+            asm.skip(4)?;
+            let virtual_adress = asm.read_u32()?;
+            let size = asm.read_u32()?;
+            let offset = asm.read_u32()?;
+            //Relocations, line numbers and characteristics
+            asm.seek(section_offset + SECTION_HEADER_SIZE)?;
+            sections.push(SectionHeader {
+                name,
+                virtual_adress,
+                offset,
+                size,
+            });
+        }
+        Ok(Self {
+            machine,
+            characteristics,
+            pe32_plus,
+            subsystem,
+            cli_header_rva,
+            cli_header_size,
+            sections,
+        })
+    }
+    pub(crate) fn machine(&self) -> u16 {
+        self.machine
+    }
+    pub(crate) fn is_dll(&self) -> bool {
+        self.characteristics & IMAGE_FILE_DLL != 0
+    }
+    pub(crate) fn is_pe32_plus(&self) -> bool {
+        self.pe32_plus
+    }
+    pub(crate) fn subsystem(&self) -> u16 {
+        self.subsystem
+    }
+    /// RVA and size of the CLI header.
+    pub(crate) fn cli_header(&self) -> (u32, u32) {
+        (self.cli_header_rva, self.cli_header_size)
+    }
+    pub(crate) fn sections(&self) -> &[SectionHeader] {
+        &self.sections
+    }
+}
+/// A PE image whose section data can be accessed by RVA, in any order.
+pub(crate) struct Image<'a> {
+    headers: PeHeaders,
+    sections: Vec<Cow<'a, [u8]>>,
+}
+impl<'a> Image<'a> {
+    /// Borrows the sections of an image already in memory, e.g. a memory-mapped file.
+    pub(crate) fn parse(data: &'a [u8]) -> Result<Self, ImportError> {
+        let headers = PeHeaders::parse(Cursor::new(data))?;
+        let sections = headers
+            .sections()
+            .iter()
+            .map(|section| {
+                let start = section.offset() as usize;
+                data.get(start..start + section.size() as usize)
+                    .map(Cow::Borrowed)
+                    .ok_or(ImportError::UnexpectedEndOfData { offset: data.len() })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { headers, sections })
+    }
+    /// Reads the headers and sections of an image from `src`.
+    pub(crate) fn read<R: Read + Seek>(src: R) -> Result<Image<'static>, ImportError> {
+        let mut asm = SmartReader::new(src);
+        let headers = PeHeaders::parse_from(&mut asm)?;
+        let sections = headers
+            .sections()
+            .iter()
+            .map(|section| {
+                asm.seek(section.offset() as usize)?;
+                asm.read_vec(section.size() as usize).map(Cow::Owned)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Image { headers, sections })
+    }
+    pub(crate) fn headers(&self) -> &PeHeaders {
+        &self.headers
+    }
+    /// Data from `rva` to the end of its section, together with its file offset.
+    pub(crate) fn data_at(&self, rva: u32) -> Result<(&[u8], usize), ImportError> {
+        self.headers
+            .sections()
+            .iter()
+            .zip(&self.sections)
+            .find_map(|(section, data)| {
+                let offset = section.file_offset(rva)?;
+                Some((&data[offset - section.offset() as usize..], offset))
+            })
+            .ok_or(ImportError::InvalidRva(rva))
+    }
+    /// `size` bytes at `rva`, together with their file offset.
+    pub(crate) fn read_rva(&self, rva: u32, size: u32) -> Result<(&[u8], usize), ImportError> {
+        let (data, offset) = self.data_at(rva)?;
+        let data = data
+            .get(..size as usize)
+            .ok_or(ImportError::UnexpectedEndOfData {
+                offset: offset + data.len(),
+            })?;
+        Ok((data, offset))
+    }
+}
+#[cfg(test)]
+fn build_headers(machine: u16, pe32_plus: bool, characteristics: u16, subsystem: u16) -> Vec<u8> {
+    let pe_offset = 0x40_usize;
+    let mut res = vec![0; pe_offset];
+    res[..2].copy_from_slice(b"MZ");
+    res[E_LFANEW_OFFSET..E_LFANEW_OFFSET + 4].copy_from_slice(&(pe_offset as u32).to_le_bytes());
+    res.extend(b"PE\0\0");
+    let optional_header_size: u16 = if pe32_plus { 0xF0 } else { 0xE0 };
+    res.extend(machine.to_le_bytes());
+    res.extend(1_u16.to_le_bytes());
+    res.extend([0; 12]);
+    res.extend(optional_header_size.to_le_bytes());
+    res.extend(characteristics.to_le_bytes());
+    let optional_offset = res.len();
+    res.resize(optional_offset + usize::from(optional_header_size), 0);
+    let optional = &mut res[optional_offset..];
+    let magic = if pe32_plus {
+        PE32_PLUS_MAGIC
+    } else {
+        PE32_MAGIC
+    };
+    optional[..2].copy_from_slice(&magic.to_le_bytes());
+    optional[68..70].copy_from_slice(&subsystem.to_le_bytes());
+    let dirs = if pe32_plus { 108 } else { 92 };
+    optional[dirs..dirs + 4].copy_from_slice(&16_u32.to_le_bytes());
+    let cli_dir = dirs + 4 + CLI_HEADER_DIRECTORY * 8;
+    optional[cli_dir..cli_dir + 4].copy_from_slice(&0x2008_u32.to_le_bytes());
+    optional[cli_dir + 4..cli_dir + 8].copy_from_slice(&0x48_u32.to_le_bytes());
+    res.extend(b".text\0\0\0");
+    for field in [0x1000_u32, 0x2000, 0x200, 0x200, 0, 0, 0, 0] {
+        res.extend(field.to_le_bytes());
+    }
+    res
+}
+#[test]
+fn pe32_dll() {
+    let dll =
+        std::fs::read("test_asm/prebuilt/SimpleFunctions.dll").expect("Could not open test file!");
+    let headers = PeHeaders::parse(Cursor::new(&dll[..])).expect("Could not parse headers!");
+    assert!(headers.is_dll());
+    assert!(!headers.is_pe32_plus());
+    assert_eq!(headers.machine(), MACHINE_I386);
+    let (cli_rva, _) = headers.cli_header();
+    let text = headers
+        .sections()
+        .iter()
+        .find(|section| section.file_offset(cli_rva).is_some())
+        .expect("No section holds the CLI header!");
+    assert_eq!(text.name(), b".text");
+    assert_eq!(
+        text.file_offset(text.virtual_adress()),
+        Some(text.offset() as usize)
+    );
+    assert_eq!(text.file_offset(text.virtual_adress() + text.size()), None);
+}
+#[test]
+fn pe32_plus_exe() {
+    //ARM64 GUI executable, with the PE header right after the DOS header.
+    let data = build_headers(MACHINE_ARM64, true, IMAGE_FILE_EXECUTABLE_IMAGE, 2);
+    let headers = PeHeaders::parse(Cursor::new(&data[..])).expect("Could not parse headers!");
+    assert!(!headers.is_dll());
+    assert!(headers.is_pe32_plus());
+    assert_eq!(headers.machine(), MACHINE_ARM64);
+    assert_eq!(headers.subsystem(), 2);
+    assert_eq!(headers.cli_header(), (0x2008, 0x48));
+    assert_eq!(headers.sections().len(), 1);
+    assert_eq!(headers.sections()[0].file_offset(0x2008), Some(0x208));
+    let data = build_headers(MACHINE_AMD64, false, IMAGE_FILE_DLL | 0x2, 3);
+    let headers = PeHeaders::parse(Cursor::new(&data[..])).expect("Could not parse headers!");
+    assert!(headers.is_dll());
+    assert!(!headers.is_pe32_plus());
+}
+#[test]
+fn malformed_headers() {
+    let mut bad = build_headers(MACHINE_AMD64, true, 0x2, 3);
+    bad[0] = b'Z';
+    assert!(matches!(
+        PeHeaders::parse(Cursor::new(&bad[..])),
+        Err(ImportError::InvalidMagic {
+            offset: 0,
+            found: 0x5A5A,
+            ..
+        })
+    ));
+    let mut bad = build_headers(MACHINE_AMD64, true, 0x2, 3);
+    bad[0x41] = b'X';
+    assert!(matches!(
+        PeHeaders::parse(Cursor::new(&bad[..])),
+        Err(ImportError::InvalidMagic { offset: 0x40, .. })
+    ));
+    let bad = build_headers(0x1234, true, 0x2, 3);
+    assert!(matches!(
+        PeHeaders::parse(Cursor::new(&bad[..])),
+        Err(ImportError::UnsupportedValue {
+            offset: 0x44,
+            field: "Machine",
+            found: 0x1234
+        })
+    ));
+    let mut bad = build_headers(MACHINE_AMD64, true, 0x2, 3);
+    bad[0x58] = 0x0C;
+    assert!(matches!(
+        PeHeaders::parse(Cursor::new(&bad[..])),
+        Err(ImportError::UnsupportedValue {
+            offset: 0x58,
+            found: 0x20C,
+            ..
+        })
+    ));
+    let data = build_headers(MACHINE_AMD64, true, 0x2, 3);
+    assert!(matches!(
+        PeHeaders::parse(Cursor::new(&data[..0x100])),
+        Err(ImportError::UnexpectedEndOfData { .. })
+    ));
+    //e_lfanew pointing back into the DOS header is fine, as long as the PE signature is there.
+    let mut bad = data;
+    bad[E_LFANEW_OFFSET] = 0x10;
+    assert!(matches!(
+        PeHeaders::parse(Cursor::new(&bad[..])),
+        Err(ImportError::InvalidMagic {
+            offset: 0x10,
+            found: 0,
+            ..
+        })
+    ));
+}
+#[test]
+fn random_access() {
+    let mut data = build_headers(MACHINE_AMD64, true, IMAGE_FILE_EXECUTABLE_IMAGE, 3);
+    data.resize(0x400, 0);
+    data[0x208] = 0x48;
+    data[0x300] = 0xAA;
+    let owned = Image::read(Cursor::new(&data)).expect("Could not read image!");
+    let borrowed = Image::parse(&data).expect("Could not parse image!");
+    for image in [owned, borrowed] {
+        //Later data first, then jump back to the CLI header.
+        assert_eq!(image.read_rva(0x2100, 1).unwrap(), (&[0xAA][..], 0x300));
+        let (cli_header_rva, _) = image.headers().cli_header();
+        let (cli_header, offset) = image.data_at(cli_header_rva).unwrap();
+        assert_eq!(
+            (cli_header[0], cli_header.len(), offset),
+            (0x48, 0x1F8, 0x208)
+        );
+        assert!(matches!(
+            image.read_rva(0x2100, 0x101),
+            Err(ImportError::UnexpectedEndOfData { offset: 0x400 })
+        ));
+        assert!(matches!(
+            image.data_at(0x2200),
+            Err(ImportError::InvalidRva(0x2200))
+        ));
+    }
+    assert!(matches!(
+        Image::parse(&data[..0x300]),
+        Err(ImportError::UnexpectedEndOfData { offset: 0x300 })
+    ));
+    assert!(matches!(
+        Image::read(Cursor::new(&data[..0x300])),
+        Err(ImportError::UnexpectedEndOfData { offset: 0x300 })
+    ));
+}
@@ -0,0 +1,473 @@
+use super::assembly::ImportError;
+use super::slice_reader::SliceReader;
+use super::tables::{CodedIndex, Token};
+use crate::ir::Signature;
+use crate::Type;
+// Element types, as described in ECMA-335 II.23.1.16
+const ELEMENT_TYPE_VOID: u8 = 0x01;
+const ELEMENT_TYPE_BOOLEAN: u8 = 0x02;
+const ELEMENT_TYPE_CHAR: u8 = 0x03;
+const ELEMENT_TYPE_I1: u8 = 0x04;
+const ELEMENT_TYPE_U1: u8 = 0x05;
+const ELEMENT_TYPE_I2: u8 = 0x06;
+const ELEMENT_TYPE_U2: u8 = 0x07;
+const ELEMENT_TYPE_I4: u8 = 0x08;
+const ELEMENT_TYPE_U4: u8 = 0x09;
+const ELEMENT_TYPE_I8: u8 = 0x0A;
+const ELEMENT_TYPE_U8: u8 = 0x0B;
+const ELEMENT_TYPE_R4: u8 = 0x0C;
+const ELEMENT_TYPE_R8: u8 = 0x0D;
+const ELEMENT_TYPE_STRING: u8 = 0x0E;
+const ELEMENT_TYPE_PTR: u8 = 0x0F;
+const ELEMENT_TYPE_BYREF: u8 = 0x10;
+const ELEMENT_TYPE_VALUETYPE: u8 = 0x11;
+const ELEMENT_TYPE_CLASS: u8 = 0x12;
+const ELEMENT_TYPE_VAR: u8 = 0x13;
+const ELEMENT_TYPE_ARRAY: u8 = 0x14;
+const ELEMENT_TYPE_GENERICINST: u8 = 0x15;
+const ELEMENT_TYPE_TYPEDBYREF: u8 = 0x16;
+const ELEMENT_TYPE_I: u8 = 0x18;
+const ELEMENT_TYPE_U: u8 = 0x19;
+const ELEMENT_TYPE_FNPTR: u8 = 0x1B;
+const ELEMENT_TYPE_OBJECT: u8 = 0x1C;
+const ELEMENT_TYPE_SZARRAY: u8 = 0x1D;
+const ELEMENT_TYPE_MVAR: u8 = 0x1E;
+const ELEMENT_TYPE_CMOD_REQD: u8 = 0x1F;
+const ELEMENT_TYPE_CMOD_OPT: u8 = 0x20;
+const ELEMENT_TYPE_SENTINEL: u8 = 0x41;
+const ELEMENT_TYPE_PINNED: u8 = 0x45;
+// Calling convention flags, ECMA-335 II.23.2.1
+const HAS_THIS: u8 = 0x20;
+const EXPLICIT_THIS: u8 = 0x40;
+const GENERIC: u8 = 0x10;
+const CALL_KIND_MASK: u8 = 0x0F;
+const DEFAULT: u8 = 0x0;
+const VARARG: u8 = 0x5;
+const FIELD_SIG: u8 = 0x06;
+const LOCAL_SIG: u8 = 0x07;
+/// Types nested deeper than this are rejected, so that malicious blobs can't overflow the stack.
+const MAX_NESTING: u32 = 64;
+/// A type, exactly as encoded in a signature blob.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ElementType {
+    Void,
+    Bool,
+    Char,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    IPtr,
+    UPtr,
+    String,
+    Object,
+    TypedByRef,
+    Class(Token),
+    ValueType(Token),
+    ByRef(Box<ElementType>),
+    Ptr(Box<ElementType>),
+    SzArray(Box<ElementType>),
+    Array(Box<ElementType>, u32),
+    GenericInst(Box<ElementType>, Vec<ElementType>),
+    Var(u32),
+    MVar(u32),
+}
+impl ElementType {
+    /// Converts this type into its IR representation, if it has one.
+    pub(crate) fn to_type(&self) -> Result<Type, ImportError> {
+        Ok(match self {
+            Self::Void => Type::Void,
+            Self::Bool => Type::Bool,
+            Self::Char => Type::Char,
+            Self::I1 => Type::I8,
+            Self::U1 => Type::U8,
+            Self::I2 => Type::I16,
+            Self::U2 => Type::U16,
+            Self::I4 => Type::I32,
+            Self::U4 => Type::U32,
+            Self::I8 => Type::I64,
+            Self::U8 => Type::U64,
+            Self::R4 => Type::F32,
+            Self::R8 => Type::F64,
+            Self::IPtr => Type::IPtr,
+            Self::UPtr => Type::UPtr,
+            //All reference types are just object references for now.
+            Self::String | Self::Object | Self::Class(_) | Self::SzArray(_) | Self::Array(..) => {
+                Type::ObjRef
+            }
+            Self::GenericInst(generic, _) if matches!(**generic, Self::Class(_)) => Type::ObjRef,
+            Self::GenericInst(..) => return Err(ImportError::UnsupportedType("generic valuetype")),
+            Self::ValueType(_) => return Err(ImportError::UnsupportedType("valuetype")),
+            Self::ByRef(_) => return Err(ImportError::UnsupportedType("byref")),
+            Self::Ptr(_) => return Err(ImportError::UnsupportedType("pointer")),
+            Self::TypedByRef => return Err(ImportError::UnsupportedType("typedref")),
+            Self::Var(_) | Self::MVar(_) => {
+                return Err(ImportError::UnsupportedType("generic parameter"))
+            }
+        })
+    }
+}
+impl ElementType {
+    /// Size of a value of this type, in bytes. Returns `None` for valuetypes, whose layout lives in the metadata tables.
+    pub(crate) fn size(&self) -> Option<u32> {
+        Some(match self {
+            Self::Bool | Self::I1 | Self::U1 => 1,
+            Self::Char | Self::I2 | Self::U2 => 2,
+            Self::I4 | Self::U4 | Self::R4 => 4,
+            Self::I8 | Self::U8 | Self::R8 => 8,
+            Self::IPtr
+            | Self::UPtr
+            | Self::String
+            | Self::Object
+            | Self::Class(_)
+            | Self::ByRef(_)
+            | Self::Ptr(_)
+            | Self::SzArray(_)
+            | Self::Array(..) => std::mem::size_of::<usize>() as u32,
+            Self::GenericInst(generic, _) if matches!(**generic, Self::Class(_)) => {
+                std::mem::size_of::<usize>() as u32
+            }
+            _ => return None,
+        })
+    }
+}
+/// Decodes a primitive element type, such as `ELEMENT_TYPE_I4`.
+pub(crate) fn primitive(element: u8) -> Option<ElementType> {
+    Some(match element {
+        ELEMENT_TYPE_BOOLEAN => ElementType::Bool,
+        ELEMENT_TYPE_CHAR => ElementType::Char,
+        ELEMENT_TYPE_I1 => ElementType::I1,
+        ELEMENT_TYPE_U1 => ElementType::U1,
+        ELEMENT_TYPE_I2 => ElementType::I2,
+        ELEMENT_TYPE_U2 => ElementType::U2,
+        ELEMENT_TYPE_I4 => ElementType::I4,
+        ELEMENT_TYPE_U4 => ElementType::U4,
+        ELEMENT_TYPE_I8 => ElementType::I8,
+        ELEMENT_TYPE_U8 => ElementType::U8,
+        ELEMENT_TYPE_R4 => ElementType::R4,
+        ELEMENT_TYPE_R8 => ElementType::R8,
+        _ => return None,
+    })
+}
+/// A MethodDefSig or MethodRefSig(ECMA-335 II.23.2.1, II.23.2.2).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MethodSig {
+    has_this: bool,
+    explicit_this: bool,
+    generic_params: u32,
+    ret: ElementType,
+    params: Vec<ElementType>,
+    vararg_params: Vec<ElementType>,
+}
+impl MethodSig {
+    /// Decodes the signature in `blob`, which begins at file offset `base`.
+    pub(crate) fn decode(blob: &[u8], base: usize) -> Result<Self, ImportError> {
+        let mut reader = SliceReader::at(blob, base);
+        let calling_convention = reader.read_u8()?;
+        let call_kind = calling_convention & CALL_KIND_MASK;
+        if call_kind != DEFAULT && call_kind != VARARG {
+            return Err(ImportError::UnsupportedCallingConvention {
+                offset: base,
+                found: calling_convention,
+            });
+        }
+        let generic_params = if calling_convention & GENERIC != 0 {
+            reader.read_compressed_u32()?
+        } else {
+            0
+        };
+        let param_count = reader.read_compressed_u32()?;
+        let ret = read_type(&mut reader, 0)?;
+        let mut params = Vec::new();
+        let mut vararg_params = Vec::new();
+        for _ in 0..param_count {
+            if reader.peek_u8()? == ELEMENT_TYPE_SENTINEL {
+                reader.skip(1)?;
+                //Everything after the sentinel is a vararg parameter.
+                vararg_params.push(read_type(&mut reader, 0)?);
+            } else if vararg_params.is_empty() {
+                params.push(read_type(&mut reader, 0)?);
+            } else {
+                vararg_params.push(read_type(&mut reader, 0)?);
+            }
+        }
+        Ok(Self {
+            has_this: calling_convention & HAS_THIS != 0,
+            explicit_this: calling_convention & EXPLICIT_THIS != 0,
+            generic_params,
+            ret,
+            params,
+            vararg_params,
+        })
+    }
+    /// Whether the method takes an implicit `this` argument.
+    pub(crate) fn has_this(&self) -> bool {
+        self.has_this && !self.explicit_this
+    }
+    pub(crate) fn generic_params(&self) -> u32 {
+        self.generic_params
+    }
+    pub(crate) fn ret(&self) -> &ElementType {
+        &self.ret
+    }
+    pub(crate) fn params(&self) -> &[ElementType] {
+        &self.params
+    }
+    /// Converts this signature into its IR representation. The implicit `this` becomes the first argument.
+    pub(crate) fn to_signature(&self) -> Result<Signature, ImportError> {
+        if self.generic_params != 0 {
+            return Err(ImportError::UnsupportedType("generic method"));
+        }
+        if !self.vararg_params.is_empty() {
+            return Err(ImportError::UnsupportedType("vararg"));
+        }
+        let mut args = Vec::with_capacity(self.params.len() + 1);
+        if self.has_this() {
+            args.push(Type::ObjRef);
+        }
+        for param in &self.params {
+            args.push(param.to_type()?);
+        }
+        let ret = self.ret.to_type()?;
+        Ok(Signature::new(&(&args[..], ret)))
+    }
+}
+/// Decodes a LocalVarSig(ECMA-335 II.23.2.6) beginning at file offset `base`.
+pub(crate) fn decode_locals(blob: &[u8], base: usize) -> Result<Vec<ElementType>, ImportError> {
+    let mut reader = SliceReader::at(blob, base);
+    let kind = reader.read_u8()?;
+    if kind != LOCAL_SIG {
+        return Err(ImportError::InvalidSignature {
+            offset: base,
+            found: kind,
+        });
+    }
+    let count = reader.read_compressed_u32()?;
+    let mut locals = Vec::new();
+    for _ in 0..count {
+        //Objects are never moved, so pinning changes nothing.
+        skip_custom_mods(&mut reader)?;
+        if reader.peek_u8()? == ELEMENT_TYPE_PINNED {
+            reader.skip(1)?;
+        }
+        locals.push(read_type(&mut reader, 0)?);
+    }
+    Ok(locals)
+}
+/// Decodes a FieldSig(ECMA-335 II.23.2.4) beginning at file offset `base`.
+pub(crate) fn decode_field(blob: &[u8], base: usize) -> Result<ElementType, ImportError> {
+    let mut reader = SliceReader::at(blob, base);
+    let kind = reader.read_u8()?;
+    if kind != FIELD_SIG {
+        return Err(ImportError::InvalidSignature {
+            offset: base,
+            found: kind,
+        });
+    }
+    read_type(&mut reader, 0)
+}
+/// Decodes a LocalVarSig into the IR types of locals.
+pub(crate) fn local_types(blob: &[u8], base: usize) -> Result<Vec<Type>, ImportError> {
+    decode_locals(blob, base)?
+        .iter()
+        .map(ElementType::to_type)
+        .collect()
+}
+fn read_type_token(reader: &mut SliceReader) -> Result<Token, ImportError> {
+    let offset = reader.file_offset();
+    let value = reader.read_compressed_u32()?;
+    CodedIndex::TypeDefOrRef
+        .decode(value)
+        .ok_or(ImportError::InvalidCodedIndex { offset, value })
+}
+/// Skips optional and required custom modifiers, which have no effect on the IR.
+fn skip_custom_mods(reader: &mut SliceReader) -> Result<(), ImportError> {
+    while matches!(
+        reader.peek_u8()?,
+        ELEMENT_TYPE_CMOD_REQD | ELEMENT_TYPE_CMOD_OPT
+    ) {
+        reader.skip(1)?;
+        read_type_token(reader)?;
+    }
+    Ok(())
+}
+fn read_type(reader: &mut SliceReader, depth: u32) -> Result<ElementType, ImportError> {
+    if depth > MAX_NESTING {
+        return Err(ImportError::UnsupportedType("deeply nested type"));
+    }
+    skip_custom_mods(reader)?;
+    let offset = reader.file_offset();
+    let element = reader.read_u8()?;
+    if let Some(primitive) = primitive(element) {
+        return Ok(primitive);
+    }
+    Ok(match element {
+        ELEMENT_TYPE_VOID => ElementType::Void,
+        ELEMENT_TYPE_I => ElementType::IPtr,
+        ELEMENT_TYPE_U => ElementType::UPtr,
+        ELEMENT_TYPE_STRING => ElementType::String,
+        ELEMENT_TYPE_OBJECT => ElementType::Object,
+        ELEMENT_TYPE_TYPEDBYREF => ElementType::TypedByRef,
+        ELEMENT_TYPE_CLASS => ElementType::Class(read_type_token(reader)?),
+        ELEMENT_TYPE_VALUETYPE => ElementType::ValueType(read_type_token(reader)?),
+        ELEMENT_TYPE_BYREF => ElementType::ByRef(Box::new(read_type(reader, depth + 1)?)),
+        ELEMENT_TYPE_PTR => ElementType::Ptr(Box::new(read_type(reader, depth + 1)?)),
+        ELEMENT_TYPE_SZARRAY => ElementType::SzArray(Box::new(read_type(reader, depth + 1)?)),
+        ELEMENT_TYPE_ARRAY => {
+            let element = read_type(reader, depth + 1)?;
+            let rank = reader.read_compressed_u32()?;
+            //Sizes and lower bounds are not needed, since arrays are only ever referenced.
+            let sizes = reader.read_compressed_u32()?;
+            for _ in 0..sizes {
+                reader.read_compressed_u32()?;
+            }
+            let lower_bounds = reader.read_compressed_u32()?;
+            for _ in 0..lower_bounds {
+                reader.read_compressed_u32()?;
+            }
+            ElementType::Array(Box::new(element), rank)
+        }
+        ELEMENT_TYPE_GENERICINST => {
+            let generic = read_type(reader, depth + 1)?;
+            if !matches!(generic, ElementType::Class(_) | ElementType::ValueType(_)) {
+                return Err(ImportError::InvalidSignature {
+                    offset,
+                    found: ELEMENT_TYPE_GENERICINST,
+                });
+            }
+            let count = reader.read_compressed_u32()?;
+            let mut args = Vec::new();
+            for _ in 0..count {
+                args.push(read_type(reader, depth + 1)?);
+            }
+            ElementType::GenericInst(Box::new(generic), args)
+        }
+        ELEMENT_TYPE_VAR => ElementType::Var(reader.read_compressed_u32()?),
+        ELEMENT_TYPE_MVAR => ElementType::MVar(reader.read_compressed_u32()?),
+        ELEMENT_TYPE_FNPTR => return Err(ImportError::UnsupportedType("function pointer")),
+        _ => {
+            return Err(ImportError::InvalidSignature {
+                offset,
+                found: element,
+            })
+        }
+    })
+}
+#[test]
+fn static_method_sig() {
+    //int32 Add(int32, int32)
+    let sig = MethodSig::decode(&[0x00, 0x02, 0x08, 0x08, 0x08], 0).expect("Could not decode sig");
+    assert!(!sig.has_this());
+    assert_eq!(sig.params(), &[ElementType::I4, ElementType::I4]);
+    let sig = sig.to_signature().expect("Could not convert sig");
+    assert_eq!(sig.args(), &[Type::I32, Type::I32]);
+    assert_eq!(sig.ret(), &Type::I32);
+}
+#[test]
+fn instance_method_sig() {
+    //instance void M(string, class TypeRef 1, float64[], class List`1<object>, int32 modopt(TypeRef 2))
+    #[rustfmt::skip]
+    let blob = [
+        0x20, 0x05, 0x01,
+        0x0E,
+        0x12, 0x05,
+        0x1D, 0x0D,
+        0x15, 0x12, 0x09, 0x01, 0x1C,
+        0x20, 0x09, 0x08,
+    ];
+    let sig = MethodSig::decode(&blob, 0).expect("Could not decode sig");
+    assert!(sig.has_this());
+    assert_eq!(
+        sig.params()[1],
+        ElementType::Class(Token::from_raw(0x0100_0001))
+    );
+    assert_eq!(
+        sig.params()[2],
+        ElementType::SzArray(Box::new(ElementType::R8))
+    );
+    assert_eq!(
+        sig.params()[3],
+        ElementType::GenericInst(
+            Box::new(ElementType::Class(Token::from_raw(0x0100_0002))),
+            vec![ElementType::Object]
+        )
+    );
+    assert_eq!(sig.params()[4], ElementType::I4);
+    let sig = sig.to_signature().expect("Could not convert sig");
+    assert_eq!(
+        sig.args(),
+        &[
+            Type::ObjRef,
+            Type::ObjRef,
+            Type::ObjRef,
+            Type::ObjRef,
+            Type::ObjRef,
+            Type::I32
+        ]
+    );
+    assert_eq!(sig.ret(), &Type::Void);
+}
+#[test]
+fn locals_sig() {
+    //int64, uint8, pinned object, char&
+    let blob = [0x07, 0x04, 0x0A, 0x05, 0x45, 0x1C, 0x10, 0x03];
+    let locals = decode_locals(&blob, 0).expect("Could not decode locals");
+    assert_eq!(locals[2], ElementType::Object);
+    assert_eq!(locals[3], ElementType::ByRef(Box::new(ElementType::Char)));
+    assert!(matches!(
+        local_types(&blob, 0),
+        Err(ImportError::UnsupportedType("byref"))
+    ));
+    let blob = [0x07, 0x03, 0x0A, 0x05, 0x45, 0x1C];
+    assert_eq!(
+        local_types(&blob, 0).expect("Could not convert locals"),
+        [Type::I64, Type::U8, Type::ObjRef]
+    );
+    assert!(matches!(
+        decode_locals(&[0x06, 0x08], 0x300),
+        Err(ImportError::InvalidSignature {
+            offset: 0x300,
+            found: 0x06
+        })
+    ));
+    assert!(matches!(
+        local_types(&[0x07, 0x01, 0x11, 0x08], 0),
+        Err(ImportError::UnsupportedType("valuetype"))
+    ));
+    //int32[][][]... nested far too deep
+    let mut blob = vec![0x07, 0x01];
+    blob.extend([ELEMENT_TYPE_SZARRAY; 10_000]);
+    blob.push(0x08);
+    assert!(matches!(
+        decode_locals(&blob, 0),
+        Err(ImportError::UnsupportedType("deeply nested type"))
+    ));
+}
+#[test]
+fn field_sig() {
+    //int64
+    let field = decode_field(&[0x06, 0x0A], 0).expect("Could not decode field");
+    assert_eq!(field, ElementType::I8);
+    assert_eq!(field.size(), Some(8));
+    //valuetype TypeDef 3, with a modreq
+    let field = decode_field(&[0x06, 0x1F, 0x09, 0x11, 0x0C], 0).expect("Could not decode field");
+    assert_eq!(field, ElementType::ValueType(Token::from_raw(0x0200_0003)));
+    assert_eq!(field.size(), None);
+    assert_eq!(
+        ElementType::SzArray(Box::new(ElementType::U1)).size(),
+        Some(std::mem::size_of::<usize>() as u32)
+    );
+    assert!(matches!(
+        decode_field(&[0x07, 0x08], 0x200),
+        Err(ImportError::InvalidSignature {
+            offset: 0x200,
+            found: 0x07
+        })
+    ));
+}
@@ -0,0 +1,131 @@
+use super::assembly::ImportError;
+/// Little-endian cursor over an in-memory part of an assembly.
+pub(crate) struct SliceReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    base: usize,
+}
+impl<'a> SliceReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self::at(data, 0)
+    }
+    /// Creates a reader over `data`, which begins at file offset `base`. Errors report file offsets.
+    pub(crate) fn at(data: &'a [u8], base: usize) -> Self {
+        Self {
+            data,
+            offset: 0,
+            base,
+        }
+    }
+    /// Offset relative to the beginning of the data.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Offset within the whole file.
+    pub(crate) fn file_offset(&self) -> usize {
+        self.base + self.offset
+    }
+    fn end_of_data(&self) -> ImportError {
+        ImportError::UnexpectedEndOfData {
+            offset: self.file_offset(),
+        }
+    }
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+    pub(crate) fn seek(&mut self, offset: usize) -> Result<(), ImportError> {
+        if offset > self.data.len() {
+            return Err(self.end_of_data());
+        }
+        self.offset = offset;
+        Ok(())
+    }
+    pub(crate) fn skip(&mut self, ammount: usize) -> Result<(), ImportError> {
+        self.seek(self.offset + ammount)
+    }
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ImportError> {
+        if self.remaining() < len {
+            return Err(self.end_of_data());
+        }
+        let res = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(res)
+    }
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ImportError> {
+        let bytes = self.read_bytes(N)?;
+        Ok(bytes.try_into().unwrap())
+    }
+    pub(crate) fn read_u8(&mut self) -> Result<u8, ImportError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+    /// Returns the next byte without consuming it.
+    pub(crate) fn peek_u8(&self) -> Result<u8, ImportError> {
+        self.data
+            .get(self.offset)
+            .copied()
+            .ok_or_else(|| self.end_of_data())
+    }
+    pub(crate) fn read_u16(&mut self) -> Result<u16, ImportError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+    pub(crate) fn read_u32(&mut self) -> Result<u32, ImportError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+    pub(crate) fn read_u64(&mut self) -> Result<u64, ImportError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+    /// Reads a null-terminated string, consuming the terminator.
+    pub(crate) fn read_cstr(&mut self) -> Result<&'a str, ImportError> {
+        let rest = &self.data[self.offset..];
+        let len = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| self.end_of_data())?;
+        let res = std::str::from_utf8(&rest[..len]).map_err(|_| ImportError::InvalidUtf8 {
+            offset: self.file_offset(),
+        })?;
+        self.offset += len + 1;
+        Ok(res)
+    }
+    /// Reads an unsigned integer compressed as described in ECMA-335 II.23.2.
+    pub(crate) fn read_compressed_u32(&mut self) -> Result<u32, ImportError> {
+        let offset = self.file_offset();
+        let first = self.read_u8()?;
+        if first & 0x80 == 0 {
+            Ok(u32::from(first))
+        } else if first & 0xC0 == 0x80 {
+            let second = self.read_u8()?;
+            Ok((u32::from(first & 0x3F) << 8) | u32::from(second))
+        } else if first & 0xE0 == 0xC0 {
+            let rest = self.read_array::<3>()?;
+            Ok((u32::from(first & 0x1F) << 24)
+                | (u32::from(rest[0]) << 16)
+                | (u32::from(rest[1]) << 8)
+                | u32::from(rest[2]))
+        } else {
+            Err(ImportError::InvalidCompressedInteger { offset })
+        }
+    }
+}
+#[test]
+fn compressed_u32() {
+    let data = [
+        0x03, 0x7F, 0x80, 0x80, 0xAE, 0x57, 0xBF, 0xFF, 0xC0, 0x00, 0x40, 0x00, 0xDF, 0xFF, 0xFF,
+        0xFF,
+    ];
+    let mut reader = SliceReader::new(&data);
+    for expected in [0x03, 0x7F, 0x80, 0x2E57, 0x3FFF, 0x4000, 0x1FFF_FFFF] {
+        assert_eq!(reader.read_compressed_u32().unwrap(), expected);
+    }
+    assert_eq!(reader.remaining(), 0);
+    let mut reader = SliceReader::at(&[0x00, 0xFF, 0xC0], 0x200);
+    reader.skip(1).unwrap();
+    assert!(matches!(
+        reader.read_compressed_u32(),
+        Err(ImportError::InvalidCompressedInteger { offset: 0x201 })
+    ));
+    assert!(matches!(
+        reader.read_compressed_u32(),
+        Err(ImportError::UnexpectedEndOfData { offset: 0x203 })
+    ));
+}
@@ -0,0 +1,1030 @@
+use super::assembly::ImportError;
+use super::slice_reader::SliceReader;
+use std::ops::Range;
+pub(crate) type StringIndex = u32;
+pub(crate) type BlobIndex = u32;
+pub(crate) type GuidIndex = u32;
+/// 1-based index of a row in a metadata table. 0 means "no row".
+pub(crate) type RowIndex = u32;
+const TABLE_COUNT: usize = 0x2D;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum TableKind {
+    Module = 0x00,
+    TypeRef = 0x01,
+    TypeDef = 0x02,
+    FieldPtr = 0x03,
+    Field = 0x04,
+    MethodPtr = 0x05,
+    MethodDef = 0x06,
+    ParamPtr = 0x07,
+    Param = 0x08,
+    InterfaceImpl = 0x09,
+    MemberRef = 0x0A,
+    Constant = 0x0B,
+    CustomAttribute = 0x0C,
+    FieldMarshal = 0x0D,
+    DeclSecurity = 0x0E,
+    ClassLayout = 0x0F,
+    FieldLayout = 0x10,
+    StandAloneSig = 0x11,
+    EventMap = 0x12,
+    EventPtr = 0x13,
+    Event = 0x14,
+    PropertyMap = 0x15,
+    PropertyPtr = 0x16,
+    Property = 0x17,
+    MethodSemantics = 0x18,
+    MethodImpl = 0x19,
+    ModuleRef = 0x1A,
+    TypeSpec = 0x1B,
+    ImplMap = 0x1C,
+    FieldRVA = 0x1D,
+    EncLog = 0x1E,
+    EncMap = 0x1F,
+    Assembly = 0x20,
+    AssemblyProcessor = 0x21,
+    AssemblyOS = 0x22,
+    AssemblyRef = 0x23,
+    AssemblyRefProcessor = 0x24,
+    AssemblyRefOS = 0x25,
+    File = 0x26,
+    ExportedType = 0x27,
+    ManifestResource = 0x28,
+    NestedClass = 0x29,
+    GenericParam = 0x2A,
+    MethodSpec = 0x2B,
+    GenericParamConstraint = 0x2C,
+}
+impl TableKind {
+    const ALL: [TableKind; TABLE_COUNT] = [
+        Self::Module,
+        Self::TypeRef,
+        Self::TypeDef,
+        Self::FieldPtr,
+        Self::Field,
+        Self::MethodPtr,
+        Self::MethodDef,
+        Self::ParamPtr,
+        Self::Param,
+        Self::InterfaceImpl,
+        Self::MemberRef,
+        Self::Constant,
+        Self::CustomAttribute,
+        Self::FieldMarshal,
+        Self::DeclSecurity,
+        Self::ClassLayout,
+        Self::FieldLayout,
+        Self::StandAloneSig,
+        Self::EventMap,
+        Self::EventPtr,
+        Self::Event,
+        Self::PropertyMap,
+        Self::PropertyPtr,
+        Self::Property,
+        Self::MethodSemantics,
+        Self::MethodImpl,
+        Self::ModuleRef,
+        Self::TypeSpec,
+        Self::ImplMap,
+        Self::FieldRVA,
+        Self::EncLog,
+        Self::EncMap,
+        Self::Assembly,
+        Self::AssemblyProcessor,
+        Self::AssemblyOS,
+        Self::AssemblyRef,
+        Self::AssemblyRefProcessor,
+        Self::AssemblyRefOS,
+        Self::File,
+        Self::ExportedType,
+        Self::ManifestResource,
+        Self::NestedClass,
+        Self::GenericParam,
+        Self::MethodSpec,
+        Self::GenericParamConstraint,
+    ];
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+    fn columns(self) -> &'static [Column] {
+        use CodedIndex as CI;
+        use Column::*;
+        use TableKind as T;
+        match self {
+            Self::Module => &[U16, String, Guid, Guid, Guid],
+            Self::TypeRef => &[Coded(CI::ResolutionScope), String, String],
+            Self::TypeDef => &[
+                U32,
+                String,
+                String,
+                Coded(CI::TypeDefOrRef),
+                Table(T::Field),
+                Table(T::MethodDef),
+            ],
+            Self::FieldPtr => &[Table(T::Field)],
+            Self::Field => &[U16, String, Blob],
+            Self::MethodPtr => &[Table(T::MethodDef)],
+            Self::MethodDef => &[U32, U16, U16, String, Blob, Table(T::Param)],
+            Self::ParamPtr => &[Table(T::Param)],
+            Self::Param => &[U16, U16, String],
+            Self::InterfaceImpl => &[Table(T::TypeDef), Coded(CI::TypeDefOrRef)],
+            Self::MemberRef => &[Coded(CI::MemberRefParent), String, Blob],
+            //Type is a single byte followed by a padding byte.
+            Self::Constant => &[U16, Coded(CI::HasConstant), Blob],
+            Self::CustomAttribute => &[
+                Coded(CI::HasCustomAttribute),
+                Coded(CI::CustomAttributeType),
+                Blob,
+            ],
+            Self::FieldMarshal => &[Coded(CI::HasFieldMarshal), Blob],
+            Self::DeclSecurity => &[U16, Coded(CI::HasDeclSecurity), Blob],
+            Self::ClassLayout => &[U16, U32, Table(T::TypeDef)],
+            Self::FieldLayout => &[U32, Table(T::Field)],
+            Self::StandAloneSig => &[Blob],
+            Self::EventMap => &[Table(T::TypeDef), Table(T::Event)],
+            Self::EventPtr => &[Table(T::Event)],
+            Self::Event => &[U16, String, Coded(CI::TypeDefOrRef)],
+            Self::PropertyMap => &[Table(T::TypeDef), Table(T::Property)],
+            Self::PropertyPtr => &[Table(T::Property)],
+            Self::Property => &[U16, String, Blob],
+            Self::MethodSemantics => &[U16, Table(T::MethodDef), Coded(CI::HasSemantics)],
+            Self::MethodImpl => &[
+                Table(T::TypeDef),
+                Coded(CI::MethodDefOrRef),
+                Coded(CI::MethodDefOrRef),
+            ],
+            Self::ModuleRef => &[String],
+            Self::TypeSpec => &[Blob],
+            Self::ImplMap => &[U16, Coded(CI::MemberForwarded), String, Table(T::ModuleRef)],
+            Self::FieldRVA => &[U32, Table(T::Field)],
+            Self::EncLog => &[U32, U32],
+            Self::EncMap => &[U32],
+            Self::Assembly => &[U32, U16, U16, U16, U16, U32, Blob, String, String],
+            Self::AssemblyProcessor => &[U32],
+            Self::AssemblyOS => &[U32, U32, U32],
+            Self::AssemblyRef => &[U16, U16, U16, U16, U32, Blob, String, String, Blob],
+            Self::AssemblyRefProcessor => &[U32, Table(T::AssemblyRef)],
+            Self::AssemblyRefOS => &[U32, U32, U32, Table(T::AssemblyRef)],
+            Self::File => &[U32, String, Blob],
+            Self::ExportedType => &[U32, U32, String, String, Coded(CI::Implementation)],
+            Self::ManifestResource => &[U32, U32, String, Coded(CI::Implementation)],
+            Self::NestedClass => &[Table(T::TypeDef), Table(T::TypeDef)],
+            Self::GenericParam => &[U16, U16, Coded(CI::TypeOrMethodDef), String],
+            Self::MethodSpec => &[Coded(CI::MethodDefOrRef), Blob],
+            Self::GenericParamConstraint => &[Table(T::GenericParam), Coded(CI::TypeDefOrRef)],
+        }
+    }
+}
+/// A metadata token: the table id in the top byte, and a 1-based row index in the lower 3 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Token(u32);
+/// Pseudo-table id of tokens pointing into the `#US` heap.
+const USER_STRING_TOKEN: u32 = 0x70;
+impl Token {
+    pub(crate) fn new(table: TableKind, row: RowIndex) -> Self {
+        Self(((table as u32) << 24) | row)
+    }
+    pub(crate) fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+    pub(crate) fn raw(self) -> u32 {
+        self.0
+    }
+    pub(crate) fn table(self) -> Option<TableKind> {
+        TableKind::from_id((self.0 >> 24) as u8)
+    }
+    pub(crate) fn row(self) -> RowIndex {
+        self.0 & 0x00FF_FFFF
+    }
+    pub(crate) fn is_null(self) -> bool {
+        self.row() == 0
+    }
+    /// Whether this token refers to the `#US` heap(as used by `ldstr`), in which case `row` is a heap index.
+    pub(crate) fn is_user_string(self) -> bool {
+        self.0 >> 24 == USER_STRING_TOKEN
+    }
+}
+/// Kinds of coded indices(ECMA-335 II.24.2.6). `None` marks tags unused by the standard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CodedIndex {
+    TypeDefOrRef,
+    HasConstant,
+    HasCustomAttribute,
+    HasFieldMarshal,
+    HasDeclSecurity,
+    MemberRefParent,
+    HasSemantics,
+    MethodDefOrRef,
+    MemberForwarded,
+    Implementation,
+    CustomAttributeType,
+    ResolutionScope,
+    TypeOrMethodDef,
+}
+impl CodedIndex {
+    fn tables(self) -> &'static [Option<TableKind>] {
+        use TableKind as T;
+        match self {
+            Self::TypeDefOrRef => &[Some(T::TypeDef), Some(T::TypeRef), Some(T::TypeSpec)],
+            Self::HasConstant => &[Some(T::Field), Some(T::Param), Some(T::Property)],
+            Self::HasCustomAttribute => &[
+                Some(T::MethodDef),
+                Some(T::Field),
+                Some(T::TypeRef),
+                Some(T::TypeDef),
+                Some(T::Param),
+                Some(T::InterfaceImpl),
+                Some(T::MemberRef),
+                Some(T::Module),
+                Some(T::DeclSecurity),
+                Some(T::Property),
+                Some(T::Event),
+                Some(T::StandAloneSig),
+                Some(T::ModuleRef),
+                Some(T::TypeSpec),
+                Some(T::Assembly),
+                Some(T::AssemblyRef),
+                Some(T::File),
+                Some(T::ExportedType),
+                Some(T::ManifestResource),
+                Some(T::GenericParam),
+                Some(T::GenericParamConstraint),
+                Some(T::MethodSpec),
+            ],
+            Self::HasFieldMarshal => &[Some(T::Field), Some(T::Param)],
+            Self::HasDeclSecurity => &[Some(T::TypeDef), Some(T::MethodDef), Some(T::Assembly)],
+            Self::MemberRefParent => &[
+                Some(T::TypeDef),
+                Some(T::TypeRef),
+                Some(T::ModuleRef),
+                Some(T::MethodDef),
+                Some(T::TypeSpec),
+            ],
+            Self::HasSemantics => &[Some(T::Event), Some(T::Property)],
+            Self::MethodDefOrRef => &[Some(T::MethodDef), Some(T::MemberRef)],
+            Self::MemberForwarded => &[Some(T::Field), Some(T::MethodDef)],
+            Self::Implementation => &[Some(T::File), Some(T::AssemblyRef), Some(T::ExportedType)],
+            Self::CustomAttributeType => {
+                &[None, None, Some(T::MethodDef), Some(T::MemberRef), None]
+            }
+            Self::ResolutionScope => &[
+                Some(T::Module),
+                Some(T::ModuleRef),
+                Some(T::AssemblyRef),
+                Some(T::TypeRef),
+            ],
+            Self::TypeOrMethodDef => &[Some(T::TypeDef), Some(T::MethodDef)],
+        }
+    }
+    fn tag_bits(self) -> u32 {
+        let tag_count = self.tables().len() as u32;
+        u32::BITS - (tag_count - 1).leading_zeros()
+    }
+    /// Converts a coded index into a token. Returns `None` for tags that do not name a table.
+    pub(crate) fn decode(self, value: u32) -> Option<Token> {
+        let bits = self.tag_bits();
+        let tag = value & ((1 << bits) - 1);
+        let table = (*self.tables().get(tag as usize)?)?;
+        Some(Token::new(table, value >> bits))
+    }
+}
+#[derive(Debug, Clone, Copy)]
+enum Column {
+    U16,
+    U32,
+    String,
+    Guid,
+    Blob,
+    Table(TableKind),
+    Coded(CodedIndex),
+}
+/// Index widths, as dictated by the `HeapSizes` flags and row counts.
+#[derive(Debug, Clone)]
+struct IndexSizes {
+    wide_strings: bool,
+    wide_guids: bool,
+    wide_blobs: bool,
+    row_counts: [u32; TABLE_COUNT],
+}
+impl IndexSizes {
+    fn table_index_size(&self, table: TableKind) -> usize {
+        if self.row_counts[table as usize] < (1 << 16) {
+            2
+        } else {
+            4
+        }
+    }
+    fn coded_index_size(&self, coded: CodedIndex) -> usize {
+        let max_rows = coded
+            .tables()
+            .iter()
+            .flatten()
+            .map(|table| self.row_counts[*table as usize])
+            .max()
+            .unwrap_or(0);
+        if max_rows < (1 << (16 - coded.tag_bits())) {
+            2
+        } else {
+            4
+        }
+    }
+    fn column_size(&self, column: Column) -> usize {
+        let wide = |is_wide| if is_wide { 4 } else { 2 };
+        match column {
+            Column::U16 => 2,
+            Column::U32 => 4,
+            Column::String => wide(self.wide_strings),
+            Column::Guid => wide(self.wide_guids),
+            Column::Blob => wide(self.wide_blobs),
+            Column::Table(table) => self.table_index_size(table),
+            Column::Coded(coded) => self.coded_index_size(coded),
+        }
+    }
+    fn row_size(&self, table: TableKind) -> usize {
+        table
+            .columns()
+            .iter()
+            .map(|column| self.column_size(*column))
+            .sum()
+    }
+}
+pub(crate) struct RowReader<'a, 'b> {
+    reader: SliceReader<'a>,
+    sizes: &'b IndexSizes,
+}
+impl<'a, 'b> RowReader<'a, 'b> {
+    fn read_sized(&mut self, size: usize) -> Result<u32, ImportError> {
+        if size == 2 {
+            Ok(u32::from(self.reader.read_u16()?))
+        } else {
+            self.reader.read_u32()
+        }
+    }
+    fn u16(&mut self) -> Result<u16, ImportError> {
+        self.reader.read_u16()
+    }
+    fn u32(&mut self) -> Result<u32, ImportError> {
+        self.reader.read_u32()
+    }
+    fn string(&mut self) -> Result<StringIndex, ImportError> {
+        self.read_sized(self.sizes.column_size(Column::String))
+    }
+    fn guid(&mut self) -> Result<GuidIndex, ImportError> {
+        self.read_sized(self.sizes.column_size(Column::Guid))
+    }
+    fn blob(&mut self) -> Result<BlobIndex, ImportError> {
+        self.read_sized(self.sizes.column_size(Column::Blob))
+    }
+    fn index(&mut self, table: TableKind) -> Result<RowIndex, ImportError> {
+        self.read_sized(self.sizes.table_index_size(table))
+    }
+    fn coded(&mut self, coded: CodedIndex) -> Result<Token, ImportError> {
+        let offset = self.reader.file_offset();
+        let value = self.read_sized(self.sizes.coded_index_size(coded))?;
+        coded
+            .decode(value)
+            .ok_or(ImportError::InvalidCodedIndex { offset, value })
+    }
+}
+pub(crate) trait Row: Sized {
+    const TABLE: TableKind;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError>;
+}
+#[derive(Debug, Clone)]
+pub(crate) struct ModuleRow {
+    #[allow(dead_code)]
+    pub(crate) name: StringIndex,
+    #[allow(dead_code)]
+    pub(crate) mvid: GuidIndex,
+}
+impl Row for ModuleRow {
+    const TABLE: TableKind = TableKind::Module;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        let _generation = reader.u16()?;
+        let name = reader.string()?;
+        let mvid = reader.guid()?;
+        let _enc_id = reader.guid()?;
+        let _enc_base_id = reader.guid()?;
+        Ok(Self { name, mvid })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct TypeRefRow {
+    pub(crate) resolution_scope: Token,
+    pub(crate) name: StringIndex,
+    pub(crate) namespace: StringIndex,
+}
+impl Row for TypeRefRow {
+    const TABLE: TableKind = TableKind::TypeRef;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            resolution_scope: reader.coded(CodedIndex::ResolutionScope)?,
+            name: reader.string()?,
+            namespace: reader.string()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct TypeDefRow {
+    #[allow(dead_code)]
+    pub(crate) flags: u32,
+    pub(crate) name: StringIndex,
+    pub(crate) namespace: StringIndex,
+    pub(crate) extends: Token,
+    pub(crate) field_list: RowIndex,
+    pub(crate) method_list: RowIndex,
+}
+impl Row for TypeDefRow {
+    const TABLE: TableKind = TableKind::TypeDef;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            flags: reader.u32()?,
+            name: reader.string()?,
+            namespace: reader.string()?,
+            extends: reader.coded(CodedIndex::TypeDefOrRef)?,
+            field_list: reader.index(TableKind::Field)?,
+            method_list: reader.index(TableKind::MethodDef)?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct FieldRow {
+    pub(crate) flags: u16,
+    pub(crate) name: StringIndex,
+    pub(crate) signature: BlobIndex,
+}
+impl FieldRow {
+    pub(crate) fn is_static(&self) -> bool {
+        self.flags & 0x0010 != 0
+    }
+    /// Literal fields are compile-time constants, which have no storage.
+    pub(crate) fn is_literal(&self) -> bool {
+        self.flags & 0x0040 != 0
+    }
+}
+impl Row for FieldRow {
+    const TABLE: TableKind = TableKind::Field;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            flags: reader.u16()?,
+            name: reader.string()?,
+            signature: reader.blob()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct MethodDefRow {
+    pub(crate) rva: u32,
+    pub(crate) impl_flags: u16,
+    pub(crate) flags: u16,
+    pub(crate) name: StringIndex,
+    pub(crate) signature: BlobIndex,
+    #[allow(dead_code)]
+    pub(crate) param_list: RowIndex,
+}
+impl MethodDefRow {
+    pub(crate) fn is_static(&self) -> bool {
+        self.flags & 0x0010 != 0
+    }
+    /// Implementation flags set by `[MethodImpl]`, such as `AggressiveInlining`, without the code type.
+    pub(crate) fn impl_options(&self) -> u16 {
+        self.impl_flags & !0x0003
+    }
+}
+impl Row for MethodDefRow {
+    const TABLE: TableKind = TableKind::MethodDef;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            rva: reader.u32()?,
+            impl_flags: reader.u16()?,
+            flags: reader.u16()?,
+            name: reader.string()?,
+            signature: reader.blob()?,
+            param_list: reader.index(TableKind::Param)?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct ParamRow {
+    #[allow(dead_code)]
+    pub(crate) flags: u16,
+    #[allow(dead_code)]
+    pub(crate) sequence: u16,
+    #[allow(dead_code)]
+    pub(crate) name: StringIndex,
+}
+impl Row for ParamRow {
+    const TABLE: TableKind = TableKind::Param;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            flags: reader.u16()?,
+            sequence: reader.u16()?,
+            name: reader.string()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct MemberRefRow {
+    pub(crate) class: Token,
+    pub(crate) name: StringIndex,
+    pub(crate) signature: BlobIndex,
+}
+impl Row for MemberRefRow {
+    const TABLE: TableKind = TableKind::MemberRef;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            class: reader.coded(CodedIndex::MemberRefParent)?,
+            name: reader.string()?,
+            signature: reader.blob()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct StandAloneSigRow {
+    pub(crate) signature: BlobIndex,
+}
+impl Row for StandAloneSigRow {
+    const TABLE: TableKind = TableKind::StandAloneSig;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            signature: reader.blob()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct ModuleRefRow {
+    #[allow(dead_code)]
+    pub(crate) name: StringIndex,
+}
+impl Row for ModuleRefRow {
+    const TABLE: TableKind = TableKind::ModuleRef;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            name: reader.string()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct TypeSpecRow {
+    #[allow(dead_code)]
+    pub(crate) signature: BlobIndex,
+}
+impl Row for TypeSpecRow {
+    const TABLE: TableKind = TableKind::TypeSpec;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            signature: reader.blob()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct AssemblyRow {
+    #[allow(dead_code)]
+    pub(crate) version: [u16; 4],
+    #[allow(dead_code)]
+    pub(crate) flags: u32,
+    #[allow(dead_code)]
+    pub(crate) public_key: BlobIndex,
+    pub(crate) name: StringIndex,
+    #[allow(dead_code)]
+    pub(crate) culture: StringIndex,
+}
+impl Row for AssemblyRow {
+    const TABLE: TableKind = TableKind::Assembly;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        let _hash_alg_id = reader.u32()?;
+        Ok(Self {
+            version: [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?],
+            flags: reader.u32()?,
+            public_key: reader.blob()?,
+            name: reader.string()?,
+            culture: reader.string()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct AssemblyRefRow {
+    #[allow(dead_code)]
+    pub(crate) version: [u16; 4],
+    #[allow(dead_code)]
+    pub(crate) flags: u32,
+    #[allow(dead_code)]
+    pub(crate) public_key_or_token: BlobIndex,
+    pub(crate) name: StringIndex,
+    #[allow(dead_code)]
+    pub(crate) culture: StringIndex,
+    #[allow(dead_code)]
+    pub(crate) hash_value: BlobIndex,
+}
+impl Row for AssemblyRefRow {
+    const TABLE: TableKind = TableKind::AssemblyRef;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            version: [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?],
+            flags: reader.u32()?,
+            public_key_or_token: reader.blob()?,
+            name: reader.string()?,
+            culture: reader.string()?,
+            hash_value: reader.blob()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct NestedClassRow {
+    #[allow(dead_code)]
+    pub(crate) nested_class: RowIndex,
+    #[allow(dead_code)]
+    pub(crate) enclosing_class: RowIndex,
+}
+impl Row for NestedClassRow {
+    const TABLE: TableKind = TableKind::NestedClass;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            nested_class: reader.index(TableKind::TypeDef)?,
+            enclosing_class: reader.index(TableKind::TypeDef)?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct CustomAttributeRow {
+    pub(crate) parent: Token,
+    /// Constructor of the attribute.
+    pub(crate) kind: Token,
+    pub(crate) value: BlobIndex,
+}
+impl Row for CustomAttributeRow {
+    const TABLE: TableKind = TableKind::CustomAttribute;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            parent: reader.coded(CodedIndex::HasCustomAttribute)?,
+            kind: reader.coded(CodedIndex::CustomAttributeType)?,
+            value: reader.blob()?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct ClassLayoutRow {
+    #[allow(dead_code)]
+    pub(crate) packing_size: u16,
+    pub(crate) class_size: u32,
+    pub(crate) parent: RowIndex,
+}
+impl Row for ClassLayoutRow {
+    const TABLE: TableKind = TableKind::ClassLayout;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            packing_size: reader.u16()?,
+            class_size: reader.u32()?,
+            parent: reader.index(TableKind::TypeDef)?,
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub(crate) struct FieldRvaRow {
+    pub(crate) rva: u32,
+    pub(crate) field: RowIndex,
+}
+impl Row for FieldRvaRow {
+    const TABLE: TableKind = TableKind::FieldRVA;
+    fn read(reader: &mut RowReader) -> Result<Self, ImportError> {
+        Ok(Self {
+            rva: reader.u32()?,
+            field: reader.index(TableKind::Field)?,
+        })
+    }
+}
+/// Decoded contents of the `#~` stream(ECMA-335 II.24.2.6).
+#[derive(Debug)]
+pub(crate) struct Tables {
+    #[allow(dead_code)]
+    pub(crate) modules: Vec<ModuleRow>,
+    pub(crate) type_refs: Vec<TypeRefRow>,
+    pub(crate) type_defs: Vec<TypeDefRow>,
+    pub(crate) fields: Vec<FieldRow>,
+    pub(crate) method_defs: Vec<MethodDefRow>,
+    #[allow(dead_code)]
+    pub(crate) params: Vec<ParamRow>,
+    pub(crate) member_refs: Vec<MemberRefRow>,
+    pub(crate) custom_attributes: Vec<CustomAttributeRow>,
+    pub(crate) class_layouts: Vec<ClassLayoutRow>,
+    pub(crate) stand_alone_sigs: Vec<StandAloneSigRow>,
+    #[allow(dead_code)]
+    pub(crate) module_refs: Vec<ModuleRefRow>,
+    #[allow(dead_code)]
+    pub(crate) type_specs: Vec<TypeSpecRow>,
+    pub(crate) field_rvas: Vec<FieldRvaRow>,
+    pub(crate) assemblies: Vec<AssemblyRow>,
+    pub(crate) assembly_refs: Vec<AssemblyRefRow>,
+    #[allow(dead_code)]
+    pub(crate) nested_classes: Vec<NestedClassRow>,
+}
+/// Gets the row with 1-based `index` from `table`.
+pub(crate) fn row<T>(table: &[T], index: RowIndex) -> Option<&T> {
+    table.get((index as usize).checked_sub(1)?)
+}
+/// Like [`row`], but reports a missing row as an error.
+pub(crate) fn get_row<T: Row>(table: &[T], index: RowIndex) -> Result<&T, ImportError> {
+    row(table, index).ok_or(ImportError::InvalidRowIndex {
+        table: T::TABLE as u8,
+        row: index,
+    })
+}
+/// Lists are encoded as the index of the first element, and run until the next owner's list begins.
+fn list_range(start: RowIndex, next_start: Option<RowIndex>, len: usize) -> Range<RowIndex> {
+    let end = next_start.unwrap_or(len as RowIndex + 1);
+    start..end.max(start)
+}
+impl Tables {
+    /// Parses the `#~` stream in `data`, which begins at file offset `base`.
+    pub(crate) fn parse(data: &[u8], base: usize) -> Result<Self, ImportError> {
+        let mut reader = SliceReader::at(data, base);
+        //Reserved, major version, minor version
+        reader.skip(6)?;
+        let heap_sizes = reader.read_u8()?;
+        //Reserved
+        reader.skip(1)?;
+        let valid = reader.read_u64()?;
+        let _sorted = reader.read_u64()?;
+        let mut row_counts = [0; TABLE_COUNT];
+        for id in 0..64 {
+            if valid & (1 << id) == 0 {
+                continue;
+            }
+            let Some(table) = TableKind::from_id(id) else {
+                return Err(ImportError::UnknownTable {
+                    offset: base + 8,
+                    table: id,
+                });
+            };
+            row_counts[table as usize] = reader.read_u32()?;
+        }
+        let sizes = IndexSizes {
+            wide_strings: heap_sizes & 0x01 != 0,
+            wide_guids: heap_sizes & 0x02 != 0,
+            wide_blobs: heap_sizes & 0x04 != 0,
+            row_counts,
+        };
+        //Tables are stored one after another, in the order of their ids.
+        let mut table_data: [(&[u8], usize); TABLE_COUNT] = [(&[], 0); TABLE_COUNT];
+        for table in TableKind::ALL {
+            let size = sizes.row_size(table) * row_counts[table as usize] as usize;
+            let offset = reader.file_offset();
+            table_data[table as usize] = (reader.read_bytes(size)?, offset);
+        }
+        fn read_table<T: Row>(
+            data: &[(&[u8], usize); TABLE_COUNT],
+            sizes: &IndexSizes,
+        ) -> Result<Vec<T>, ImportError> {
+            let count = sizes.row_counts[T::TABLE as usize] as usize;
+            let (data, offset) = data[T::TABLE as usize];
+            let mut reader = RowReader {
+                reader: SliceReader::at(data, offset),
+                sizes,
+            };
+            let mut rows = Vec::with_capacity(count);
+            for _ in 0..count {
+                rows.push(T::read(&mut reader)?);
+            }
+            Ok(rows)
+        }
+        Ok(Self {
+            modules: read_table(&table_data, &sizes)?,
+            type_refs: read_table(&table_data, &sizes)?,
+            type_defs: read_table(&table_data, &sizes)?,
+            fields: read_table(&table_data, &sizes)?,
+            method_defs: read_table(&table_data, &sizes)?,
+            params: read_table(&table_data, &sizes)?,
+            member_refs: read_table(&table_data, &sizes)?,
+            custom_attributes: read_table(&table_data, &sizes)?,
+            class_layouts: read_table(&table_data, &sizes)?,
+            stand_alone_sigs: read_table(&table_data, &sizes)?,
+            module_refs: read_table(&table_data, &sizes)?,
+            type_specs: read_table(&table_data, &sizes)?,
+            field_rvas: read_table(&table_data, &sizes)?,
+            assemblies: read_table(&table_data, &sizes)?,
+            assembly_refs: read_table(&table_data, &sizes)?,
+            nested_classes: read_table(&table_data, &sizes)?,
+        })
+    }
+    /// Rows of the MethodDef table owned by TypeDef `type_def`.
+    pub(crate) fn type_methods(&self, type_def: RowIndex) -> Range<RowIndex> {
+        let Some(owner) = row(&self.type_defs, type_def) else {
+            return 0..0;
+        };
+        let next = row(&self.type_defs, type_def + 1).map(|next| next.method_list);
+        list_range(owner.method_list, next, self.method_defs.len())
+    }
+    /// TypeDef owning MethodDef `method_def`.
+    pub(crate) fn method_owner(&self, method_def: RowIndex) -> Option<RowIndex> {
+        //Method lists are sorted, so the owner is the last TypeDef whose list starts at or before `method_def`.
+        let owner = self
+            .type_defs
+            .partition_point(|type_def| type_def.method_list <= method_def)
+            as RowIndex;
+        self.type_methods(owner)
+            .contains(&method_def)
+            .then_some(owner)
+    }
+    /// Rows of the Field table owned by TypeDef `type_def`.
+    pub(crate) fn type_fields(&self, type_def: RowIndex) -> Range<RowIndex> {
+        let Some(owner) = row(&self.type_defs, type_def) else {
+            return 0..0;
+        };
+        let next = row(&self.type_defs, type_def + 1).map(|next| next.field_list);
+        list_range(owner.field_list, next, self.fields.len())
+    }
+    /// Explicit layout of TypeDef `type_def`, if it has one.
+    pub(crate) fn class_layout(&self, type_def: RowIndex) -> Option<&ClassLayoutRow> {
+        self.class_layouts
+            .iter()
+            .find(|layout| layout.parent == type_def)
+    }
+    /// RVA of the initial data of Field `field`, if it is mapped onto the image.
+    pub(crate) fn field_rva(&self, field: RowIndex) -> Option<u32> {
+        self.field_rvas
+            .iter()
+            .find(|field_rva| field_rva.field == field)
+            .map(|field_rva| field_rva.rva)
+    }
+    /// Rows of the Param table owned by MethodDef `method_def`.
+    #[allow(dead_code)]
+    pub(crate) fn method_params(&self, method_def: RowIndex) -> Range<RowIndex> {
+        let Some(owner) = row(&self.method_defs, method_def) else {
+            return 0..0;
+        };
+        let next = row(&self.method_defs, method_def + 1).map(|next| next.param_list);
+        list_range(owner.param_list, next, self.params.len())
+    }
+}
+#[cfg(test)]
+fn build_table_stream(heap_sizes: u8, tables: &[(TableKind, u32, &[u8])]) -> Vec<u8> {
+    let mut res = vec![0, 0, 0, 0, 2, 0, heap_sizes, 1];
+    let valid = tables
+        .iter()
+        .fold(0_u64, |valid, (table, _, _)| valid | (1 << *table as u64));
+    res.extend(valid.to_le_bytes());
+    res.extend(0_u64.to_le_bytes());
+    for (_, count, _) in tables {
+        res.extend(count.to_le_bytes());
+    }
+    for (_, _, data) in tables {
+        res.extend(*data);
+    }
+    res
+}
+#[test]
+fn coded_index_sizes() {
+    let mut sizes = IndexSizes {
+        wide_strings: false,
+        wide_guids: false,
+        wide_blobs: true,
+        row_counts: [0; TABLE_COUNT],
+    };
+    assert_eq!(sizes.column_size(Column::Blob), 4);
+    assert_eq!(sizes.column_size(Column::String), 2);
+    assert_eq!(CodedIndex::HasCustomAttribute.tag_bits(), 5);
+    assert_eq!(CodedIndex::CustomAttributeType.tag_bits(), 3);
+    assert_eq!(CodedIndex::MethodDefOrRef.tag_bits(), 1);
+    //TypeDefOrRef has 2 tag bits, so it fits in 2 bytes as long as all tables have less than 2^14 rows.
+    sizes.row_counts[TableKind::TypeRef as usize] = (1 << 14) - 1;
+    assert_eq!(sizes.coded_index_size(CodedIndex::TypeDefOrRef), 2);
+    sizes.row_counts[TableKind::TypeRef as usize] = 1 << 14;
+    assert_eq!(sizes.coded_index_size(CodedIndex::TypeDefOrRef), 4);
+    assert_eq!(sizes.coded_index_size(CodedIndex::MethodDefOrRef), 2);
+    assert_eq!(sizes.table_index_size(TableKind::TypeRef), 2);
+    sizes.row_counts[TableKind::TypeRef as usize] = 1 << 16;
+    assert_eq!(sizes.table_index_size(TableKind::TypeRef), 4);
+    assert_eq!(sizes.row_size(TableKind::TypeDef), 4 + 2 + 2 + 4 + 2 + 2);
+}
+#[test]
+fn decode_tables() {
+    #[rustfmt::skip]
+    let type_defs = [
+        // <Module>: flags, name, namespace, extends, field list, method list
+        0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 1, 0,
+        // Class1 extends TypeRef 1
+        0x81, 0x01, 0x10, 0, 10, 0, 17, 0, 0x05, 0, 1, 0, 1, 0,
+        // Class2 extends TypeRef 1
+        0x81, 0x01, 0x10, 0, 33, 0, 17, 0, 0x05, 0, 1, 0, 3, 0,
+    ];
+    #[rustfmt::skip]
+    let type_refs = [
+        // ResolutionScope: AssemblyRef 1, name, namespace
+        0x06, 0, 40, 0, 47, 0,
+    ];
+    #[rustfmt::skip]
+    let method_defs = [
+        // rva, impl flags, flags, name, signature, param list
+        0x50, 0x20, 0, 0, 0, 0, 0x96, 0, 54, 0, 1, 0, 1, 0,
+        0x54, 0x20, 0, 0, 0, 0, 0x96, 0, 58, 0, 1, 0, 3, 0,
+        0x58, 0x20, 0, 0, 0, 0, 0x86, 0, 62, 0, 7, 0, 3, 0,
+    ];
+    #[rustfmt::skip]
+    let params = [
+        0, 0, 1, 0, 66, 0,
+        0, 0, 2, 0, 68, 0,
+    ];
+    let data = build_table_stream(
+        0,
+        &[
+            (TableKind::TypeRef, 1, &type_refs),
+            (TableKind::TypeDef, 3, &type_defs),
+            (TableKind::MethodDef, 3, &method_defs),
+            (TableKind::Param, 2, &params),
+        ],
+    );
+    let tables = Tables::parse(&data, 0).expect("Could not decode tables!");
+    assert_eq!(tables.type_defs.len(), 3);
+    assert_eq!(tables.method_defs.len(), 3);
+    let class1 = row(&tables.type_defs, 2).unwrap();
+    assert_eq!(class1.flags, 0x0010_0181);
+    assert_eq!(class1.name, 10);
+    assert_eq!(class1.extends, Token::new(TableKind::TypeRef, 1));
+    assert_eq!(
+        tables.type_refs[0].resolution_scope,
+        Token::new(TableKind::AssemblyRef, 1)
+    );
+    assert_eq!(tables.type_methods(1), 1..1);
+    assert_eq!(tables.type_methods(2), 1..3);
+    assert_eq!(tables.type_methods(3), 3..4);
+    assert_eq!(tables.method_owner(1), Some(2));
+    assert_eq!(tables.method_owner(2), Some(2));
+    assert_eq!(tables.method_owner(3), Some(3));
+    assert_eq!(tables.method_owner(4), None);
+    assert_eq!(tables.method_params(1), 1..3);
+    assert_eq!(tables.method_params(2), 3..3);
+    assert_eq!(tables.method_params(3), 3..3);
+    let add = row(&tables.method_defs, 1).unwrap();
+    assert_eq!(add.rva, 0x2050);
+    assert!(add.is_static());
+    assert!(!row(&tables.method_defs, 3).unwrap().is_static());
+    assert_eq!(row(&tables.params, 2).unwrap().sequence, 2);
+    assert!(matches!(
+        get_row(&tables.params, 3),
+        Err(ImportError::InvalidRowIndex {
+            table: 0x08,
+            row: 3
+        })
+    ));
+    let mut data = data;
+    //Mark table 0x2D, which does not exist, as present.
+    data[8 + 5] |= 1 << 5;
+    assert!(matches!(
+        Tables::parse(&data, 0x100),
+        Err(ImportError::UnknownTable {
+            offset: 0x108,
+            table: 0x2D
+        })
+    ));
+}
+#[test]
+fn static_data_tables() {
+    #[rustfmt::skip]
+    let type_defs = [
+        // <Module>
+        0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 1, 0,
+        // Tables, owning fields 1 and 2
+        0x81, 0x01, 0x10, 0, 10, 0, 17, 0, 0, 0, 1, 0, 1, 0,
+        // Block16, a nested valuetype
+        0x03, 0x01, 0, 0, 24, 0, 0, 0, 0, 0, 3, 0, 1, 0,
+    ];
+    #[rustfmt::skip]
+    let fields = [
+        // flags, name, signature
+        0x36, 0x01, 32, 0, 1, 0,
+        0x16, 0, 39, 0, 4, 0,
+    ];
+    #[rustfmt::skip]
+    let class_layouts = [
+        // packing size, class size, parent
+        1, 0, 16, 0, 0, 0, 3, 0,
+    ];
+    #[rustfmt::skip]
+    let field_rvas = [
+        // rva, field
+        0x00, 0x40, 0, 0, 1, 0,
+    ];
+    let data = build_table_stream(
+        0,
+        &[
+            (TableKind::TypeDef, 3, &type_defs),
+            (TableKind::Field, 2, &fields),
+            (TableKind::ClassLayout, 1, &class_layouts),
+            (TableKind::FieldRVA, 1, &field_rvas),
+        ],
+    );
+    let tables = Tables::parse(&data, 0).expect("Could not decode tables!");
+    assert_eq!(tables.type_fields(2), 1..3);
+    assert_eq!(tables.type_fields(3), 3..3);
+    let primes = row(&tables.fields, 1).unwrap();
+    assert!(primes.is_static());
+    assert!(!primes.is_literal());
+    assert_eq!(tables.field_rva(1), Some(0x4000));
+    assert_eq!(tables.field_rva(2), None);
+    let layout = tables
+        .class_layout(3)
+        .expect("Block16 has an explicit layout");
+    assert_eq!(layout.packing_size, 1);
+    assert_eq!(layout.class_size, 16);
+    assert!(tables.class_layout(2).is_none());
+}
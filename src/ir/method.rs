@@ -4,24 +4,87 @@ use super::{
     BlockLink, InstructionIndex, MethodIRError, OpBlock, Signature, StackState, VBlocks, VOp,
 };
 
+use crate::type_system::paths::ClassPath;
 use inkwell::context::Context;
 use inkwell::types::FunctionType;
-
+use std::ops::Range;
+/// What runs when an exception leaves the `try` block of a [`ProtectedRegion`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handler {
+    /// Catches exceptions of the given class.
+    Catch(ClassPath),
+    /// The filter block beginning at this instruction decides if the handler runs.
+    Filter(InstructionIndex),
+    Finally,
+    Fault,
+}
+/// A `try` block together with its handler. Ranges are half-open ranges of instruction indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtectedRegion {
+    try_range: Range<InstructionIndex>,
+    handler_range: Range<InstructionIndex>,
+    handler: Handler,
+}
+impl ProtectedRegion {
+    pub fn new(
+        try_range: Range<InstructionIndex>,
+        handler_range: Range<InstructionIndex>,
+        handler: Handler,
+    ) -> Self {
+        Self {
+            try_range,
+            handler_range,
+            handler,
+        }
+    }
+    pub fn try_range(&self) -> Range<InstructionIndex> {
+        self.try_range.clone()
+    }
+    pub fn handler_range(&self) -> Range<InstructionIndex> {
+        self.handler_range.clone()
+    }
+    pub fn handler(&self) -> &Handler {
+        &self.handler
+    }
+    /// Instructions at which a block must begin, since control can enter or leave the region there.
+    fn boundaries(&self) -> impl Iterator<Item = InstructionIndex> + '_ {
+        let filter = match self.handler {
+            Handler::Filter(filter) => Some(filter),
+            _ => None,
+        };
+        [
+            self.try_range.start,
+            self.try_range.end,
+            self.handler_range.start,
+            self.handler_range.end,
+        ]
+        .into_iter()
+        .chain(filter)
+    }
+}
 #[derive(Debug)]
 pub struct Method {
     signature: Signature,
     pub(crate) blocks: VBlocks,
     pub(crate) locals: Vec<Type>,
+    protected_regions: Vec<ProtectedRegion>,
 }
-fn spilt_into_blocks(ops: &[OpKind]) -> VBlocks {
+fn spilt_into_blocks(ops: &[OpKind], regions: &[ProtectedRegion]) -> VBlocks {
     //nothing to do for now!
     let mut targets: Vec<InstructionIndex> = Vec::new();
     for (index, op) in ops.iter().enumerate() {
         if let Some(target) = op.branch_target() {
-            targets.push(target - 1);
+            if target > 0 {
+                targets.push(target - 1);
+            }
             targets.push(index);
         }
     }
+    for boundary in regions.iter().flat_map(ProtectedRegion::boundaries) {
+        if boundary > 0 {
+            targets.push(boundary - 1);
+        }
+    }
     let mut block = VOp::new();
     let mut blocks = VBlocks::new();
     let mut index: InstructionIndex = 0;
@@ -50,57 +113,162 @@ impl Method {
         }
         panic!("No block begins at instruction with index {index}!");
     }
+    /// Indices of blocks control can pass to from block `index`, one per outgoing edge.
+    pub(crate) fn successors(&self, index: usize) -> Vec<usize> {
+        match self.blocks[index].link_out() {
+            BlockLink::Return => Vec::new(),
+            BlockLink::Pass => vec![index + 1],
+            BlockLink::Jump(target) => vec![self.get_index_of_block_beginig_at(target)],
+            BlockLink::Branch(_, target) => {
+                vec![index + 1, self.get_index_of_block_beginig_at(target)]
+            }
+        }
+    }
     pub(crate) fn get_local_type(&self, index: usize) -> &Type {
         &self.locals[index]
     }
+    /// Index of the block control passes to from block `index` when it does not branch.
+    fn get_next_block(&self, index: usize) -> Result<usize, MethodIRError> {
+        match self.blocks.get(index + 1) {
+            Some(_) => Ok(index + 1),
+            None => Err(MethodIRError::FallsOffEnd(
+                self.blocks[index].block_end() - 1,
+            )),
+        }
+    }
     fn resolve_node(
         &mut self,
         index: usize,
-        parrent_state: StackState,
+        mut parrent_state: StackState,
     ) -> Result<(), MethodIRError> {
-        if self.blocks[index].is_resolved() {
-            return Ok(());
+        //Every path into a block has to agree on the stack it begins with.
+        if let Some(state_in) = self.blocks[index].state_in() {
+            let Some(merged) = state_in.merge(&parrent_state) else {
+                return Err(MethodIRError::StackMismatch {
+                    block: index,
+                    first: state_in.types().into(),
+                    second: parrent_state.types().into(),
+                });
+            };
+            if merged.types() == state_in.types() {
+                return Ok(());
+            }
+            //The merged stack is wider, so this block and the ones after it are resolved again. Types only widen once, so this ends.
+            parrent_state = merged;
         }
         self.blocks[index].resolve(parrent_state, &self.signature, &self.locals)?;
         let link = self.blocks[index].link_out();
         match link {
             BlockLink::Return => Ok(()),
             BlockLink::Pass => {
-                let child_beg_index = self.blocks[index].block_end();
-                let child_index = self.get_index_of_block_beginig_at(child_beg_index);
+                let child_index = self.get_next_block(index)?;
                 let Some(state) = self.blocks[index].state() else {
-                   return Err(MethodIRError::StateUnresolvedNoError);
+                    return Err(MethodIRError::StateUnresolvedNoError);
                 };
                 self.resolve_node(child_index, state)
             }
+            BlockLink::Jump(target) => {
+                let target_index = self.get_index_of_block_beginig_at(target);
+                let Some(state) = self.blocks[index].state() else {
+                    return Err(MethodIRError::StateUnresolvedNoError);
+                };
+                if target < self.blocks[index].block_end() && !state.types().is_empty() {
+                    return Err(MethodIRError::BackEdgeWithStack {
+                        block: target_index,
+                        state: state.types().into(),
+                    });
+                }
+                self.resolve_node(target_index, state)
+            }
             BlockLink::Branch(default, target) => {
-                let def_index = self.get_index_of_block_beginig_at(default);
+                let def_index = self.get_next_block(index)?;
                 let Some(state) = self.blocks[index].state() else {
-                   return Err(MethodIRError::StateUnresolvedNoError);
+                    return Err(MethodIRError::StateUnresolvedNoError);
                 };
                 self.resolve_node(def_index, state.clone())?;
                 let target_index = self.get_index_of_block_beginig_at(target);
+                if target < default && !state.types().is_empty() {
+                    return Err(MethodIRError::BackEdgeWithStack {
+                        block: target_index,
+                        state: state.types().into(),
+                    });
+                }
                 self.resolve_node(target_index, state)
             } // _ => todo!("Resolving block links of type {link:?} is not supported"),
         }
     }
     fn resolve(&mut self) -> Result<(), MethodIRError> {
-        self.resolve_node(0, StackState::default())
+        self.resolve_node(0, StackState::default())?;
+        //Handlers are never branched to, so they have to be resolved separately. They begin with the exception on the stack, if they can access it.
+        for region in self.protected_regions.clone() {
+            let mut state = StackState::default();
+            match region.handler() {
+                Handler::Catch(_) => state.push(Type::ObjRef),
+                Handler::Filter(filter) => {
+                    state.push(Type::ObjRef);
+                    let filter_index = self.get_index_of_block_beginig_at(*filter);
+                    self.resolve_node(filter_index, state.clone())?;
+                }
+                Handler::Finally | Handler::Fault => (),
+            }
+            let handler_index = self.get_index_of_block_beginig_at(region.handler_range.start);
+            self.resolve_node(handler_index, state)?;
+        }
+        Ok(())
     }
     pub fn from_ops(
         sig: Signature,
         ops: &[OpKind],
         locals: &[Type],
     ) -> Result<Self, MethodIRError> {
-        let blocks: VBlocks = spilt_into_blocks(ops);
+        Self::from_ops_with_regions(sig, ops, locals, Vec::new())
+    }
+    /// Like [`Method::from_ops`], but with exception handling regions. Regions must be ordered innermost first.
+    pub fn from_ops_with_regions(
+        sig: Signature,
+        ops: &[OpKind],
+        locals: &[Type],
+        protected_regions: Vec<ProtectedRegion>,
+    ) -> Result<Self, MethodIRError> {
+        for (index, region) in protected_regions.iter().enumerate() {
+            let in_bounds =
+                |range: &Range<InstructionIndex>| range.start < range.end && range.end <= ops.len();
+            let filter_in_bounds = match region.handler {
+                Handler::Filter(filter) => filter < region.handler_range.start,
+                _ => true,
+            };
+            if !in_bounds(&region.try_range)
+                || !in_bounds(&region.handler_range)
+                || !filter_in_bounds
+            {
+                return Err(MethodIRError::InvalidProtectedRegion(index));
+            }
+        }
+        if ops.is_empty() {
+            return Err(MethodIRError::FallsOffEnd(0));
+        }
+        for (index, op) in ops.iter().enumerate() {
+            match op.branch_target() {
+                Some(target) if target >= ops.len() => {
+                    return Err(MethodIRError::InvalidBranchTarget { index, target })
+                }
+                _ => (),
+            }
+        }
+        let blocks: VBlocks = spilt_into_blocks(ops, &protected_regions);
         let mut res = Self {
             blocks,
             signature: sig,
             locals: locals.into(),
+            protected_regions,
         };
         res.resolve()?;
         Ok(res)
     }
+    /// Exception handling regions, innermost first.
+    pub fn protected_regions(&self) -> &[ProtectedRegion] {
+        &self.protected_regions
+    }
     pub(crate) fn as_fn_type<'a>(&self, ctx: &'a Context) -> FunctionType<'a> {
         self.signature.as_fn_type(ctx)
     }
@@ -108,3 +276,262 @@ impl Method {
         &self.signature
     }
 }
+#[test]
+fn protected_regions() {
+    let args = [Type::I32, Type::I32];
+    let sig = Signature::new(&(&args[..], Type::I32));
+    let ops = [
+        //try
+        OpKind::LDArg(0),
+        OpKind::LDArg(1),
+        OpKind::Div,
+        OpKind::Ret,
+        //catch, with the exception on the stack
+        OpKind::Pop,
+        OpKind::LDCI32(0),
+        OpKind::Ret,
+    ];
+    let catch = Handler::Catch(ClassPath::new(
+        "System.Runtime",
+        "System",
+        "DivideByZeroException",
+    ));
+    let region = ProtectedRegion::new(0..4, 4..7, catch);
+    let method = Method::from_ops_with_regions(sig.clone(), &ops, &[], vec![region.clone()])
+        .expect("Could not create method with a protected region!");
+    assert_eq!(method.protected_regions(), &[region]);
+    let handler = method.get_index_of_block_beginig_at(4);
+    assert!(method.blocks[handler].is_resolved());
+    for region in [
+        ProtectedRegion::new(0..8, 4..7, Handler::Fault),
+        ProtectedRegion::new(0..4, 4..4, Handler::Fault),
+        ProtectedRegion::new(0..3, 4..7, Handler::Filter(5)),
+    ] {
+        assert!(matches!(
+            Method::from_ops_with_regions(sig.clone(), &ops, &[], vec![region]),
+            Err(MethodIRError::InvalidProtectedRegion(0))
+        ));
+    }
+}
+#[test]
+fn stack_merge() {
+    use super::text::parse_method;
+    let resolve = |src| {
+        let (sig, ops, locals) = parse_method(src).expect("Could not parse method!");
+        Method::from_ops(sig, &ops, &locals)
+    };
+    //Both paths push one i32 before joining.
+    resolve(
+        ".sig i32 (i32)
+                 ldarg 0
+                 ldc.i32 0
+                 bge positive
+                 ldc.i32 -1
+                 br done
+         positive: ldc.i32 1
+         done:   ret",
+    )
+    .expect("Could not resolve method with matching stacks!");
+    let error = resolve(
+        ".sig void (i32)
+                 ldarg 0
+                 ldc.i32 0
+                 bge positive
+                 ldc.i32 -1
+                 conv.i64
+                 br done
+         positive: ldc.i32 1
+         done:   pop
+                 ret",
+    );
+    assert!(
+        matches!(
+            &error,
+            Err(MethodIRError::StackMismatch { block: 3, first, second })
+                if first == &[Type::I64] && second == &[Type::I32]
+        ),
+        "{error:?}"
+    );
+    let error = resolve(
+        ".sig void ()
+                 ldc.i32 1
+         loop:   ldc.i32 2
+                 br loop",
+    );
+    assert!(
+        matches!(
+            &error,
+            Err(MethodIRError::BackEdgeWithStack { block: 1, state }) if state == &[Type::I32, Type::I32]
+        ),
+        "{error:?}"
+    );
+}
+#[test]
+fn verification_errors() {
+    use super::text::parse_method;
+    let resolve = |src| {
+        let (sig, ops, locals) = parse_method(src).expect("Could not parse method!");
+        Method::from_ops(sig, &ops, &locals)
+    };
+    let error = resolve(".sig i32 (i32)\n ldarg 0\n add\n ret");
+    assert!(
+        matches!(error, Err(MethodIRError::StackUnderflow(1))),
+        "{error:?}"
+    );
+    let error = resolve(".sig i32 (i32)\n nop\n ldarg 1\n ret");
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::ArgOutOfRange { index: 1, arg: 1 })
+        ),
+        "{error:?}"
+    );
+    let error = resolve(".sig void (i32)\n.locals (i32)\n ldarg 0\n stloc 1\n ret");
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::LocalOutOfRange { index: 1, local: 1 })
+        ),
+        "{error:?}"
+    );
+    let error = resolve(".sig void ()\n ldnull\n call void [Asm]Log::Print(i32)\n ret");
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::CallArgMismatch {
+                index: 1,
+                expected: Type::I32,
+                got: Type::ObjRef
+            })
+        ),
+        "{error:?}"
+    );
+    let error = resolve(".sig void ()\n ldnull\n ldnull\n add\n ret");
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::NotArithmetic {
+                index: 2,
+                ty: Type::ObjRef
+            })
+        ),
+        "{error:?}"
+    );
+    //Bools are int32 on the stack, and references can be compared for equality.
+    resolve(".sig i32 (bool, bool)\n ldarg 0\n ldarg 1\n and\n not\n neg\n ret")
+        .expect("Could not resolve bitwise ops on bools!");
+    resolve(".sig i32 (obj)\n ldarg 0\n ldnull\n bne set\n ldc.i32 0\n ret\n set: ldc.i32 1\n ret")
+        .expect("Could not resolve comparison of references!");
+    //An int32 may be stored into any integer, bool or char of at most 32 bits.
+    resolve(".sig bool (i32)\n.locals (u8, char, i16)\n ldarg 0\n stloc 0\n ldloc 0\n stloc 1\n ldc.i32 -1\n stloc 2\n ldc.i32 1\n ret")
+        .expect("Could not resolve int32 stores into narrow slots!");
+    resolve(".sig void (i32)\n ldarg 0\n call void [Asm]Log::Print(i16, char)\n ret")
+        .expect_err("Expected a stack underflow!");
+    resolve(".sig void (i32)\n ldarg 0\n dup\n call void [Asm]Log::Print(i16, char)\n ret")
+        .expect("Could not resolve int32 arguments to narrow parameters!");
+    let error = resolve(".sig void (i64)\n.locals (i32)\n ldarg 0\n stloc 0\n ret");
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::LocalVarTypeMismatch {
+                index: 1,
+                got: Type::I64,
+                expected: Type::I32,
+                local: 0
+            })
+        ),
+        "{error:?}"
+    );
+    let error = resolve(".sig i64 (i32)\n ldarg 0\n ret");
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::WrongReturnType {
+                index: 1,
+                expected: Type::I64,
+                got: Type::I32
+            })
+        ),
+        "{error:?}"
+    );
+    let error = resolve(".sig i64 (i32, i64)\n ldarg 0\n ldarg 1\n add\n ret");
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::OpOnMismatchedTypes {
+                index: 2,
+                a: Type::I64,
+                b: Type::I32
+            })
+        ),
+        "{error:?}"
+    );
+    for (src, index) in [
+        (".sig void ()\n ldnull\n ldnull\n and\n ret", 2),
+        (".sig void ()\n ldnull\n not\n ret", 1),
+        (".sig void (f64)\n ldarg 0\n dup\n xor\n ret", 2),
+    ] {
+        let error = resolve(src);
+        assert!(
+            matches!(error, Err(MethodIRError::NotInteger { index: i, .. }) if i == index),
+            "{src}: {error:?}"
+        );
+    }
+    for src in [
+        ".sig void ()\n ldnull\n neg\n ret",
+        ".sig i32 ()\n ldnull\n conv.i32\n ret",
+        ".sig f64 ()\n ldstr \"pi\"\n conv.f64\n ret",
+        ".sig f64 ()\n ldnull\n conv.r.un\n ret",
+        ".sig u8 ()\n ldnull\n conv.ovf.u8\n ret",
+        ".sig void ()\n ldnull\n ldnull\n bge end\n end: ret",
+    ] {
+        let error = resolve(src);
+        assert!(
+            matches!(
+                error,
+                Err(MethodIRError::NotArithmetic {
+                    ty: Type::ObjRef,
+                    ..
+                })
+            ),
+            "{src}: {error:?}"
+        );
+    }
+    //Int32 values of any width or signedness merge at join points, in either order.
+    for src in [
+        ".sig i32 (i16, i32)\n ldarg 1\n ldc.i32 0\n bgt pos\n ldc.i32 0\n br done\n pos: ldarg 0\n done: ret",
+        ".sig i32 (i16, i32)\n ldarg 1\n ldc.i32 0\n bgt pos\n ldarg 0\n br done\n pos: ldc.i32 0\n done: ret",
+        ".sig u32 (u32, i32)\n ldarg 1\n ldc.i32 0\n bgt pos\n ldarg 0\n br done\n pos: ldc.i32 0\n done: ret",
+    ] {
+        resolve(src).expect("Could not resolve join of int32 values!");
+    }
+    let error = resolve(
+        ".sig i64 (i64, i32)\n ldarg 1\n ldc.i32 0\n bgt pos\n ldarg 0\n br done\n pos: ldc.i32 0\n done: ret",
+    );
+    assert!(
+        matches!(error, Err(MethodIRError::StackMismatch { .. })),
+        "{error:?}"
+    );
+    let error = resolve(".sig void (i32)\n ldarg 0\n pop");
+    assert!(
+        matches!(error, Err(MethodIRError::FallsOffEnd(1))),
+        "{error:?}"
+    );
+    let sig = Signature::new(&(&[], Type::Void));
+    let error = Method::from_ops(sig.clone(), &[OpKind::Nop, OpKind::BR(7)], &[]);
+    assert!(
+        matches!(
+            error,
+            Err(MethodIRError::InvalidBranchTarget {
+                index: 1,
+                target: 7
+            })
+        ),
+        "{error:?}"
+    );
+    let error = Method::from_ops(sig, &[], &[]);
+    assert!(
+        matches!(error, Err(MethodIRError::FallsOffEnd(0))),
+        "{error:?}"
+    );
+}
@@ -1,6 +1,7 @@
 pub mod method;
 pub mod op;
 pub mod op_block;
+pub mod text;
 pub mod r#type;
 use inkwell::context::Context;
 use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType};
@@ -8,10 +9,72 @@ use op_block::OpBlock;
 use r#type::{AsArgTypeList, GetType, Type};
 #[derive(Debug)]
 pub enum MethodIRError {
-    WrongReturnType { expected: Type, got: Type },
-    OpOnMismatchedTypes(Type, Type),
-    LocalVarTypeMismatch(Type, Type, usize),
+    /// Value returned at `index` can't be stored into the method's return type.
+    WrongReturnType {
+        index: InstructionIndex,
+        expected: Type,
+        got: Type,
+    },
+    /// Binary op at `index` is applied to operands of different types.
+    OpOnMismatchedTypes {
+        index: InstructionIndex,
+        a: Type,
+        b: Type,
+    },
+    /// Value stored at `index` can't be stored into `local`.
+    LocalVarTypeMismatch {
+        index: InstructionIndex,
+        got: Type,
+        expected: Type,
+        local: LocalVarIndex,
+    },
     StateUnresolvedNoError,
+    /// Protected region at this index is empty, out of bounds, or has a filter that does not precede its handler.
+    InvalidProtectedRegion(usize),
+    /// Block at this index is reached with two different stacks.
+    StackMismatch {
+        block: usize,
+        first: VType,
+        second: VType,
+    },
+    /// Instruction at this index pops from an empty stack.
+    StackUnderflow(InstructionIndex),
+    ArgOutOfRange {
+        index: InstructionIndex,
+        arg: ArgIndex,
+    },
+    LocalOutOfRange {
+        index: InstructionIndex,
+        local: LocalVarIndex,
+    },
+    /// Argument of the call at `index` does not match the callee's signature.
+    CallArgMismatch {
+        index: InstructionIndex,
+        expected: Type,
+        got: Type,
+    },
+    /// Arithmetic op at `index` is applied to an operand of a type without arithmetic.
+    NotArithmetic {
+        index: InstructionIndex,
+        ty: Type,
+    },
+    /// Checked integer op at `index` is applied to an operand that is not an integer.
+    NotInteger {
+        index: InstructionIndex,
+        ty: Type,
+    },
+    /// Branch at `index` targets an instruction past the end of the method.
+    InvalidBranchTarget {
+        index: InstructionIndex,
+        target: InstructionIndex,
+    },
+    /// Instruction at this index passes to the next one, but it is the last in the method.
+    FallsOffEnd(InstructionIndex),
+    /// Backward branch to the block at this index leaves values on the stack.
+    BackEdgeWithStack {
+        block: usize,
+        state: VType,
+    },
 }
 pub type VType = Vec<Type>;
 pub type SigType<'a> = (&'a [Type], Type);
@@ -34,6 +97,30 @@ impl StackState {
     pub(crate) fn pop(&mut self) -> Option<Type> {
         self.output.pop()
     }
+    /// Types on the stack, bottom first.
+    pub(crate) fn types(&self) -> &[Type] {
+        &self.output
+    }
+    /// Merges stacks meeting at a join point. Values differing only in width or signedness merge to their verification type.
+    /// `None` if the stacks can't be merged.
+    pub(crate) fn merge(&self, other: &Self) -> Option<Self> {
+        if self.output.len() != other.output.len() {
+            return None;
+        }
+        let output = self
+            .output
+            .iter()
+            .zip(&other.output)
+            .map(
+                |(a, b)| match (a.verification_type(), b.verification_type()) {
+                    _ if a == b => Some(a.clone()),
+                    (a, b) if a == b => Some(a),
+                    _ => None,
+                },
+            )
+            .collect::<Option<_>>()?;
+        Some(Self { output })
+    }
     /*pub(crate) fn is_empty(&mut self) -> bool {
         self.output.is_empty()
     }*/
@@ -98,6 +185,8 @@ impl Signature {
 pub(crate) enum BlockLink {
     Return,
     Branch(InstructionIndex, InstructionIndex),
+    /// Unconditionally branches to the instruction, never passing to the next one.
+    Jump(InstructionIndex),
     Pass, //Passes to the next instruction normaly
 }
 #[cfg(test)]
@@ -23,12 +23,45 @@ pub enum OpKind {
     ConvI32,
     ConvU64,
     ConvI64,
+    ConvF32,
+    ConvF64,
+    /// Converts an integer, interpreted as unsigned, to f64.
+    ConvRUn,
+    /// Checked conversions. If the value does not fit the target type, the overflow handler is called.
+    /// The `Un` variants interpret an integer source as unsigned.
+    ConvOvfU8,
+    ConvOvfI8,
+    ConvOvfU16,
+    ConvOvfI16,
+    ConvOvfU32,
+    ConvOvfI32,
+    ConvOvfU64,
+    ConvOvfI64,
+    ConvOvfU8Un,
+    ConvOvfI8Un,
+    ConvOvfU16Un,
+    ConvOvfI16Un,
+    ConvOvfU32Un,
+    ConvOvfI32Un,
+    ConvOvfU64Un,
+    ConvOvfI64Un,
+    /// Checked integer arithmetic. On overflow, the overflow handler is called.
+    AddOvf,
+    AddOvfUn,
+    SubOvf,
+    SubOvfUn,
+    MulOvf,
+    MulOvfUn,
     Call(MethodPath, Signature),
     Div,
     Dup,
     LDCI32(i32), //Load const i32
+    LDCI64(i64), //Load const i64
+    LDCF32(f32), //Load const f32
+    LDCF64(f64), //Load const f64
     LDArg(ArgIndex),
     LDNull,
+    LDStr(Box<[u16]>), //Load a string literal, stored as UTF-16
     Nop,
     Not,
     Neg,
@@ -68,7 +101,11 @@ impl OpKind {
             | Self::Div
             | Self::Dup
             | Self::LDCI32(_)
+            | Self::LDCI64(_)
+            | Self::LDCF32(_)
+            | Self::LDCF64(_)
             | Self::LDNull
+            | Self::LDStr(_)
             | Self::Neg
             | Self::Not
             | Self::Or
@@ -87,6 +124,31 @@ impl OpKind {
             | Self::ConvI32
             | Self::ConvU64
             | Self::ConvI64
+            | Self::ConvF32
+            | Self::ConvF64
+            | Self::ConvRUn
+            | Self::ConvOvfU8
+            | Self::ConvOvfI8
+            | Self::ConvOvfU16
+            | Self::ConvOvfI16
+            | Self::ConvOvfU32
+            | Self::ConvOvfI32
+            | Self::ConvOvfU64
+            | Self::ConvOvfI64
+            | Self::ConvOvfU8Un
+            | Self::ConvOvfI8Un
+            | Self::ConvOvfU16Un
+            | Self::ConvOvfI16Un
+            | Self::ConvOvfU32Un
+            | Self::ConvOvfI32Un
+            | Self::ConvOvfU64Un
+            | Self::ConvOvfI64Un
+            | Self::AddOvf
+            | Self::AddOvfUn
+            | Self::SubOvf
+            | Self::SubOvfUn
+            | Self::MulOvf
+            | Self::MulOvfUn
             | Self::Call(_, _)
             | Self::XOr => None,
             Self::BGE(target)
@@ -98,15 +160,68 @@ impl OpKind {
             | Self::BR(target) => Some(*target),
         }
     }
+    /// If instruction is a checked conversion, return it's target type and whether the source is read as unsigned.
+    pub(crate) fn checked_conv(&self) -> Option<(Type, bool)> {
+        let conv = match self {
+            Self::ConvOvfU8 => (Type::U8, false),
+            Self::ConvOvfI8 => (Type::I8, false),
+            Self::ConvOvfU16 => (Type::U16, false),
+            Self::ConvOvfI16 => (Type::I16, false),
+            Self::ConvOvfU32 => (Type::U32, false),
+            Self::ConvOvfI32 => (Type::I32, false),
+            Self::ConvOvfU64 => (Type::U64, false),
+            Self::ConvOvfI64 => (Type::I64, false),
+            Self::ConvOvfU8Un => (Type::U8, true),
+            Self::ConvOvfI8Un => (Type::I8, true),
+            Self::ConvOvfU16Un => (Type::U16, true),
+            Self::ConvOvfI16Un => (Type::I16, true),
+            Self::ConvOvfU32Un => (Type::U32, true),
+            Self::ConvOvfI32Un => (Type::I32, true),
+            Self::ConvOvfU64Un => (Type::U64, true),
+            Self::ConvOvfI64Un => (Type::I64, true),
+            _ => return None,
+        };
+        Some(conv)
+    }
 }
-fn get_op_type(a: Type, b: Type) -> Result<Type, MethodIRError> {
-    let a = a.arthm_promote();
-    let b = b.arthm_promote();
+fn get_op_type(index: InstructionIndex, a: Type, b: Type) -> Result<Type, MethodIRError> {
+    let a = promote(index, a)?;
+    let b = promote(index, b)?;
     if a != b {
-        return Err(MethodIRError::OpOnMismatchedTypes(a, b));
+        return Err(MethodIRError::OpOnMismatchedTypes { index, a, b });
     }
     Ok(a)
 }
+fn promote(index: InstructionIndex, ty: Type) -> Result<Type, MethodIRError> {
+    ty.arthm_promote()
+        .ok_or(MethodIRError::NotArithmetic { index, ty })
+}
+/// Checks that the value converted by op at `index` is a number.
+fn check_arthmetic(index: InstructionIndex, ty: Type) -> Result<(), MethodIRError> {
+    if !ty.is_arthmetic() {
+        return Err(MethodIRError::NotArithmetic { index, ty });
+    }
+    Ok(())
+}
+/// Checks that bitwise op at `index` is applied to an integer or a bool.
+fn check_bitwise(index: InstructionIndex, ty: &Type) -> Result<(), MethodIRError> {
+    if ty.is_int() || *ty == Type::Bool {
+        return Ok(());
+    }
+    Err(MethodIRError::NotInteger {
+        index,
+        ty: ty.clone(),
+    })
+}
+fn get_local(
+    locals: &[Type],
+    index: InstructionIndex,
+    local: LocalVarIndex,
+) -> Result<&Type, MethodIRError> {
+    locals
+        .get(local)
+        .ok_or(MethodIRError::LocalOutOfRange { index, local })
+}
 #[derive(Clone, Debug)]
 pub(crate) struct Op {
     kind: OpKind,
@@ -125,8 +240,10 @@ impl Op {
     pub(crate) fn kind(&self) -> &OpKind {
         &self.kind
     }
+    /// Resolves the type of the op at `index`, updating the stack `state`.
     pub(crate) fn resolve(
         &mut self,
+        index: InstructionIndex,
         state: &mut StackState,
         sig: &Signature,
         locals: &[Type],
@@ -143,8 +260,9 @@ impl Op {
                 } else {
                     Type::Void
                 };
-                if ret != sig.ret {
+                if !ret.is_assignable_to(&sig.ret) {
                     return Err(MethodIRError::WrongReturnType {
+                        index,
                         expected: sig.ret.clone(),
                         got: ret.clone(),
                     });
@@ -152,123 +270,229 @@ impl Op {
                 self.resolved_type = Some(ret);
             }
             OpKind::LDArg(arg) => {
-                let t = &sig.args[*arg];
+                let t = sig
+                    .args
+                    .get(*arg)
+                    .ok_or(MethodIRError::ArgOutOfRange { index, arg: *arg })?;
                 self.resolved_type = Some(t.clone());
                 state.push(t.clone());
             }
             // Arthmentic
             OpKind::Mul | OpKind::Add | OpKind::Div | OpKind::Rem | OpKind::Sub => {
-                let a = state.pop().unwrap();
-                let b = state.pop().unwrap();
-                let op_res = get_op_type(a, b)?;
+                let a = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                let b = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                if let Some(ty) = [&a, &b].into_iter().find(|ty| !ty.is_arthmetic()) {
+                    return Err(MethodIRError::NotArithmetic {
+                        index,
+                        ty: ty.clone(),
+                    });
+                }
+                let op_res = get_op_type(index, a, b)?;
+                self.resolved_type = Some(op_res.clone());
+                state.push(op_res);
+            }
+            OpKind::AddOvf
+            | OpKind::AddOvfUn
+            | OpKind::SubOvf
+            | OpKind::SubOvfUn
+            | OpKind::MulOvf
+            | OpKind::MulOvfUn => {
+                let a = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                let b = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                if let Some(ty) = [&a, &b].into_iter().find(|ty| !ty.is_int()) {
+                    return Err(MethodIRError::NotInteger {
+                        index,
+                        ty: ty.clone(),
+                    });
+                }
+                let op_res = get_op_type(index, a, b)?;
                 self.resolved_type = Some(op_res.clone());
-                assert!(op_res.is_arthmetic());
                 state.push(op_res);
             }
             // Bool-aplicable
             OpKind::And | OpKind::Or | OpKind::XOr | OpKind::SHL | OpKind::SHR => {
-                let a = state.pop().unwrap();
-                let b = state.pop().unwrap();
-                let op_res = get_op_type(a, b)?;
+                let a = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                let b = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_bitwise(index, &a)?;
+                check_bitwise(index, &b)?;
+                let op_res = get_op_type(index, a, b)?;
                 self.resolved_type = Some(op_res.clone());
                 state.push(op_res);
             }
-            OpKind::Not | OpKind::Neg => {
-                let a = state.pop().unwrap();
-                let op_res = a.arthm_promote();
+            OpKind::Not => {
+                let a = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_bitwise(index, &a)?;
+                let op_res = promote(index, a)?;
+                self.resolved_type = Some(op_res.clone());
+                state.push(op_res);
+            }
+            OpKind::Neg => {
+                let a = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                let op_res = promote(index, a)?;
                 self.resolved_type = Some(op_res.clone());
                 state.push(op_res);
             }
             OpKind::Dup => {
-                let a = state.pop().unwrap();
+                let a = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
                 state.push(a.clone());
                 state.push(a.clone());
                 self.resolved_type = Some(a);
             }
             OpKind::Pop => {
-                let t = state.pop().unwrap();
+                let t = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
                 self.resolved_type = Some(t);
             }
             OpKind::LDCI32(_) => {
                 self.resolved_type = Some(Type::I32);
                 state.push(Type::I32);
             }
-            OpKind::LDNull => {
+            OpKind::LDCI64(_) => {
+                self.resolved_type = Some(Type::I64);
+                state.push(Type::I64);
+            }
+            OpKind::LDCF32(_) => {
+                self.resolved_type = Some(Type::F32);
+                state.push(Type::F32);
+            }
+            OpKind::LDCF64(_) => {
+                self.resolved_type = Some(Type::F64);
+                state.push(Type::F64);
+            }
+            OpKind::LDNull | OpKind::LDStr(_) => {
                 self.resolved_type = Some(Type::ObjRef);
                 state.push(Type::ObjRef);
             }
+            //Object references can only be compared for equality.
+            OpKind::BEQ(_) | OpKind::BNE(_)
+                if state.types().ends_with(&[Type::ObjRef, Type::ObjRef]) =>
+            {
+                state.pop();
+                state.pop();
+                self.resolved_type = Some(Type::ObjRef);
+            }
             OpKind::BGE(_)
             | OpKind::BLE(_)
             | OpKind::BEQ(_)
             | OpKind::BNE(_)
             | OpKind::BLT(_)
             | OpKind::BGT(_) => {
-                let a = state.pop().unwrap();
-                let b = state.pop().unwrap();
-                let op_res = get_op_type(a, b)?;
+                let a = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                let b = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                let op_res = get_op_type(index, a, b)?;
                 self.resolved_type = Some(op_res);
             }
             OpKind::ConvU8 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::U8);
                 state.push(Type::U8);
             }
             OpKind::ConvI8 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::I8);
                 state.push(Type::I8);
             }
             OpKind::ConvU16 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::U16);
                 state.push(Type::U16);
             }
             OpKind::ConvI16 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::I16);
                 state.push(Type::I16);
             }
             OpKind::ConvU32 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::U32);
                 state.push(Type::U32);
             }
             OpKind::ConvI32 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::I32);
                 state.push(Type::I32);
             }
             OpKind::ConvU64 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::U64);
                 state.push(Type::U64);
             }
             OpKind::ConvI64 => {
-                let _ = state.pop().unwrap();
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
                 self.resolved_type = Some(Type::I64);
                 state.push(Type::I64);
             }
+            OpKind::ConvF32 => {
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
+                self.resolved_type = Some(Type::F32);
+                state.push(Type::F32);
+            }
+            OpKind::ConvF64 | OpKind::ConvRUn => {
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
+                self.resolved_type = Some(Type::F64);
+                state.push(Type::F64);
+            }
+            OpKind::ConvOvfU8
+            | OpKind::ConvOvfI8
+            | OpKind::ConvOvfU16
+            | OpKind::ConvOvfI16
+            | OpKind::ConvOvfU32
+            | OpKind::ConvOvfI32
+            | OpKind::ConvOvfU64
+            | OpKind::ConvOvfI64
+            | OpKind::ConvOvfU8Un
+            | OpKind::ConvOvfI8Un
+            | OpKind::ConvOvfU16Un
+            | OpKind::ConvOvfI16Un
+            | OpKind::ConvOvfU32Un
+            | OpKind::ConvOvfI32Un
+            | OpKind::ConvOvfU64Un
+            | OpKind::ConvOvfI64Un => {
+                let src = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                check_arthmetic(index, src)?;
+                let (target, _) = self
+                    .kind
+                    .checked_conv()
+                    .expect("Checked conversion has a target!");
+                self.resolved_type = Some(target.clone());
+                state.push(target);
+            }
             OpKind::BR(_) => self.resolved_type = Some(Type::Void),
-            OpKind::LDLoc(index) => {
-                let loc_type = &locals[*index];
+            OpKind::LDLoc(local) => {
+                let loc_type = get_local(locals, index, *local)?;
                 state.push(loc_type.clone());
             }
-            OpKind::STLoc(index) => {
-                let s_type = state.pop().unwrap();
-                if s_type != locals[*index] {
-                    return Err(MethodIRError::LocalVarTypeMismatch(
-                        s_type,
-                        locals[*index].clone(),
-                        *index,
-                    ));
+            OpKind::STLoc(local) => {
+                let loc_type = get_local(locals, index, *local)?;
+                let s_type = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                if !s_type.is_assignable_to(loc_type) {
+                    return Err(MethodIRError::LocalVarTypeMismatch {
+                        index,
+                        got: s_type,
+                        expected: loc_type.clone(),
+                        local: *local,
+                    });
                 }
             }
             OpKind::Call(_, sig) => {
                 for arg_index in 0..sig.args.len() {
                     let arg = sig.args[sig.args.len() - arg_index - 1].clone();
-                    let curr = state.pop().unwrap();
-                    if arg != curr {
-                        panic!("arg type mismatch in call!");
+                    let curr = state.pop().ok_or(MethodIRError::StackUnderflow(index))?;
+                    if !curr.is_assignable_to(&arg) {
+                        return Err(MethodIRError::CallArgMismatch {
+                            index,
+                            expected: arg,
+                            got: curr,
+                        });
                     }
                 }
                 if sig.ret != Type::Void {
@@ -4,6 +4,7 @@ use super::{op::OpKind, BlockLink, InstructionIndex, MethodIRError, Signature, S
 #[derive(Debug, Clone)]
 pub(crate) struct OpBlock {
     pub(crate) block: VOp,
+    state_in: Option<StackState>,
     state_change: Option<StackState>,
     link_out: BlockLink,
     block_beg: InstructionIndex,
@@ -15,8 +16,9 @@ impl OpBlock {
         sig: &Signature,
         locals: &[Type],
     ) -> Result<(), MethodIRError> {
-        for op in &mut self.block {
-            op.resolve(&mut state, sig, locals)?;
+        self.state_in = Some(state.clone());
+        for (index, op) in self.block.iter_mut().enumerate() {
+            op.resolve(self.block_beg + index, &mut state, sig, locals)?;
         }
         self.state_change = Some(state);
         Ok(())
@@ -33,6 +35,10 @@ impl OpBlock {
     pub(crate) fn state(&self) -> Option<StackState> {
         self.state_change.clone()
     }
+    /// Stack state at the beginning of the block.
+    pub(crate) fn state_in(&self) -> Option<&StackState> {
+        self.state_in.as_ref()
+    }
     pub(crate) fn is_resolved(&self) -> bool {
         self.state_change.is_some()
     }
@@ -41,6 +47,8 @@ impl OpBlock {
         let last = &ops[ops.len() - 1].kind();
         let link_out = if let OpKind::Ret = last {
             BlockLink::Return
+        } else if let OpKind::BR(target) = last {
+            BlockLink::Jump(*target)
         } else if let Some(target) = last.branch_target() {
             BlockLink::Branch(block_end, target)
         } else {
@@ -48,6 +56,7 @@ impl OpBlock {
         };
         OpBlock {
             link_out,
+            state_in: None,
             state_change: None,
             block: ops,
             block_beg,
@@ -309,6 +309,27 @@ fn neg_i32() {
     }
 }
 #[test]
+fn neg_u16() {
+    let args: [Type; 1] = [Type::U16];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [OpKind::LDArg(0), OpKind::Neg, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `neg`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(u16) -> i32>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u16();
+        let rust_result = -(a as i32);
+        let c = unsafe { f.call(a) };
+        assert_eq!(rust_result, c, "-a");
+    }
+}
+#[test]
 fn ld_st_loc_i32() {
     let args: [Type; 1] = [Type::I32];
     let sig: (&[Type], Type) = (&args, Type::I32);
@@ -593,6 +614,50 @@ fn shl_i32() {
     }
 }
 #[test]
+fn shr_i32() {
+    let args: [Type; 2] = [Type::I32, Type::I32];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::SHR, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `shr`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32, i32) -> i32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_i32();
+        let b = rnd_i32().abs() % 32;
+        let rust_result = a >> b;
+        let csharp_result = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, csharp_result, "a >> b");
+    }
+}
+#[test]
+fn shr_u32() {
+    let args: [Type; 2] = [Type::U32, Type::U32];
+    let sig: (&[Type], Type) = (&args, Type::U32);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::SHR, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `shr`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(u32, u32) -> u32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u32();
+        let b = rnd_u32() % 32;
+        let rust_result = a >> b;
+        let csharp_result = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, csharp_result, "a >> b");
+    }
+}
+#[test]
 fn conv_i8() {
     let args: [Type; 1] = [Type::I32];
     let sig: (&[Type], Type) = (&args, Type::I8);
@@ -774,3 +839,1106 @@ fn conv_u64() {
         assert_eq!(rust_result, csharp_result, "a as u64");
     }
 }
+#[test]
+fn beq_obj() {
+    let args: [Type; 2] = [Type::ObjRef, Type::ObjRef];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDArg(1),
+        OpKind::BEQ(5),
+        OpKind::LDCI32(0),
+        OpKind::Ret,
+        OpKind::LDCI32(1),
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `beq`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe {
+        execution_engine.get_function::<unsafe extern "C" fn(*const u8, *const u8) -> i32>("f")
+    }
+    .unwrap();
+    let (a, b) = (1_u8, 2_u8);
+    assert_eq!(unsafe { f.call(&a, &a) }, 1);
+    assert_eq!(unsafe { f.call(&a, &b) }, 0);
+    assert_eq!(unsafe { f.call(std::ptr::null(), std::ptr::null()) }, 1);
+}
+#[test]
+fn is_null() {
+    let args: [Type; 1] = [Type::ObjRef];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDNull,
+        OpKind::BEQ(5),
+        OpKind::LDCI32(0),
+        OpKind::Ret,
+        OpKind::LDCI32(1),
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `is_null`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(*const u8) -> i32>("f") }
+        .unwrap();
+    assert_eq!(unsafe { f.call(&1) }, 0);
+    assert_eq!(unsafe { f.call(std::ptr::null()) }, 1);
+}
+#[test]
+fn ternary_i32() {
+    //`a` stays on the stack while `b > 0 ? 1 : -1` is evaluated.
+    let (sig, ops, locals) = crate::ir::text::parse_method(
+        ".sig i32 (i32, i32)
+                   ldarg 0
+                   ldarg 1
+                   ldc.i32 0
+                   bgt positive
+                   ldc.i32 -1
+                   br done
+         positive: ldc.i32 1
+         done:     mul
+                   ret",
+    )
+    .expect("Could not parse method `ternary`");
+    let ctx = Context::create();
+    let method = Method::from_ops(sig, &ops, &locals).expect("Could not compile method `ternary`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32, i32) -> i32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_i32() % (i32::MAX / 2);
+        let b = rnd_i32();
+        let rust_result = a * if b > 0 { 1 } else { -1 };
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, c, "a * (b > 0 ? 1 : -1)");
+    }
+}
+#[test]
+fn add_i64() {
+    let args: [Type; 2] = [Type::I64, Type::I64];
+    let sig: (&[Type], Type) = (&args, Type::I64);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::Add, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `add`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i64, i64) -> i64>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = (i64::from(rnd_i32()) << 30) ^ i64::from(rnd_u32());
+        let b = (i64::from(rnd_i32()) << 30) ^ i64::from(rnd_u32());
+        let rust_result = a.wrapping_add(b);
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, c, "a + b");
+    }
+}
+#[test]
+fn add_i16() {
+    //Narrow values are extended to int32 on the stack, so the sum does not wrap at 16 bits.
+    let args: [Type; 2] = [Type::I16, Type::I16];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::Add, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `add`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i16, i16) -> i32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u32() as i16;
+        let b = rnd_u32() as i16;
+        let rust_result = i32::from(a) + i32::from(b);
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, c, "{a} + {b}");
+    }
+}
+#[test]
+fn add_u8() {
+    let args: [Type; 2] = [Type::U8, Type::U8];
+    let sig: (&[Type], Type) = (&args, Type::U32);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::Add, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `add`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(u8, u8) -> u32>("f") }
+        .unwrap();
+    assert_eq!(unsafe { f.call(200, 100) }, 300);
+    for _ in 0..10_000 {
+        let a = rnd_u32() as u8;
+        let b = rnd_u32() as u8;
+        let rust_result = u32::from(a) + u32::from(b);
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, c, "{a} + {b}");
+    }
+}
+#[test]
+fn and_bool() {
+    let args: [Type; 2] = [Type::Bool, Type::Bool];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::And, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `and`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(bool, bool) -> i32>("f") }
+            .unwrap();
+    for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+        assert_eq!(i32::from(a & b), unsafe { f.call(a, b) }, "{a} & {b}");
+    }
+}
+#[test]
+fn max_i16() {
+    //The larger argument flows into the return block through a phi node.
+    let (sig, ops, locals) = crate::ir::text::parse_method(
+        ".sig i16 (i16, i16)
+                   ldarg 0
+                   ldarg 1
+                   bge first
+                   ldarg 1
+                   br done
+         first:    ldarg 0
+         done:     ret",
+    )
+    .expect("Could not parse method `max`");
+    let ctx = Context::create();
+    let method = Method::from_ops(sig, &ops, &locals).expect("Could not compile method `max`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i16, i16) -> i16>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u32() as i16;
+        let b = rnd_u32() as i16;
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(a.max(b), c, "max({a}, {b})");
+    }
+}
+#[test]
+fn store_narrow_local() {
+    //Storing an int32 into a narrow local truncates it, and loading it back extends it by the local's type.
+    let (sig, ops, locals) = crate::ir::text::parse_method(
+        ".sig i32 (i32)
+         .locals (i8, i16)
+         ldarg 0
+         stloc 0
+         ldarg 0
+         stloc 1
+         ldloc 0
+         ldloc 1
+         add
+         ret",
+    )
+    .expect("Could not parse method `store_narrow`");
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(sig, &ops, &locals).expect("Could not compile method `store_narrow`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32) -> i32>("f") }.unwrap();
+    assert_eq!(unsafe { f.call(300) }, 44 + 300);
+    for _ in 0..10_000 {
+        let a = rnd_u32() as i32;
+        let rust_result = i32::from(a as i8) + i32::from(a as i16);
+        assert_eq!(rust_result, unsafe { f.call(a) }, "{a}");
+    }
+}
+#[test]
+fn ternary_i16() {
+    //`b > 0 ? 0 : a` joins an i16 with an int32, which merge as int32.
+    let (sig, ops, locals) = crate::ir::text::parse_method(
+        ".sig i32 (i16, i32)
+                   ldarg 1
+                   ldc.i32 0
+                   bgt positive
+                   ldarg 0
+                   br done
+         positive: ldc.i32 0
+         done:     ret",
+    )
+    .expect("Could not parse method `ternary`");
+    let ctx = Context::create();
+    let method = Method::from_ops(sig, &ops, &locals).expect("Could not compile method `ternary`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i16, i32) -> i32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u32() as i16;
+        let b = rnd_i32();
+        let rust_result = if b > 0 { 0 } else { i32::from(a) };
+        assert_eq!(rust_result, unsafe { f.call(a, b) }, "{b} > 0 ? 0 : {a}");
+    }
+}
+#[test]
+fn add_f64() {
+    let args: [Type; 2] = [Type::F64, Type::F64];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::Add, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `add`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(f64, f64) -> f64>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = f64::from(rnd_i32()) / 7.0;
+        let b = f64::from(rnd_i32()) / 3.0;
+        let rust_result = a + b;
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result.to_bits(), c.to_bits(), "a + b");
+    }
+}
+#[test]
+fn add_iptr() {
+    let args: [Type; 2] = [Type::IPtr, Type::IPtr];
+    let sig: (&[Type], Type) = (&args, Type::IPtr);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::Add, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `add`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe {
+        execution_engine.get_function::<unsafe extern "C" fn(isize, isize) -> isize>("f")
+    }
+    .unwrap();
+    for _ in 0..10_000 {
+        let a = (rnd_i32() as isize) << (usize::BITS - 32);
+        let b = rnd_i32() as isize;
+        let rust_result = a.wrapping_add(b);
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, c, "a + b");
+    }
+}
+#[test]
+fn add_uptr() {
+    let args: [Type; 2] = [Type::UPtr, Type::UPtr];
+    let sig: (&[Type], Type) = (&args, Type::UPtr);
+    let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::Add, OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `add`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe {
+        execution_engine.get_function::<unsafe extern "C" fn(usize, usize) -> usize>("f")
+    }
+    .unwrap();
+    for _ in 0..10_000 {
+        let a = (rnd_u32() as usize) << (usize::BITS - 32);
+        let b = rnd_u32() as usize;
+        let rust_result = a.wrapping_add(b);
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, c, "a + b");
+    }
+}
+#[test]
+fn ret_bool() {
+    let args: [Type; 1] = [Type::Bool];
+    let sig: (&[Type], Type) = (&args, Type::Bool);
+    let ops = [OpKind::LDArg(0), OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `ret`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(bool) -> bool>("f") }
+        .unwrap();
+    unsafe {
+        assert!(f.call(true));
+        assert!(!f.call(false));
+    }
+}
+#[test]
+fn ret_bool_from_i32() {
+    let args: [Type; 1] = [Type::I32];
+    let sig: (&[Type], Type) = (&args, Type::Bool);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDCI32(2),
+        OpKind::And,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `ret`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    // Read the raw byte, so a `bool` other than 0 or 1 can't slip through.
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32) -> u8>("f") }.unwrap();
+    unsafe {
+        assert_eq!(f.call(2), 1);
+        assert_eq!(f.call(6), 1);
+        assert_eq!(f.call(1), 0);
+        assert_eq!(f.call(0), 0);
+    }
+}
+#[test]
+fn ret_char() {
+    let args: [Type; 1] = [Type::Char];
+    let sig: (&[Type], Type) = (&args, Type::Char);
+    let ops = [OpKind::LDArg(0), OpKind::Ret];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `ret`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(u16) -> u16>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u16();
+        let c = unsafe { f.call(a) };
+        assert_eq!(a, c, "a");
+    }
+}
+#[test]
+fn locals_all_types() {
+    let args: [Type; 1] = [Type::I32];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [OpKind::LDArg(0), OpKind::Ret];
+    let locals = [
+        Type::I64,
+        Type::U64,
+        Type::F64,
+        Type::I32,
+        Type::U32,
+        Type::F32,
+        Type::I16,
+        Type::U16,
+        Type::U8,
+        Type::I8,
+        Type::UPtr,
+        Type::IPtr,
+        Type::Char,
+        Type::ObjRef,
+        Type::Bool,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &locals)
+        .expect("Could not compile method `locals`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32) -> i32>("f") }.unwrap();
+    unsafe {
+        assert_eq!(f.call(7), 7);
+    }
+}
+#[test]
+fn ld_st_loc_u32() {
+    //Values loaded from unsigned locals have to stay unsigned for `div` and `blt`.
+    let (sig, ops, locals) = crate::ir::text::parse_method(
+        ".sig u32 (u32, u32)
+         .locals (u32, u32)
+                 ldarg 0
+                 stloc 0
+                 ldarg 1
+                 stloc 1
+                 ldloc 0
+                 ldloc 1
+                 blt less
+                 ldloc 0
+                 ldloc 1
+                 div
+                 ret
+         less:   ldloc 1
+                 ret",
+    )
+    .expect("Could not parse method `ld_st_loc`");
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(sig, &ops, &locals).expect("Could not compile method `ld_st_loc`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(u32, u32) -> u32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u32();
+        let b = (rnd_u32() >> (rnd_u32() % 32)).max(1);
+        let rust_result = if a < b { b } else { a / b };
+        let c = unsafe { f.call(a, b) };
+        assert_eq!(rust_result, c, "a < b ? b : a / b");
+    }
+}
+#[test]
+fn ld_st_loc_f64() {
+    let args: [Type; 1] = [Type::F64];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::STLoc(0),
+        OpKind::LDLoc(0),
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[Type::F64])
+        .expect("Could not compile method `ld_st_loc`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f64) -> f64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = f64::from(rnd_i32()) / 13.0;
+        let c = unsafe { f.call(a) };
+        assert_eq!(a.to_bits(), c.to_bits(), "a");
+    }
+}
+#[test]
+fn ld_st_loc_u8() {
+    let args: [Type; 1] = [Type::U8];
+    let sig: (&[Type], Type) = (&args, Type::U8);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::STLoc(0),
+        OpKind::LDLoc(0),
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[Type::U8])
+        .expect("Could not compile method `ld_st_loc`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(u8) -> u8>("f") }.unwrap();
+    for a in 0..=u8::MAX {
+        let c = unsafe { f.call(a) };
+        assert_eq!(a, c, "a");
+    }
+}
+#[test]
+fn ldc_i64() {
+    let args: [Type; 1] = [Type::I64];
+    let sig: (&[Type], Type) = (&args, Type::I64);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDCI64(0x1234_5678_9ABC),
+        OpKind::Add,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `ldc`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i64) -> i64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = i64::from(rnd_i32()) << 16;
+        let rust_result = a + 0x1234_5678_9ABC;
+        let c = unsafe { f.call(a) };
+        assert_eq!(rust_result, c, "a + 0x1234_5678_9ABC");
+    }
+}
+#[test]
+fn ldc_f32() {
+    let args: [Type; 1] = [Type::F32];
+    let sig: (&[Type], Type) = (&args, Type::F32);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDCF32(0.1),
+        OpKind::Mul,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `ldc`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f32) -> f32>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = f32::from(rnd_i16()) / 3.0;
+        let rust_result = a * 0.1;
+        let c = unsafe { f.call(a) };
+        assert_eq!(rust_result.to_bits(), c.to_bits(), "a * 0.1");
+    }
+}
+#[test]
+fn ldc_f64() {
+    let args: [Type; 1] = [Type::F64];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDCF64(-2.5e-7),
+        OpKind::Mul,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method =
+        Method::from_ops(Signature::new(&sig), &ops, &[]).expect("Could not compile method `ldc`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f64) -> f64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = f64::from(rnd_i32()) / 7.0;
+        let rust_result = a * -2.5e-7;
+        let c = unsafe { f.call(a) };
+        assert_eq!(rust_result.to_bits(), c.to_bits(), "a * -2.5e-7");
+    }
+}
+fn rnd_u64() -> u64 {
+    (u64::from(rnd_u32()) << 32) | u64::from(rnd_u32())
+}
+/// Random float bits, including NaNs, infinities and values out of range of any integer.
+fn rnd_f64() -> f64 {
+    f64::from_bits(rnd_u64())
+}
+#[test]
+fn conv_f32() {
+    let args: [Type; 1] = [Type::I64];
+    let sig: (&[Type], Type) = (&args, Type::F32);
+    let ops = [OpKind::LDArg(0), OpKind::ConvF32, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_f32`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i64) -> f32>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u64() as i64 >> (rnd_u32() % 64);
+        let rust_result = a as f32;
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(rust_result.to_bits(), csharp_result.to_bits(), "a as f32");
+    }
+}
+#[test]
+fn conv_f64() {
+    let args: [Type; 1] = [Type::I32];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [OpKind::LDArg(0), OpKind::ConvF64, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_f64`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32) -> f64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_i32();
+        let rust_result = f64::from(a);
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(rust_result.to_bits(), csharp_result.to_bits(), "a as f64");
+    }
+}
+#[test]
+fn conv_f64_u32() {
+    //`conv.r8` interprets int32 as signed, even if the IR knows it is unsigned.
+    let args: [Type; 1] = [Type::U32];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [OpKind::LDArg(0), OpKind::ConvF64, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_f64`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(u32) -> f64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u32();
+        let rust_result = f64::from(a as i32);
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(
+            rust_result.to_bits(),
+            csharp_result.to_bits(),
+            "a as i32 as f64"
+        );
+    }
+}
+#[test]
+fn conv_f64_u16() {
+    //Narrow unsigned values are zero-extended to int32 on the stack, so they stay positive.
+    let args: [Type; 1] = [Type::U16];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [OpKind::LDArg(0), OpKind::ConvF64, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_f64`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(u16) -> f64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u16() | 0x8000;
+        let rust_result = f64::from(a);
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(rust_result.to_bits(), csharp_result.to_bits(), "a as f64");
+    }
+}
+#[test]
+fn conv_r_un() {
+    let args: [Type; 1] = [Type::I64];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [OpKind::LDArg(0), OpKind::ConvRUn, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_r_un`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i64) -> f64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_u64() as i64;
+        let rust_result = a as u64 as f64;
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(
+            rust_result.to_bits(),
+            csharp_result.to_bits(),
+            "a as u64 as f64"
+        );
+    }
+}
+#[test]
+fn conv_r_un_i8() {
+    //A negative int8 is sign-extended to int32 first, and then read as unsigned.
+    let args: [Type; 1] = [Type::I8];
+    let sig: (&[Type], Type) = (&args, Type::F64);
+    let ops = [OpKind::LDArg(0), OpKind::ConvRUn, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_r_un`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i8) -> f64>("f") }.unwrap();
+    for a in i8::MIN..=i8::MAX {
+        let rust_result = f64::from(i32::from(a) as u32);
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(
+            rust_result.to_bits(),
+            csharp_result.to_bits(),
+            "a as i32 as u32 as f64"
+        );
+    }
+}
+#[test]
+fn conv_i32_f64() {
+    let args: [Type; 1] = [Type::F64];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [OpKind::LDArg(0), OpKind::ConvI32, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_i32`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f64) -> i32>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = match rnd_u32() % 2 {
+            0 => rnd_f64(),
+            _ => f64::from(rnd_i32()) * 1.75,
+        };
+        let rust_result = a as i32;
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(rust_result, csharp_result, "{a} as i32");
+    }
+}
+#[test]
+fn conv_u8_f32() {
+    let args: [Type; 1] = [Type::F32];
+    let sig: (&[Type], Type) = (&args, Type::U8);
+    let ops = [OpKind::LDArg(0), OpKind::ConvU8, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_u8`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f32) -> u8>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = f32::from(rnd_i16()) / 64.0;
+        let rust_result = a as u8;
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(rust_result, csharp_result, "{a} as u8");
+    }
+}
+#[test]
+fn conv_u64_f64() {
+    let args: [Type; 1] = [Type::F64];
+    let sig: (&[Type], Type) = (&args, Type::U64);
+    let ops = [OpKind::LDArg(0), OpKind::ConvU64, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_u64`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f64) -> u64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_f64();
+        let rust_result = a as u64;
+        let csharp_result = unsafe { f.call(a) };
+        assert_eq!(rust_result, csharp_result, "{a} as u64");
+    }
+}
+#[test]
+fn conv_f32_f64() {
+    let args: [Type; 1] = [Type::F64];
+    let sig: (&[Type], Type) = (&args, Type::F32);
+    let ops = [OpKind::LDArg(0), OpKind::ConvF32, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_f32`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f64) -> f32>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_f64();
+        let rust_result = a as f32;
+        let csharp_result = unsafe { f.call(a) };
+        assert!(
+            rust_result.to_bits() == csharp_result.to_bits()
+                || (rust_result.is_nan() && csharp_result.is_nan()),
+            "{a} as f32"
+        );
+    }
+}
+thread_local! {
+    static OVERFLOWS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+extern "C" fn count_overflow() {
+    OVERFLOWS.with(|overflows| overflows.set(overflows.get() + 1));
+}
+/// Runs `f` with the counting overflow handler, returning its result and whether it overflowed.
+fn catch_overflow<T>(f: impl FnOnce() -> T) -> (T, bool) {
+    crate::set_overflow_handler(count_overflow);
+    OVERFLOWS.with(|overflows| overflows.set(0));
+    let res = f();
+    (res, OVERFLOWS.with(std::cell::Cell::get) != 0)
+}
+/// Random value, biased towards the bounds of checked ops.
+fn rnd_edge_i32() -> i32 {
+    match rnd_u32() % 4 {
+        0 => rnd_i32(),
+        1 => rnd_i32() % 0x1_0000,
+        2 => i32::MAX - (rnd_u32() % 4) as i32,
+        _ => i32::MIN + (rnd_u32() % 4) as i32,
+    }
+}
+#[test]
+fn add_ovf_i32() {
+    let args: [Type; 2] = [Type::I32, Type::I32];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDArg(1),
+        OpKind::AddOvf,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `add_ovf`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32, i32) -> i32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let (a, b) = (rnd_edge_i32(), rnd_edge_i32());
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a, b) });
+        match a.checked_add(b) {
+            Some(rust_result) => {
+                assert!(!overflowed, "{a} + {b} should not overflow");
+                assert_eq!(rust_result, csharp_result, "{a} + {b}");
+            }
+            None => assert!(overflowed, "{a} + {b} should overflow"),
+        }
+    }
+}
+#[test]
+fn sub_ovf_un_u16() {
+    let args: [Type; 2] = [Type::U16, Type::U16];
+    let sig: (&[Type], Type) = (&args, Type::U32);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDArg(1),
+        OpKind::SubOvfUn,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `sub_ovf_un`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(u16, u16) -> u32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let (a, b) = (rnd_u16(), rnd_u16());
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a, b) });
+        match u32::from(a).checked_sub(u32::from(b)) {
+            Some(rust_result) => {
+                assert!(!overflowed, "{a} - {b} should not overflow");
+                assert_eq!(rust_result, csharp_result, "{a} - {b}");
+            }
+            None => assert!(overflowed, "{a} - {b} should overflow"),
+        }
+    }
+}
+#[test]
+fn mul_ovf_i64() {
+    let args: [Type; 2] = [Type::I64, Type::I64];
+    let sig: (&[Type], Type) = (&args, Type::I64);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDArg(1),
+        OpKind::MulOvf,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `mul_ovf`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(i64, i64) -> i64>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let (a, b) = (i64::from(rnd_edge_i32()), i64::from(rnd_edge_i32()) * 3);
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a, b) });
+        match a.checked_mul(b) {
+            Some(rust_result) => {
+                assert!(!overflowed, "{a} * {b} should not overflow");
+                assert_eq!(rust_result, csharp_result, "{a} * {b}");
+            }
+            None => assert!(overflowed, "{a} * {b} should overflow"),
+        }
+    }
+}
+#[test]
+fn mul_ovf_un_u32() {
+    let args: [Type; 2] = [Type::U32, Type::U32];
+    let sig: (&[Type], Type) = (&args, Type::U32);
+    let ops = [
+        OpKind::LDArg(0),
+        OpKind::LDArg(1),
+        OpKind::MulOvfUn,
+        OpKind::Ret,
+    ];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `mul_ovf_un`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f = unsafe { execution_engine.get_function::<unsafe extern "C" fn(u32, u32) -> u32>("f") }
+        .unwrap();
+    for _ in 0..10_000 {
+        let (a, b) = (rnd_u32() >> (rnd_u32() % 32), rnd_u32() >> (rnd_u32() % 32));
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a, b) });
+        match a.checked_mul(b) {
+            Some(rust_result) => {
+                assert!(!overflowed, "{a} * {b} should not overflow");
+                assert_eq!(rust_result, csharp_result, "{a} * {b}");
+            }
+            None => assert!(overflowed, "{a} * {b} should overflow"),
+        }
+    }
+}
+#[test]
+fn conv_ovf_u8_i32() {
+    let args: [Type; 1] = [Type::I32];
+    let sig: (&[Type], Type) = (&args, Type::U8);
+    let ops = [OpKind::LDArg(0), OpKind::ConvOvfU8, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_ovf_u8`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32) -> u8>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_i32() % 0x200;
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a) });
+        match u8::try_from(a) {
+            Ok(rust_result) => {
+                assert!(!overflowed, "{a} should fit u8");
+                assert_eq!(rust_result, csharp_result, "{a} as u8");
+            }
+            Err(_) => assert!(overflowed, "{a} should not fit u8"),
+        }
+    }
+}
+#[test]
+fn conv_ovf_i16_un_u32() {
+    let args: [Type; 1] = [Type::I32];
+    let sig: (&[Type], Type) = (&args, Type::I16);
+    let ops = [OpKind::LDArg(0), OpKind::ConvOvfI16Un, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_ovf_i16_un`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(i32) -> i16>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = match rnd_u32() % 2 {
+            0 => rnd_i32(),
+            _ => rnd_i32() % 0x1_0000,
+        };
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a) });
+        //`.un` reads the int32 as unsigned, so negative values are huge and never fit.
+        match i16::try_from(a as u32) {
+            Ok(rust_result) => {
+                assert!(!overflowed, "{a} should fit i16");
+                assert_eq!(rust_result, csharp_result, "{a} as i16");
+            }
+            Err(_) => assert!(overflowed, "{a} should not fit i16"),
+        }
+    }
+}
+#[test]
+fn conv_ovf_i32_f64() {
+    let args: [Type; 1] = [Type::F64];
+    let sig: (&[Type], Type) = (&args, Type::I32);
+    let ops = [OpKind::LDArg(0), OpKind::ConvOvfI32, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_ovf_i32`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f64) -> i32>("f") }.unwrap();
+    let edges = [
+        f64::NAN,
+        f64::INFINITY,
+        -2_147_483_648.9,
+        -2_147_483_649.0,
+        2_147_483_647.9,
+        2_147_483_648.0,
+    ];
+    for i in 0..10_000 {
+        let a = match edges.get(i) {
+            Some(edge) => *edge,
+            None if rnd_u32() & 1 == 0 => rnd_f64(),
+            None => f64::from(rnd_i32()) * 1.25,
+        };
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a) });
+        let truncated = a.trunc();
+        if truncated >= f64::from(i32::MIN) && truncated <= f64::from(i32::MAX) {
+            assert!(!overflowed, "{a} should fit i32");
+            assert_eq!(a as i32, csharp_result, "{a} as i32");
+        } else {
+            assert!(overflowed, "{a} should not fit i32");
+        }
+    }
+}
+#[test]
+fn conv_ovf_u64_f32() {
+    let args: [Type; 1] = [Type::F32];
+    let sig: (&[Type], Type) = (&args, Type::U64);
+    let ops = [OpKind::LDArg(0), OpKind::ConvOvfU64, OpKind::Ret];
+    let ctx = Context::create();
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `conv_ovf_u64`");
+    let module = compile_fn(&ctx, &method);
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Aggressive)
+        .unwrap();
+    let f =
+        unsafe { execution_engine.get_function::<unsafe extern "C" fn(f32) -> u64>("f") }.unwrap();
+    for _ in 0..10_000 {
+        let a = rnd_f64() as f32;
+        let (csharp_result, overflowed) = catch_overflow(|| unsafe { f.call(a) });
+        let truncated = f64::from(a).trunc();
+        if (0.0..18_446_744_073_709_551_616.0).contains(&truncated) {
+            assert!(!overflowed, "{a} should fit u64");
+            assert_eq!(a as u64, csharp_result, "{a} as u64");
+        } else {
+            assert!(overflowed, "{a} should not fit u64");
+        }
+    }
+}
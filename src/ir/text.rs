@@ -0,0 +1,882 @@
+//! A small textual format for writing IR by hand, e.g.
+//! ```text
+//! .sig i32 (i32)
+//! .locals (i32)
+//!         ldarg 0
+//!         stloc 0
+//! loop:   ldloc 0
+//!         ldc.i32 1
+//!         sub
+//!         dup
+//!         stloc 0
+//!         ldc.i32 0
+//!         bgt loop
+//!         ldloc 0
+//!         ret
+//! ```
+//! Types use their mangled names, and calls are written like in the disassembler: `call i32 [Asm]Namespace.Class::Add(i32, i32)`.
+//! Protected regions are written as `.try start to end catch [Asm]Namespace.Class handler start to end`, with `filter label`, `finally` or `fault` in place of `catch`.
+//! Region ends may be past the last instruction, so `IL_<index>` can be used in place of a label to name an instruction by its index.
+//! The [`Display`] output of a resolved [`Method`] uses the same format.
+use super::{InstructionIndex, Signature, VType};
+use crate::type_system::paths::{ClassPath, MethodPath};
+use crate::{Handler, Method, OpKind, ProtectedRegion, Type};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorKind {
+    UnknownInstruction(String),
+    UnknownDirective(String),
+    UnknownType(String),
+    MissingOperand,
+    InvalidOperand(String),
+    UnexpectedOperand(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    /// Label is not followed by any instruction.
+    DanglingLabel(String),
+    DuplicateDirective(&'static str),
+    MissingSignature,
+}
+/// An error in IR text, with the 1-based line it occured on.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
+impl ParseError {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "line {}: {:?}", self.line, self.kind)
+    }
+}
+/// Parses IR text into a signature, ops and locals, ready to be passed to [`crate::Method::from_ops`].
+/// Protected regions are parsed, but dropped. Use [`parse_method_with_regions`] to keep them.
+pub fn parse_method(src: &str) -> Result<(Signature, Vec<OpKind>, VType), ParseError> {
+    let (sig, ops, locals, _) = parse_method_with_regions(src)?;
+    Ok((sig, ops, locals))
+}
+/// A parsed method: its signature, ops, locals and protected regions.
+pub type ParsedMethod = (Signature, Vec<OpKind>, VType, Vec<ProtectedRegion>);
+/// Like [`parse_method`], but also returns the protected regions, ready to be passed to [`crate::Method::from_ops_with_regions`].
+pub fn parse_method_with_regions(src: &str) -> Result<ParsedMethod, ParseError> {
+    let mut sig = None;
+    let mut locals = None;
+    let mut labels = HashMap::new();
+    //Branches are resolved once all labels are known: (op index, label, line).
+    let mut branches = Vec::new();
+    //Regions are resolved the same way, once all labels are known.
+    let mut regions = Vec::new();
+    let mut ops = Vec::new();
+    let mut first_op_line = None;
+    let mut pending_label = None;
+    let mut line_number = 0;
+    for (index, line) in src.lines().enumerate() {
+        line_number = index + 1;
+        let error = |kind| ParseError {
+            line: line_number,
+            kind,
+        };
+        let mut line = strip_comment(line).trim();
+        if let Some(directive) = line.strip_prefix('.') {
+            let (name, rest) = split_word(directive);
+            match name {
+                "sig" => {
+                    if sig.is_some() {
+                        return Err(error(ParseErrorKind::DuplicateDirective(".sig")));
+                    }
+                    let (ret, rest) = split_word(rest);
+                    let ret = parse_type(ret).map_err(error)?;
+                    let args = parse_type_list(rest).map_err(error)?;
+                    sig = Some(Signature::new(&(&args, ret)));
+                }
+                "locals" => {
+                    if locals.is_some() {
+                        return Err(error(ParseErrorKind::DuplicateDirective(".locals")));
+                    }
+                    locals = Some(parse_type_list(rest).map_err(error)?);
+                }
+                "try" => regions.push((parse_region(rest).map_err(error)?, line_number)),
+                _ => return Err(error(ParseErrorKind::UnknownDirective(name.to_owned()))),
+            }
+            continue;
+        }
+        if let Some((label, rest)) = line.split_once(':') {
+            //`::` only appears in call targets.
+            if is_ident(label.trim()) && !rest.starts_with(':') {
+                let label = label.trim();
+                if labels.insert(label.to_owned(), ops.len()).is_some() {
+                    return Err(error(ParseErrorKind::DuplicateLabel(label.to_owned())));
+                }
+                pending_label = Some((label.to_owned(), line_number));
+                line = rest.trim();
+            }
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, operand) = split_word(line);
+        let op = parse_op(mnemonic, operand).map_err(error)?;
+        if let Some(target) = branch_label(mnemonic, operand) {
+            branches.push((ops.len(), target.to_owned(), line_number));
+        }
+        first_op_line.get_or_insert(line_number);
+        pending_label = None;
+        ops.push(op);
+    }
+    if let Some((label, line)) = pending_label {
+        return Err(ParseError {
+            line,
+            kind: ParseErrorKind::DanglingLabel(label),
+        });
+    }
+    for (index, label, line) in branches {
+        let target = resolve_label(&labels, &label).ok_or(ParseError {
+            line,
+            kind: ParseErrorKind::UndefinedLabel(label),
+        })?;
+        set_branch_target(&mut ops[index], target);
+    }
+    let regions = regions
+        .into_iter()
+        .map(|(region, line)| {
+            let resolve = |label: &str| {
+                resolve_label(&labels, label).ok_or(ParseError {
+                    line,
+                    kind: ParseErrorKind::UndefinedLabel(label.to_owned()),
+                })
+            };
+            let handler = match (region.handler, region.filter) {
+                (Handler::Filter(_), Some(filter)) => Handler::Filter(resolve(filter)?),
+                (handler, _) => handler,
+            };
+            Ok(ProtectedRegion::new(
+                resolve(region.try_range.0)?..resolve(region.try_range.1)?,
+                resolve(region.handler_range.0)?..resolve(region.handler_range.1)?,
+                handler,
+            ))
+        })
+        .collect::<Result<_, _>>()?;
+    let sig = sig.ok_or(ParseError {
+        line: first_op_line.unwrap_or(line_number),
+        kind: ParseErrorKind::MissingSignature,
+    })?;
+    Ok((sig, ops, locals.unwrap_or_default(), regions))
+}
+/// Instruction index `label` refers to. `IL_<index>` names an instruction by its index, even if it has no label.
+fn resolve_label(labels: &HashMap<String, usize>, label: &str) -> Option<InstructionIndex> {
+    labels
+        .get(label)
+        .copied()
+        .or_else(|| label.strip_prefix("IL_")?.parse().ok())
+}
+/// A `.try` directive, with labels not resolved yet.
+struct RegionLabels<'a> {
+    try_range: (&'a str, &'a str),
+    handler_range: (&'a str, &'a str),
+    handler: Handler,
+    /// Label of the filter block, if `handler` is a filter.
+    filter: Option<&'a str>,
+}
+/// Parses the operands of a `.try` directive, e.g. `start to end finally handler start to end`.
+fn parse_region(src: &str) -> Result<RegionLabels<'_>, ParseErrorKind> {
+    let mut words = src.split_whitespace();
+    let try_start = next_word(&mut words)?;
+    expect_word(&mut words, "to")?;
+    let try_end = next_word(&mut words)?;
+    let (handler, filter) = match next_word(&mut words)? {
+        "catch" => (Handler::Catch(parse_class(next_word(&mut words)?)?), None),
+        "filter" => (Handler::Filter(0), Some(next_word(&mut words)?)),
+        "finally" => (Handler::Finally, None),
+        "fault" => (Handler::Fault, None),
+        word => return Err(ParseErrorKind::InvalidOperand(word.to_owned())),
+    };
+    expect_word(&mut words, "handler")?;
+    let handler_start = next_word(&mut words)?;
+    expect_word(&mut words, "to")?;
+    let handler_end = next_word(&mut words)?;
+    if let Some(word) = words.next() {
+        return Err(ParseErrorKind::UnexpectedOperand(word.to_owned()));
+    }
+    Ok(RegionLabels {
+        try_range: (try_start, try_end),
+        handler_range: (handler_start, handler_end),
+        handler,
+        filter,
+    })
+}
+fn next_word<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<&'a str, ParseErrorKind> {
+    words.next().ok_or(ParseErrorKind::MissingOperand)
+}
+fn expect_word<'a>(
+    words: &mut impl Iterator<Item = &'a str>,
+    keyword: &str,
+) -> Result<(), ParseErrorKind> {
+    match next_word(words)? {
+        word if word == keyword => Ok(()),
+        word => Err(ParseErrorKind::InvalidOperand(word.to_owned())),
+    }
+}
+/// Parses a class, written like in the disassembler, e.g. `[Asm]Namespace.Class`.
+fn parse_class(operand: &str) -> Result<ClassPath, ParseErrorKind> {
+    let (assembly, class) = operand
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .ok_or_else(|| ParseErrorKind::InvalidOperand(operand.to_owned()))?;
+    let (namespace, class_name) = class.rsplit_once('.').unwrap_or(("", class));
+    if class_name.is_empty() {
+        return Err(ParseErrorKind::InvalidOperand(operand.to_owned()));
+    }
+    Ok(ClassPath::new(assembly, namespace, class_name))
+}
+/// Dumps a resolved method in the textual format. Block edges and the stack after each instruction are written as comments,
+/// so the dump parses back into the same ops and protected regions with [`parse_method_with_regions`].
+impl Display for Method {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let sig = self.signature();
+        writeln!(
+            f,
+            ".sig {} ({})",
+            sig.ret().to_mangle_string(),
+            type_list(sig.args())
+        )?;
+        if !self.locals.is_empty() {
+            writeln!(f, ".locals ({})", type_list(&self.locals))?;
+        }
+        let block_at = |index: InstructionIndex| {
+            self.blocks
+                .iter()
+                .position(|block| block.block_beg() == index)
+        };
+        let label = |index: InstructionIndex| match block_at(index) {
+            Some(block) => format!("block_{block}"),
+            None => format!("IL_{index}"),
+        };
+        let successors: Vec<Vec<usize>> = (0..self.blocks.len())
+            .map(|index| self.successors(index))
+            .collect();
+        let block_list = |blocks: &mut dyn Iterator<Item = usize>| {
+            let blocks: Vec<_> = blocks.map(|block| format!("block_{block}")).collect();
+            match blocks.is_empty() {
+                true => "-".to_owned(),
+                false => blocks.join(", "),
+            }
+        };
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut predecessors =
+                (0..self.blocks.len()).filter(|pred| successors[*pred].contains(&index));
+            write!(f, "block_{index}: // ")?;
+            match block.state_in() {
+                Some(state) => write!(f, "in: [{}]", type_list(state.types()))?,
+                None => write!(f, "unreachable")?,
+            }
+            writeln!(
+                f,
+                ", preds: {}, succs: {}",
+                block_list(&mut predecessors),
+                block_list(&mut successors[index].iter().copied())
+            )?;
+            //Stack types are not stored per instruction, so they are recomputed from the state the block begins with.
+            let mut state = block.state_in().cloned();
+            for (op_index, op) in block.block.iter().enumerate() {
+                let mut text = String::new();
+                write_op(&mut text, op.kind(), label)?;
+                state = state.and_then(|mut state| {
+                    op.clone()
+                        .resolve(block.block_beg() + op_index, &mut state, sig, &self.locals)
+                        .ok()
+                        .map(|_| state)
+                });
+                match &state {
+                    Some(state) => writeln!(f, "    {text:<32} // [{}]", type_list(state.types()))?,
+                    None => writeln!(f, "    {text}")?,
+                }
+            }
+        }
+        for region in self.protected_regions() {
+            let (try_range, handler_range) = (region.try_range(), region.handler_range());
+            write!(
+                f,
+                ".try {} to {} ",
+                label(try_range.start),
+                label(try_range.end)
+            )?;
+            match region.handler() {
+                Handler::Catch(class) => write!(f, "catch {class} ")?,
+                Handler::Filter(filter) => write!(f, "filter {} ", label(*filter))?,
+                Handler::Finally => write!(f, "finally ")?,
+                Handler::Fault => write!(f, "fault ")?,
+            }
+            writeln!(
+                f,
+                "handler {} to {}",
+                label(handler_range.start),
+                label(handler_range.end)
+            )?;
+        }
+        Ok(())
+    }
+}
+/// Writes `op` in the textual format, naming branch targets with `label`.
+fn write_op(
+    out: &mut impl std::fmt::Write,
+    op: &OpKind,
+    label: impl Fn(InstructionIndex) -> String,
+) -> std::fmt::Result {
+    write!(out, "{}", mnemonic(op))?;
+    match op {
+        OpKind::BGE(target)
+        | OpKind::BLE(target)
+        | OpKind::BLT(target)
+        | OpKind::BGT(target)
+        | OpKind::BR(target)
+        | OpKind::BEQ(target)
+        | OpKind::BNE(target) => write!(out, " {}", label(*target)),
+        OpKind::LDCI32(value) => write!(out, " {value}"),
+        OpKind::LDCI64(value) => write!(out, " {value}"),
+        //`Debug` prints the shortest text that parses back into the same float.
+        OpKind::LDCF32(value) => write!(out, " {value:?}"),
+        OpKind::LDCF64(value) => write!(out, " {value:?}"),
+        OpKind::LDArg(index) | OpKind::LDLoc(index) | OpKind::STLoc(index) => {
+            write!(out, " {index}")
+        }
+        OpKind::LDStr(chars) => {
+            write!(out, " ")?;
+            write_string(out, chars)
+        }
+        OpKind::Call(path, sig) => write!(out, " {}", call_target(path, sig)),
+        _ => Ok(()),
+    }
+}
+/// Writes UTF-16 `chars` as a quoted string literal. Characters outside the BMP and lone surrogates
+/// are written as one `\u{...}` escape per code unit, so the literal parses back into the same units.
+fn write_string(out: &mut impl std::fmt::Write, chars: &[u16]) -> std::fmt::Result {
+    write!(out, "\"")?;
+    for c in char::decode_utf16(chars.iter().copied()) {
+        match c {
+            Ok(c) if c.len_utf16() == 1 => write!(out, "{}", c.escape_debug())?,
+            Ok(c) => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    write!(out, "\\u{{{unit:X}}}")?;
+                }
+            }
+            Err(err) => write!(out, "\\u{{{:X}}}", err.unpaired_surrogate())?,
+        }
+    }
+    write!(out, "\"")
+}
+fn mnemonic(op: &OpKind) -> &'static str {
+    match op {
+        OpKind::Add => "add",
+        OpKind::And => "and",
+        OpKind::BGE(_) => "bge",
+        OpKind::BLE(_) => "ble",
+        OpKind::BLT(_) => "blt",
+        OpKind::BGT(_) => "bgt",
+        OpKind::BR(_) => "br",
+        OpKind::BEQ(_) => "beq",
+        OpKind::BNE(_) => "bne",
+        OpKind::ConvU8 => "conv.u8",
+        OpKind::ConvI8 => "conv.i8",
+        OpKind::ConvU16 => "conv.u16",
+        OpKind::ConvI16 => "conv.i16",
+        OpKind::ConvU32 => "conv.u32",
+        OpKind::ConvI32 => "conv.i32",
+        OpKind::ConvU64 => "conv.u64",
+        OpKind::ConvI64 => "conv.i64",
+        OpKind::ConvF32 => "conv.f32",
+        OpKind::ConvF64 => "conv.f64",
+        OpKind::ConvRUn => "conv.r.un",
+        OpKind::ConvOvfU8 => "conv.ovf.u8",
+        OpKind::ConvOvfI8 => "conv.ovf.i8",
+        OpKind::ConvOvfU16 => "conv.ovf.u16",
+        OpKind::ConvOvfI16 => "conv.ovf.i16",
+        OpKind::ConvOvfU32 => "conv.ovf.u32",
+        OpKind::ConvOvfI32 => "conv.ovf.i32",
+        OpKind::ConvOvfU64 => "conv.ovf.u64",
+        OpKind::ConvOvfI64 => "conv.ovf.i64",
+        OpKind::ConvOvfU8Un => "conv.ovf.u8.un",
+        OpKind::ConvOvfI8Un => "conv.ovf.i8.un",
+        OpKind::ConvOvfU16Un => "conv.ovf.u16.un",
+        OpKind::ConvOvfI16Un => "conv.ovf.i16.un",
+        OpKind::ConvOvfU32Un => "conv.ovf.u32.un",
+        OpKind::ConvOvfI32Un => "conv.ovf.i32.un",
+        OpKind::ConvOvfU64Un => "conv.ovf.u64.un",
+        OpKind::ConvOvfI64Un => "conv.ovf.i64.un",
+        OpKind::AddOvf => "add.ovf",
+        OpKind::AddOvfUn => "add.ovf.un",
+        OpKind::SubOvf => "sub.ovf",
+        OpKind::SubOvfUn => "sub.ovf.un",
+        OpKind::MulOvf => "mul.ovf",
+        OpKind::MulOvfUn => "mul.ovf.un",
+        OpKind::Call(_, _) => "call",
+        OpKind::Div => "div",
+        OpKind::Dup => "dup",
+        OpKind::LDCI32(_) => "ldc.i32",
+        OpKind::LDCI64(_) => "ldc.i64",
+        OpKind::LDCF32(_) => "ldc.f32",
+        OpKind::LDCF64(_) => "ldc.f64",
+        OpKind::LDArg(_) => "ldarg",
+        OpKind::LDNull => "ldnull",
+        OpKind::LDStr(_) => "ldstr",
+        OpKind::Nop => "nop",
+        OpKind::Not => "not",
+        OpKind::Neg => "neg",
+        OpKind::Mul => "mul",
+        OpKind::Or => "or",
+        OpKind::Pop => "pop",
+        OpKind::Ret => "ret",
+        OpKind::Rem => "rem",
+        OpKind::Sub => "sub",
+        OpKind::SHL => "shl",
+        OpKind::SHR => "shr",
+        OpKind::XOr => "xor",
+        OpKind::LDLoc(_) => "ldloc",
+        OpKind::STLoc(_) => "stloc",
+    }
+}
+pub(crate) fn type_list(types: &[Type]) -> String {
+    let types: Vec<_> = types.iter().map(Type::to_mangle_string).collect();
+    types.join(", ")
+}
+/// Formats a call target like ILDasm does, e.g. `i32 [SimpleFunctions]SimpleFunctions.Class1::Add(i32, i32)`.
+pub(crate) fn call_target(path: &MethodPath, sig: &Signature) -> String {
+    format!(
+        "{} {path}({})",
+        sig.ret().to_mangle_string(),
+        type_list(sig.args())
+    )
+}
+fn parse_op(mnemonic: &str, operand: &str) -> Result<OpKind, ParseErrorKind> {
+    let no_operand = |op: OpKind| match operand {
+        "" => Ok(op),
+        _ => Err(ParseErrorKind::UnexpectedOperand(operand.to_owned())),
+    };
+    //Branch targets are patched in once all labels are known.
+    let branch = |op: fn(usize) -> OpKind| match operand {
+        "" => Err(ParseErrorKind::MissingOperand),
+        label if is_ident(label) => Ok(op(0)),
+        _ => Err(ParseErrorKind::InvalidOperand(operand.to_owned())),
+    };
+    Ok(match mnemonic {
+        "add" => no_operand(OpKind::Add)?,
+        "and" => no_operand(OpKind::And)?,
+        "bge" => branch(OpKind::BGE)?,
+        "ble" => branch(OpKind::BLE)?,
+        "blt" => branch(OpKind::BLT)?,
+        "bgt" => branch(OpKind::BGT)?,
+        "br" => branch(OpKind::BR)?,
+        "beq" => branch(OpKind::BEQ)?,
+        "bne" => branch(OpKind::BNE)?,
+        "conv.u8" => no_operand(OpKind::ConvU8)?,
+        "conv.i8" => no_operand(OpKind::ConvI8)?,
+        "conv.u16" => no_operand(OpKind::ConvU16)?,
+        "conv.i16" => no_operand(OpKind::ConvI16)?,
+        "conv.u32" => no_operand(OpKind::ConvU32)?,
+        "conv.i32" => no_operand(OpKind::ConvI32)?,
+        "conv.u64" => no_operand(OpKind::ConvU64)?,
+        "conv.i64" => no_operand(OpKind::ConvI64)?,
+        "conv.f32" => no_operand(OpKind::ConvF32)?,
+        "conv.f64" => no_operand(OpKind::ConvF64)?,
+        "conv.r.un" => no_operand(OpKind::ConvRUn)?,
+        "conv.ovf.u8" => no_operand(OpKind::ConvOvfU8)?,
+        "conv.ovf.i8" => no_operand(OpKind::ConvOvfI8)?,
+        "conv.ovf.u16" => no_operand(OpKind::ConvOvfU16)?,
+        "conv.ovf.i16" => no_operand(OpKind::ConvOvfI16)?,
+        "conv.ovf.u32" => no_operand(OpKind::ConvOvfU32)?,
+        "conv.ovf.i32" => no_operand(OpKind::ConvOvfI32)?,
+        "conv.ovf.u64" => no_operand(OpKind::ConvOvfU64)?,
+        "conv.ovf.i64" => no_operand(OpKind::ConvOvfI64)?,
+        "conv.ovf.u8.un" => no_operand(OpKind::ConvOvfU8Un)?,
+        "conv.ovf.i8.un" => no_operand(OpKind::ConvOvfI8Un)?,
+        "conv.ovf.u16.un" => no_operand(OpKind::ConvOvfU16Un)?,
+        "conv.ovf.i16.un" => no_operand(OpKind::ConvOvfI16Un)?,
+        "conv.ovf.u32.un" => no_operand(OpKind::ConvOvfU32Un)?,
+        "conv.ovf.i32.un" => no_operand(OpKind::ConvOvfI32Un)?,
+        "conv.ovf.u64.un" => no_operand(OpKind::ConvOvfU64Un)?,
+        "conv.ovf.i64.un" => no_operand(OpKind::ConvOvfI64Un)?,
+        "add.ovf" => no_operand(OpKind::AddOvf)?,
+        "add.ovf.un" => no_operand(OpKind::AddOvfUn)?,
+        "sub.ovf" => no_operand(OpKind::SubOvf)?,
+        "sub.ovf.un" => no_operand(OpKind::SubOvfUn)?,
+        "mul.ovf" => no_operand(OpKind::MulOvf)?,
+        "mul.ovf.un" => no_operand(OpKind::MulOvfUn)?,
+        "call" => parse_call(operand)?,
+        "div" => no_operand(OpKind::Div)?,
+        "dup" => no_operand(OpKind::Dup)?,
+        "ldc.i32" => OpKind::LDCI32(parse_number(operand)?),
+        "ldc.i64" => OpKind::LDCI64(parse_number(operand)?),
+        "ldc.f32" => OpKind::LDCF32(parse_number(operand)?),
+        "ldc.f64" => OpKind::LDCF64(parse_number(operand)?),
+        "ldarg" => OpKind::LDArg(parse_number(operand)?),
+        "ldnull" => no_operand(OpKind::LDNull)?,
+        "ldstr" => OpKind::LDStr(parse_string(operand)?.into()),
+        "nop" => no_operand(OpKind::Nop)?,
+        "not" => no_operand(OpKind::Not)?,
+        "neg" => no_operand(OpKind::Neg)?,
+        "mul" => no_operand(OpKind::Mul)?,
+        "or" => no_operand(OpKind::Or)?,
+        "pop" => no_operand(OpKind::Pop)?,
+        "ret" => no_operand(OpKind::Ret)?,
+        "rem" => no_operand(OpKind::Rem)?,
+        "sub" => no_operand(OpKind::Sub)?,
+        "shl" => no_operand(OpKind::SHL)?,
+        "shr" => no_operand(OpKind::SHR)?,
+        "xor" => no_operand(OpKind::XOr)?,
+        "ldloc" => OpKind::LDLoc(parse_number(operand)?),
+        "stloc" => OpKind::STLoc(parse_number(operand)?),
+        _ => return Err(ParseErrorKind::UnknownInstruction(mnemonic.to_owned())),
+    })
+}
+fn branch_label<'a>(mnemonic: &str, operand: &'a str) -> Option<&'a str> {
+    match mnemonic {
+        "bge" | "ble" | "blt" | "bgt" | "br" | "beq" | "bne" => Some(operand),
+        _ => None,
+    }
+}
+fn set_branch_target(op: &mut OpKind, target: usize) {
+    match op {
+        OpKind::BGE(index)
+        | OpKind::BLE(index)
+        | OpKind::BLT(index)
+        | OpKind::BGT(index)
+        | OpKind::BR(index)
+        | OpKind::BEQ(index)
+        | OpKind::BNE(index) => *index = target,
+        _ => unreachable!("{op:?} is not a branch"),
+    }
+}
+/// Parses a call target, e.g. `i32 [Asm]Namespace.Class::Add(i32, i32)`.
+fn parse_call(operand: &str) -> Result<OpKind, ParseErrorKind> {
+    let invalid = || ParseErrorKind::InvalidOperand(operand.to_owned());
+    if operand.is_empty() {
+        return Err(ParseErrorKind::MissingOperand);
+    }
+    let (ret, rest) = split_word(operand);
+    let ret = parse_type(ret)?;
+    let (assembly, rest) = rest
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .ok_or_else(invalid)?;
+    let args_start = rest.find('(').ok_or_else(invalid)?;
+    let (path, args) = rest.split_at(args_start);
+    let (class, method_name) = path.trim().split_once("::").ok_or_else(invalid)?;
+    let (namespace, class_name) = class.rsplit_once('.').unwrap_or(("", class));
+    if class_name.is_empty() || method_name.is_empty() {
+        return Err(invalid());
+    }
+    let args = parse_type_list(args)?;
+    let sig = Signature::new(&(&args, ret));
+    let path = MethodPath::new(assembly, namespace, class_name, method_name, &sig);
+    Ok(OpKind::Call(path, sig))
+}
+/// Parses a parenthesised list of types, e.g. `(i32, i64)`.
+fn parse_type_list(src: &str) -> Result<VType, ParseErrorKind> {
+    let inner = src
+        .trim()
+        .strip_prefix('(')
+        .and_then(|src| src.strip_suffix(')'))
+        .ok_or_else(|| ParseErrorKind::InvalidOperand(src.to_owned()))?;
+    if inner.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    inner.split(',').map(|ty| parse_type(ty.trim())).collect()
+}
+fn parse_type(name: &str) -> Result<Type, ParseErrorKind> {
+    Ok(match name {
+        "i64" => Type::I64,
+        "u64" => Type::U64,
+        "f64" => Type::F64,
+        "i32" => Type::I32,
+        "u32" => Type::U32,
+        "f32" => Type::F32,
+        "i16" => Type::I16,
+        "u16" => Type::U16,
+        "i8" => Type::I8,
+        "u8" => Type::U8,
+        "bool" => Type::Bool,
+        "void" => Type::Void,
+        "uptr" => Type::UPtr,
+        "iptr" => Type::IPtr,
+        "char" => Type::Char,
+        "obj" => Type::ObjRef,
+        _ => return Err(ParseErrorKind::UnknownType(name.to_owned())),
+    })
+}
+fn parse_number<T: std::str::FromStr>(operand: &str) -> Result<T, ParseErrorKind> {
+    match operand {
+        "" => Err(ParseErrorKind::MissingOperand),
+        _ => operand
+            .parse()
+            .map_err(|_| ParseErrorKind::InvalidOperand(operand.to_owned())),
+    }
+}
+/// Parses a quoted string literal into UTF-16, with the same escapes Rust's `Debug` output uses.
+/// A `\u{...}` escape below `0x10000` is a single code unit, so it may also be a surrogate.
+fn parse_string(operand: &str) -> Result<Vec<u16>, ParseErrorKind> {
+    let invalid = || ParseErrorKind::InvalidOperand(operand.to_owned());
+    if operand.is_empty() {
+        return Err(ParseErrorKind::MissingOperand);
+    }
+    let inner = operand
+        .strip_prefix('"')
+        .and_then(|operand| operand.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let mut res = Vec::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    let mut buf = [0; 2];
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            return Err(invalid());
+        }
+        if c != '\\' {
+            res.extend_from_slice(c.encode_utf16(&mut buf));
+            continue;
+        }
+        let c = match chars.next().ok_or_else(invalid)? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            '"' => '"',
+            'u' => {
+                let digits = chars
+                    .as_str()
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .ok_or_else(invalid)?
+                    .0;
+                let code = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+                //Skip `{digits}`.
+                chars.nth(digits.len() + 1);
+                if let Ok(unit) = u16::try_from(code) {
+                    res.push(unit);
+                    continue;
+                }
+                char::from_u32(code).ok_or_else(invalid)?
+            }
+            _ => return Err(invalid()),
+        };
+        res.extend_from_slice(c.encode_utf16(&mut buf));
+    }
+    Ok(res)
+}
+/// Removes a `//` comment, unless it is inside a string literal.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '/' if !in_string && line[index..].starts_with("//") => return &line[..index],
+            _ => (),
+        }
+    }
+    line
+}
+/// Splits off the first whitespace-separated word.
+fn split_word(src: &str) -> (&str, &str) {
+    let src = src.trim();
+    match src.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (src, ""),
+    }
+}
+fn is_ident(src: &str) -> bool {
+    let mut chars = src.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+#[test]
+fn parse_labels() {
+    let (sig, ops, locals) = parse_method(
+        ".sig i32 (i32)
+         .locals (i32, i64)
+                 ldarg 0
+                 stloc 0
+         loop:   ldloc 0
+                 ldc.i32 -1 // Decrement
+                 add
+                 dup
+                 stloc 0
+                 ldc.i32 0
+                 bgt loop
+         done:   ldloc 0
+                 ret",
+    )
+    .expect("Could not parse method!");
+    assert_eq!(sig.args(), &[Type::I32]);
+    assert_eq!(*sig.ret(), Type::I32);
+    assert_eq!(locals, [Type::I32, Type::I64]);
+    assert_eq!(ops.len(), 11);
+    assert!(matches!(ops[3], OpKind::LDCI32(-1)));
+    assert!(matches!(ops[8], OpKind::BGT(2)));
+    crate::Method::from_ops(sig, &ops, &locals).expect("Could not resolve method!");
+}
+#[test]
+fn parse_call_and_string() {
+    let (_, ops, _) = parse_method(
+        ".sig void ()
+         ldstr \"a // \\\"b\\\"\\n\\u{e9}\"
+         call void [SimpleFunctions]SimpleFunctions.Class1::Print(obj)
+         call i32 [Asm]Global::Get()
+         ret",
+    )
+    .expect("Could not parse method!");
+    match &ops[0] {
+        OpKind::LDStr(chars) => assert_eq!(String::from_utf16_lossy(chars), "a // \"b\"\n\u{e9}"),
+        op => panic!("Expected ldstr, got {op:?}"),
+    }
+    match &ops[1] {
+        OpKind::Call(path, sig) => {
+            assert_eq!(path.assembly_name(), "SimpleFunctions");
+            assert_eq!(path.namespace(), "SimpleFunctions");
+            assert_eq!(path.class_name(), "Class1");
+            assert_eq!(path.method_name(), "Print");
+            assert_eq!(sig.args(), &[Type::ObjRef]);
+            assert_eq!(*sig.ret(), Type::Void);
+        }
+        op => panic!("Expected call, got {op:?}"),
+    }
+    match &ops[2] {
+        OpKind::Call(path, _) => {
+            assert_eq!(path.namespace(), "");
+            assert_eq!(path.class_name(), "Global");
+        }
+        op => panic!("Expected call, got {op:?}"),
+    }
+}
+#[test]
+fn parse_errors() {
+    let error = |src| parse_method(src).expect_err("Parsing should fail!");
+    assert_eq!(
+        error(".sig i32 ()\n\n  ldc.i32 1\n  bge nowhere\n  ret"),
+        ParseError {
+            line: 4,
+            kind: ParseErrorKind::UndefinedLabel("nowhere".to_owned())
+        }
+    );
+    assert_eq!(
+        error(".sig i32 ()\n  ldc.i32 x").kind(),
+        &ParseErrorKind::InvalidOperand("x".to_owned())
+    );
+    assert_eq!(error(".sig i32 ()\n  ret 1").line(), 2);
+    assert_eq!(error(".sig i32 (int)").line(), 1);
+    assert_eq!(error(".sig void ()\n  frob").line(), 2);
+    assert_eq!(error(".sig void ()\na: ret\na: ret").line(), 3);
+    assert_eq!(error(".sig void ()\n  ret\nend:").line(), 3);
+    assert_eq!(
+        error(".sig void ()\n  ret\n.try IL_0 to IL_1 finally handler").kind(),
+        &ParseErrorKind::MissingOperand
+    );
+    assert_eq!(
+        error(".sig void ()\n  ret\n.try IL_0 to IL_1 catch Exception handler IL_0 to IL_1").kind(),
+        &ParseErrorKind::InvalidOperand("Exception".to_owned())
+    );
+    assert_eq!(
+        error(".sig void ()\n  ret\n.try IL_0 to IL_1 fault handler nowhere to IL_1").line(),
+        3
+    );
+    assert_eq!(
+        error("// No signature\n  ret"),
+        ParseError {
+            line: 2,
+            kind: ParseErrorKind::MissingSignature
+        }
+    );
+}
+#[test]
+fn dump_round_trip() {
+    let src = ".sig i32 (i32)
+               .locals (i32)
+                       ldarg 0
+                       stloc 0
+               loop:   ldloc 0
+                       ldc.i32 1
+                       sub
+                       dup
+                       stloc 0
+                       ldc.i32 0
+                       bgt loop
+                       ldstr \"done\"
+                       call void [Asm]Log::Print(obj)
+                       ldc.f64 0.1
+                       ldc.f32 -3.5e-20
+                       ldc.i64 -9000000000
+                       pop
+                       pop
+                       pop
+                       ldloc 0
+                       ret";
+    let (sig, ops, locals) = parse_method(src).expect("Could not parse method!");
+    let method = Method::from_ops(sig, &ops, &locals).expect("Could not resolve method!");
+    let dump = method.to_string();
+    assert!(dump.starts_with(".sig i32 (i32)\n.locals (i32)\n"));
+    assert!(dump.contains("block_1: // in: [], preds: block_0, block_1, succs: block_2, block_1"));
+    assert!(dump.contains("    bgt block_1                      // []"));
+    assert!(dump.contains("    call void [Asm]Log::Print(obj)   // []"));
+    let (_, reparsed, reparsed_locals) = parse_method(&dump).expect("Could not parse dump!");
+    assert_eq!(format!("{ops:?}"), format!("{reparsed:?}"));
+    assert_eq!(locals, reparsed_locals);
+}
+#[test]
+fn dump_round_trip_regions() {
+    let src = ".sig i32 (i32, i32)
+                       ldarg 0
+                       ldarg 1
+                       div
+                       ret
+               check:  pop
+                       ldc.i32 1
+                       ret
+               caught: pop
+                       ldc.i32 0
+                       ret
+               .try IL_0 to check catch [System.Runtime]System.DivideByZeroException handler caught to IL_10
+               .try IL_0 to check filter check handler caught to IL_10";
+    let (sig, ops, locals, regions) =
+        parse_method_with_regions(src).expect("Could not parse method!");
+    assert_eq!(regions[1].handler(), &Handler::Filter(4));
+    let method = Method::from_ops_with_regions(sig, &ops, &locals, regions.clone())
+        .expect("Could not resolve method!");
+    let dump = method.to_string();
+    assert!(dump.contains(
+        ".try block_0 to block_1 catch [System.Runtime]System.DivideByZeroException handler block_2 to IL_10"
+    ));
+    assert!(dump.contains(".try block_0 to block_1 filter block_1 handler block_2 to IL_10"));
+    let (_, reparsed, _, reparsed_regions) =
+        parse_method_with_regions(&dump).expect("Could not parse dump!");
+    assert_eq!(format!("{ops:?}"), format!("{reparsed:?}"));
+    assert_eq!(regions, reparsed_regions);
+}
+#[test]
+fn dump_round_trip_utf16() {
+    let src = ".sig void ()
+                       ldstr \"\\u{D800}a\\u{1F600}\"
+                       pop
+                       ret";
+    let (sig, ops, locals) = parse_method(src).expect("Could not parse method!");
+    match &ops[0] {
+        OpKind::LDStr(chars) => assert_eq!(**chars, [0xD800, 'a' as u16, 0xD83D, 0xDE00]),
+        op => panic!("Expected ldstr, got {op:?}"),
+    }
+    let method = Method::from_ops(sig, &ops, &locals).expect("Could not resolve method!");
+    let dump = method.to_string();
+    assert!(
+        dump.contains("ldstr \"\\u{D800}a\\u{D83D}\\u{DE00}\""),
+        "{dump}"
+    );
+    let (_, reparsed, _) = parse_method(&dump).expect("Could not parse dump!");
+    assert_eq!(format!("{ops:?}"), format!("{reparsed:?}"));
+}
@@ -1,5 +1,6 @@
 use inkwell::context::Context;
-use inkwell::types::{AnyTypeEnum, BasicTypeEnum, IntType};
+use inkwell::types::{AnyType, AnyTypeEnum, BasicTypeEnum, IntType, PointerType};
+use inkwell::AddressSpace;
 #[derive(Clone, Debug, PartialEq)]
 #[allow(dead_code)]
 pub enum Type {
@@ -35,7 +36,10 @@ impl Type {
             Self::U8 => "u8".to_owned(),
             Self::Bool => "bool".to_owned(),
             Self::Void => "void".to_owned(),
-            _ => todo!("Can't create mangle string from type:{self:?}!"),
+            Self::UPtr => "uptr".to_owned(),
+            Self::IPtr => "iptr".to_owned(),
+            Self::Char => "char".to_owned(),
+            Self::ObjRef => "obj".to_owned(),
         }
     }
     pub(crate) fn as_int<'ctx>(&self, ctx: &'ctx Context) -> Option<IntType<'ctx>> {
@@ -44,47 +48,84 @@ impl Type {
             Type::I32 | Type::U32 => Some(ctx.i32_type()),
             Type::I16 | Type::U16 | Type::Char => Some(ctx.i16_type()),
             Type::I8 | Type::U8 => Some(ctx.i8_type()),
-
+            //Bools take up a byte, like in the CLI.
+            Type::Bool => Some(ctx.i8_type()),
+            //Code is compiled for the machine it runs on, so native ints are as wide as its pointers.
+            Type::IPtr | Type::UPtr => Some(ctx.custom_width_int_type(usize::BITS)),
             _ => None,
         }
     }
+    /// Type of object references. Objects are opaque to LLVM, so this is a byte pointer.
+    pub(crate) fn obj_ref_type(ctx: &Context) -> PointerType {
+        ctx.i8_type().ptr_type(AddressSpace::default())
+    }
     pub(crate) fn is_int(&self) -> bool {
         match self {
             Type::I64 | Type::U64 => true,
             Type::I32 | Type::U32 => true,
             Type::I16 | Type::U16 | Type::Char => true,
             Type::I8 | Type::U8 => true,
+            Type::IPtr | Type::UPtr => true,
             _ => false,
         }
     }
-    pub(crate) fn arthm_promote(&self) -> Type {
+    pub(crate) fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Type::I64 | Type::I32 | Type::I16 | Type::I8 | Type::IPtr
+        )
+    }
+    /// Type values of this type have on the evaluation stack, where narrow integers and bools are widened to 32 bits.
+    /// `None` for types without arithmetic, like object references.
+    pub(crate) fn arthm_promote(&self) -> Option<Type> {
         match self {
-            Self::I64 | Self::U64 | Self::F64 | Self::I32 | Self::U32 | Self::F32 => self.clone(),
-            Self::I16 | Self::I8 => Self::I32,
-            Self::U16 | Self::U8 => Self::U32,
-            _ => todo!("Type promotion for arithmetic operations on type {self:?} unhanded!"),
+            Self::I64
+            | Self::U64
+            | Self::F64
+            | Self::I32
+            | Self::U32
+            | Self::F32
+            | Self::IPtr
+            | Self::UPtr => Some(self.clone()),
+            Self::I16 | Self::I8 | Self::Bool => Some(Self::I32),
+            Self::U16 | Self::U8 | Self::Char => Some(Self::U32),
+            Self::ObjRef | Self::Void => None,
+        }
+    }
+    /// Type checked when stacks meet at a join point. Values of the same width share one, whatever their declared width or signedness,
+    /// so `cond ? shortArg : 0` verifies.
+    pub(crate) fn verification_type(&self) -> Type {
+        match self.arthm_promote() {
+            Some(Self::U32) => Self::I32,
+            Some(Self::U64) => Self::I64,
+            Some(Self::UPtr) => Self::IPtr,
+            Some(promoted) => promoted,
+            None => self.clone(),
         }
     }
+    /// Checks if a value of this stack type may be stored into a slot (local, argument or return value) of type `slot`.
+    /// Besides exact matches, an int32 may be stored into any integer, bool or char of at most 32 bits, truncating it (a bool becomes 1 if it is nonzero).
+    pub(crate) fn is_assignable_to(&self, slot: &Type) -> bool {
+        let is_int32 = |t: &Type| matches!(t.arthm_promote(), Some(Type::I32 | Type::U32));
+        self == slot || (is_int32(self) && is_int32(slot))
+    }
     pub(crate) fn as_llvm_type<'ctx>(&self, ctx: &'ctx Context) -> AnyTypeEnum<'ctx> {
-        match self {
-            Type::Void => inkwell::types::AnyTypeEnum::VoidType(ctx.void_type()),
-            Type::I64 | Type::U64 => inkwell::types::AnyTypeEnum::IntType(ctx.i64_type()),
-            Type::I32 | Type::U32 => inkwell::types::AnyTypeEnum::IntType(ctx.i32_type()),
-            Type::I16 | Type::U16 => inkwell::types::AnyTypeEnum::IntType(ctx.i16_type()),
-            Type::I8 | Type::U8 => inkwell::types::AnyTypeEnum::IntType(ctx.i8_type()),
-            Type::F32 => inkwell::types::AnyTypeEnum::FloatType(ctx.f32_type()),
-            _ => todo!("Can't convert type {self:?} to llvm type!"),
+        match self.as_llvm_basic_type(ctx) {
+            Some(t) => t.as_any_type_enum(),
+            None => AnyTypeEnum::VoidType(ctx.void_type()),
         }
     }
+    /// LLVM type of values of this type, or `None` for `Void`.
     pub(crate) fn as_llvm_basic_type<'ctx>(
         &self,
         ctx: &'ctx Context,
     ) -> Option<BasicTypeEnum<'ctx>> {
         match self {
             Type::Void => None,
-            Type::I32 | Type::U32 => Some(BasicTypeEnum::IntType(ctx.i32_type())),
+            Type::F64 => Some(BasicTypeEnum::FloatType(ctx.f64_type())),
             Type::F32 => Some(BasicTypeEnum::FloatType(ctx.f32_type())),
-            _ => todo!("Can't convert type {self:?} to llvm type!"),
+            Type::ObjRef => Some(BasicTypeEnum::PointerType(Self::obj_ref_type(ctx))),
+            _ => self.as_int(ctx).map(BasicTypeEnum::IntType),
         }
     }
     pub(crate) fn is_arthmetic(&self) -> bool {
@@ -158,6 +199,24 @@ impl GetType for i64 {
         Type::I64
     }
 }
+impl GetType for isize {
+    type RawType = isize;
+    fn get_type() -> Type {
+        Type::IPtr
+    }
+}
+impl GetType for usize {
+    type RawType = usize;
+    fn get_type() -> Type {
+        Type::UPtr
+    }
+}
+impl GetType for bool {
+    type RawType = bool;
+    fn get_type() -> Type {
+        Type::Bool
+    }
+}
 impl GetType for f32 {
     type RawType = f32;
     fn get_type() -> Type {
@@ -170,6 +229,12 @@ impl GetType for f64 {
         Type::F64
     }
 }
+impl GetType for *const crate::gc::ManagedString {
+    type RawType = Self;
+    fn get_type() -> Type {
+        Type::ObjRef
+    }
+}
 pub trait InteropSend: GetType {
     fn get_raw(&self) -> Self::RawType;
 }
@@ -228,6 +293,13 @@ impl<A: GetType, B: GetType, C: GetType> AsArgTypeList for (A, B, C) {
         [A::get_type(), B::get_type(), C::get_type()]
     }
 }
+impl<A: GetType, B: GetType, C: GetType, D: GetType> AsArgTypeList for (A, B, C, D) {
+    type Output = [Type; 4];
+    type RawType = (A::RawType, B::RawType, C::RawType, D::RawType);
+    fn get_type_list() -> Self::Output {
+        [A::get_type(), B::get_type(), C::get_type(), D::get_type()]
+    }
+}
 pub trait ArgsToRaw {
     type Raw;
     fn to_raw(self) -> Self::Raw;
@@ -262,4 +334,20 @@ impl<A: InteropSend, B: InteropSend, C: InteropSend> ArgsToRaw for (A, B, C) {
         )
     }
 }
+impl<A: InteropSend, B: InteropSend, C: InteropSend, D: InteropSend> ArgsToRaw for (A, B, C, D) {
+    type Raw = (
+        <A as GetType>::RawType,
+        <B as GetType>::RawType,
+        <C as GetType>::RawType,
+        <D as GetType>::RawType,
+    );
+    fn to_raw(self) -> Self::Raw {
+        (
+            A::get_raw(&self.0),
+            B::get_raw(&self.1),
+            C::get_raw(&self.2),
+            D::get_raw(&self.3),
+        )
+    }
+}
 //impl AsArgTypeList for (,)
@@ -43,20 +43,17 @@ impl<'a> Variable<'a> {
             _ => panic!("Variable {val} is not an integer!"),
         }
     }
-    pub fn from_bve(bve: BasicValueEnum<'a>) -> Self {
-        match bve {
-            BasicValueEnum::IntValue(int) => Self::Int(int),
-            BasicValueEnum::FloatValue(f) => Self::Float(f),
-            BasicValueEnum::PointerValue(p) => Self::Pointer(p),
-            _ => todo!("Can't convert {bve:?} to a Variable IR!"),
-        }
-    }
     pub fn from_bve_typed(bve: BasicValueEnum<'a>, t: &Type) -> Self {
         match t {
-            Type::I64 | Type::I32 | Type::I16 | Type::I8 => Self::Int(bve.into_int_value()),
-            Type::U64 | Type::U32 | Type::U16 | Type::U8 => Self::UInt(bve.into_int_value()),
+            Type::I64 | Type::I32 | Type::I16 | Type::I8 | Type::IPtr => {
+                Self::Int(bve.into_int_value())
+            }
+            Type::U64 | Type::U32 | Type::U16 | Type::U8 | Type::UPtr | Type::Char | Type::Bool => {
+                Self::UInt(bve.into_int_value())
+            }
             Type::F64 | Type::F32 => Self::Float(bve.into_float_value()),
-            _ => todo!("Can't convert {bve:?} to type {t:?}"),
+            Type::ObjRef => Self::Pointer(bve.into_pointer_value()),
+            Type::Void => panic!("Can't convert {bve:?} to type {t:?}, void has no values!"),
         }
     }
     pub fn as_bve(&self) -> BasicValueEnum<'a> {
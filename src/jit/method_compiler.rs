@@ -1,10 +1,24 @@
 use super::compile_variable::Variable;
+use super::trap::{overflow_trap, OverflowHandler};
 use super::MethodCompileError;
-use crate::ir::{method::Method, op_block::OpBlock, r#type::Type, BlockLink, Signature};
+use crate::ir::{
+    method::Method, op::OpKind, op_block::OpBlock, r#type::Type, BlockLink, Signature,
+};
 use inkwell::{
-    basic_block::BasicBlock, builder::Builder, context::Context, module::Module, types::IntType,
-    values::FunctionValue, FloatPredicate, IntPredicate,
+    basic_block::BasicBlock,
+    builder::Builder,
+    context::Context,
+    intrinsics::Intrinsic,
+    module::Module,
+    types::IntType,
+    types::{BasicTypeEnum, FloatType},
+    values::{BasicValueEnum, FloatValue, FunctionValue, IntValue, PhiValue},
+    AddressSpace, FloatPredicate, IntPredicate,
 };
+/// Type values of type `t` have on the evaluation stack.
+fn stack_type(t: &Type) -> Type {
+    t.arthm_promote().unwrap_or_else(|| t.clone())
+}
 fn as_u64(i: i64) -> u64 {
     unsafe { std::mem::transmute(i) }
 }
@@ -53,6 +67,8 @@ pub(crate) struct MethodCompiler<'a> {
     method: &'a Method,
     variables: Vec<Variable<'a>>,
     blocks: Vec<BasicBlock<'a>>,
+    /// Phi nodes receiving the evaluation stack each block begins with, bottom first.
+    entry_stacks: Vec<Vec<PhiValue<'a>>>,
     builder: Builder<'a>,
     module: *const Module<'a>,
 }
@@ -76,6 +92,37 @@ impl<'a> MethodCompiler<'a> {
         self.variables.push(Variable::Int(var));
         self.variables.len() - 1
     }
+    pub(crate) fn add_const_i64(&mut self, val: i64) -> usize {
+        let var = self.ctx.i64_type().const_int(as_u64(val), false);
+        self.variables.push(Variable::Int(var));
+        self.variables.len() - 1
+    }
+    pub(crate) fn add_const_f32(&mut self, val: f32) -> usize {
+        let var = self.ctx.f32_type().const_float(f64::from(val));
+        self.variables.push(Variable::Float(var));
+        self.variables.len() - 1
+    }
+    pub(crate) fn add_const_f64(&mut self, val: f64) -> usize {
+        let var = self.ctx.f64_type().const_float(val);
+        self.variables.push(Variable::Float(var));
+        self.variables.len() - 1
+    }
+    /// Loads a pointer to the interned string object with contents `chars`.
+    pub(crate) fn add_const_str(&mut self, chars: &[u16]) -> usize {
+        let string = crate::gc::intern_string(chars) as *const _ as u64;
+        let ptr = self
+            .ctx
+            .custom_width_int_type(usize::BITS)
+            .const_int(string, false)
+            .const_to_pointer(Type::obj_ref_type(self.ctx));
+        self.variables.push(Variable::Pointer(ptr));
+        self.variables.len() - 1
+    }
+    pub(crate) fn add_null(&mut self) -> usize {
+        let ptr = Type::obj_ref_type(self.ctx).const_null();
+        self.variables.push(Variable::Pointer(ptr));
+        self.variables.len() - 1
+    }
     pub(crate) fn get_next_block(&self) -> Option<BasicBlock<'a>> {
         self.builder.get_insert_block()?.get_next_basic_block()
     }
@@ -92,22 +139,61 @@ impl<'a> MethodCompiler<'a> {
                 .expect("Can't find method!")
         };
         let mut arg_bves = Vec::with_capacity(args.len());
-        for arg in args {
-            let arg = self.variables[*arg];
-            arg_bves.push(arg.as_bve().into());
+        for (arg, t) in args.iter().zip(sig.args()) {
+            arg_bves.push(self.as_declared(*arg, t).into());
         }
         let res = self.builder.build_call(call_target, &arg_bves, name);
         if *sig.ret() == Type::Void {
             return None;
         }
         let res = res.try_as_basic_value().left().unwrap();
-        let res = Variable::from_bve_typed(res, sig.ret());
-        self.variables.push(res);
-        Some(self.variables.len() - 1)
+        Some(self.push_value(res, sig.ret()))
     }
     fn get_local_index(&self, loc_index: usize) -> usize {
         self.method.signature().argc() + loc_index
     }
+    /// Pushes `value` of type `t` as a new variable. Like on the CIL evaluation stack, integers narrower than 32 bits, bools and chars are extended to int32.
+    fn push_value(&mut self, value: BasicValueEnum<'a>, t: &Type) -> usize {
+        let var = Variable::from_bve_typed(value, t);
+        let stack_t = stack_type(t);
+        let var = if stack_t == *t {
+            var
+        } else {
+            let widened = self.convert_to_int(var, self.ctx.i32_type());
+            Variable::from_bve_typed(widened.into(), &stack_t)
+        };
+        self.variables.push(var);
+        self.variables.len() - 1
+    }
+    /// Value of variable `index` as type `t`, truncating integers widened by [`Self::push_value`] back to their declared width.
+    /// Integers stored to a `Bool` are normalized to 0 or 1 instead, since any nonzero value is true.
+    fn as_declared(&self, index: usize, t: &Type) -> BasicValueEnum<'a> {
+        let var = self.variables[index];
+        match (var, t.as_llvm_basic_type(self.ctx)) {
+            (Variable::Int(value) | Variable::UInt(value), Some(BasicTypeEnum::IntType(int)))
+                if *t == Type::Bool =>
+            {
+                let zero = value.get_type().const_zero();
+                let is_true = self
+                    .builder
+                    .build_int_compare(IntPredicate::NE, value, zero, "");
+                self.builder.build_int_z_extend(is_true, int, "").into()
+            }
+            (Variable::Int(_) | Variable::UInt(_), Some(BasicTypeEnum::IntType(int))) => {
+                self.convert_to_int(var, int).into()
+            }
+            _ => var.as_bve(),
+        }
+    }
+    /// Pushes argument `arg_index`, widening it if it is narrower than 32 bits.
+    pub(crate) fn load_arg(&mut self, arg_index: usize) -> usize {
+        let t = &self.method.signature().args()[arg_index];
+        if stack_type(t) == *t {
+            return arg_index;
+        }
+        let arg = self.variables[arg_index].as_bve();
+        self.push_value(arg, t)
+    }
     pub(crate) fn set_at_end_block(&mut self, block_id: usize) {
         self.builder.position_at_end(self.blocks[block_id]);
     }
@@ -171,6 +257,21 @@ impl<'a> MethodCompiler<'a> {
             Variable::Pointer(_) => todo!("Adding pointers unsupported!"),
         }
     }
+    /// Shifts right, keeping the sign of signed integers.
+    pub(crate) fn shr(&mut self, index_a: usize, index_b: usize) -> Option<usize> {
+        let (var_a, var_b) = (self.variables[index_a], self.variables[index_b]);
+        match var_a {
+            Variable::UInt(int_a) | Variable::Int(int_a) => {
+                let int_b = var_b.as_any_int()?;
+                let signed = matches!(var_a, Variable::Int(_));
+                let res = self.builder.build_right_shift(int_a, int_b, signed, "");
+                self.variables.push(var_a.matching_int(res));
+                Some(self.variables.len() - 1)
+            }
+            Variable::Float(_) => panic!("Can't shift a float!"),
+            Variable::Pointer(_) => todo!("Shifting pointers unsupported!"),
+        }
+    }
     pub(crate) fn not(&mut self, index_a: usize) -> Option<usize> {
         let var_a = self.variables[index_a];
         match var_a {
@@ -224,7 +325,14 @@ impl<'a> MethodCompiler<'a> {
                 self.variables.push(Variable::Int(res));
                 Some(self.variables.len() - 1)
             }
-            Variable::UInt(_) => panic!("Attempting to negate unsigned integer!"),
+            // Narrow unsigned values (`u8`, `u16`, `char`) widen to unsigned int32, and CIL negates them as two's complement.
+            Variable::UInt(var_a) => {
+                let res = self
+                    .builder
+                    .build_int_sub(var_a.get_type().const_zero(), var_a, "");
+                self.variables.push(Variable::UInt(res));
+                Some(self.variables.len() - 1)
+            }
             Variable::Float(var_a) => {
                 let res = self
                     .builder
@@ -338,67 +446,252 @@ impl<'a> MethodCompiler<'a> {
                     .build_float_compare(cmp.float_cmp(), float_a, var_b, "");
                 self.builder.build_conditional_branch(cmp, b_then, b_else);
             }
-            Variable::Pointer(_) => todo!("Branching using pointers unsported!"),
+            Variable::Pointer(ptr_a) => {
+                let Variable::Pointer(ptr_b) = var_b else {
+                    return None;
+                };
+                //Only `beq` and `bne` verify on references, and those are the same signed and unsigned.
+                let cmp = self
+                    .builder
+                    .build_int_compare(cmp.uint_cmp(), ptr_a, ptr_b, "");
+                self.builder.build_conditional_branch(cmp, b_then, b_else);
+            }
         }
         Some(())
     }
     pub(crate) fn ret(&mut self, index_ret: Option<usize>) {
         if let Some(index_ret) = index_ret {
-            let val_ret = &self.variables[index_ret];
-            self.builder.build_return(Some(&val_ret.as_bve()));
+            let val_ret = self.as_declared(index_ret, self.method.signature().ret());
+            self.builder.build_return(Some(&val_ret));
         } else {
             self.builder.build_return(None);
         }
     }
-    fn convert_to_int(&mut self, src_index: usize, target: IntType<'a>) -> usize {
-        let src = self.variables[src_index];
+    fn convert_to_int(&self, src: Variable<'a>, target: IntType<'a>) -> IntValue<'a> {
+        let (src_int, signed) = match src {
+            Variable::Int(src_int) => (src_int, true),
+            Variable::UInt(src_int) => (src_int, false),
+            _ => panic!("Only integers can be extended or truncated!"),
+        };
+        if src_int.get_type().get_bit_width() >= target.get_bit_width() {
+            self.builder.build_int_truncate(src_int, target, "")
+        } else if signed {
+            self.builder.build_int_s_extend(src_int, target, "")
+        } else {
+            self.builder.build_int_z_extend(src_int, target, "")
+        }
+    }
+    /// Truncates a float towards zero. Out of range values saturate and NaN becomes 0, so the result is always defined.
+    fn float_to_int(&self, src: FloatValue<'a>, target: IntType<'a>, signed: bool) -> IntValue<'a> {
+        let name = if signed {
+            "llvm.fptosi.sat"
+        } else {
+            "llvm.fptoui.sat"
+        };
+        let intrinsic = Intrinsic::find(name).expect("LLVM lacks saturating float conversions!");
+        let module = unsafe { &*self.module };
+        let declaration = intrinsic
+            .get_declaration(module, &[target.into(), src.get_type().into()])
+            .expect("Could not declare a saturating float conversion!");
+        self.builder
+            .build_call(declaration, &[src.into()], "")
+            .try_as_basic_value()
+            .left()
+            .expect("Float conversion must return a value!")
+            .into_int_value()
+    }
+    /// Integer `src` as it is on the evaluation stack, where integers narrower than 32 bits are extended to int32.
+    fn stack_int(&self, src: Variable<'a>) -> IntValue<'a> {
         match src {
-            Variable::Int(src_int) => {
-                if src_int.get_type().get_bit_width() < target.get_bit_width() {
-                    self.variables.push(Variable::Int(
-                        self.builder.build_int_s_extend(src_int, target, ""),
-                    ));
-                } else {
-                    self.variables.push(Variable::Int(
-                        self.builder.build_int_truncate(src_int, target, ""),
-                    ));
-                }
-                self.variables.len() - 1
-            }
-            Variable::UInt(src_int) => {
-                if src_int.get_type().get_bit_width() < target.get_bit_width() {
-                    self.variables.push(Variable::Int(
-                        self.builder.build_int_z_extend(src_int, target, ""),
-                    ));
-                } else {
-                    self.variables.push(Variable::Int(
-                        self.builder.build_int_truncate(src_int, target, ""),
-                    ));
-                }
-                self.variables.len() - 1
+            Variable::Int(src_int) | Variable::UInt(src_int)
+                if src_int.get_type().get_bit_width() >= 32 =>
+            {
+                src_int
             }
-            _ => panic!("Converting from anything else than signed integers is not supported!"),
+            _ => self.convert_to_int(src, self.ctx.i32_type()),
         }
     }
-    pub(crate) fn convert(&mut self, src_index: usize, target: Type) -> Option<usize> {
-        if target.is_int() {
-            Some(self.convert_to_int(src_index, target.as_int(self.ctx).unwrap()))
+    /// Converts an integer to a float. If `unsigned`, the integer is interpreted as unsigned, like `conv.r.un` does.
+    fn int_to_float(
+        &self,
+        src: Variable<'a>,
+        target: FloatType<'a>,
+        unsigned: bool,
+    ) -> FloatValue<'a> {
+        let src_int = self.stack_int(src);
+        if unsigned {
+            self.builder
+                .build_unsigned_int_to_float(src_int, target, "")
         } else {
-            panic!("Can't convert type:{target:?}");
+            self.builder.build_signed_int_to_float(src_int, target, "")
         }
     }
+    /// Converts a value to `target`. Integer and float sources of any width are supported. If `unsigned`, integers are interpreted as unsigned.
+    pub(crate) fn convert(
+        &mut self,
+        src_index: usize,
+        target: Type,
+        unsigned: bool,
+    ) -> Option<usize> {
+        let src = self.variables[src_index];
+        let res: BasicValueEnum = match (src, target.as_llvm_basic_type(self.ctx)?) {
+            (Variable::Int(_) | Variable::UInt(_), BasicTypeEnum::IntType(int)) => {
+                self.convert_to_int(src, int).into()
+            }
+            (Variable::Float(float), BasicTypeEnum::IntType(int)) => {
+                self.float_to_int(float, int, target.is_signed()).into()
+            }
+            (Variable::Int(_) | Variable::UInt(_), BasicTypeEnum::FloatType(float)) => {
+                self.int_to_float(src, float, unsigned).into()
+            }
+            (Variable::Float(src_float), BasicTypeEnum::FloatType(float)) => {
+                self.builder.build_float_cast(src_float, float, "").into()
+            }
+            _ => panic!("Can't convert type:{target:?}"),
+        };
+        Some(self.push_value(res, &target))
+    }
+    /// Calls the overflow trap if `overflowed` is set. Code emitted afterwards goes to a new block, reached either way.
+    fn trap_if(&mut self, overflowed: IntValue<'a>) -> Option<()> {
+        let current = self.builder.get_insert_block()?;
+        let trap = self.ctx.insert_basic_block_after(current, "overflow");
+        let resume = self.ctx.insert_basic_block_after(trap, "no_overflow");
+        self.builder
+            .build_conditional_branch(overflowed, trap, resume);
+        self.builder.position_at_end(trap);
+        let trap_type = self.ctx.void_type().fn_type(&[], false);
+        let trap_ptr = self
+            .ctx
+            .custom_width_int_type(usize::BITS)
+            .const_int(overflow_trap as OverflowHandler as usize as u64, false)
+            .const_to_pointer(trap_type.ptr_type(AddressSpace::default()));
+        self.builder
+            .build_indirect_call(trap_type, trap_ptr, &[], "");
+        self.builder.build_unconditional_branch(resume);
+        self.builder.position_at_end(resume);
+        Some(())
+    }
+    /// Integer arithmetic for `add.ovf`, `sub.ovf`, `mul.ovf` and their `.un` variants, trapping if the result does not fit.
+    pub(crate) fn checked_arithmetic(
+        &mut self,
+        index_a: usize,
+        index_b: usize,
+        op: &OpKind,
+    ) -> Option<usize> {
+        let name = match op {
+            OpKind::AddOvf => "llvm.sadd.with.overflow",
+            OpKind::AddOvfUn => "llvm.uadd.with.overflow",
+            OpKind::SubOvf => "llvm.ssub.with.overflow",
+            OpKind::SubOvfUn => "llvm.usub.with.overflow",
+            OpKind::MulOvf => "llvm.smul.with.overflow",
+            OpKind::MulOvfUn => "llvm.umul.with.overflow",
+            _ => return None,
+        };
+        let (var_a, var_b) = (self.variables[index_a], self.variables[index_b]);
+        let (int_a, int_b) = (self.stack_int(var_a), self.stack_int(var_b));
+        let intrinsic = Intrinsic::find(name).expect("LLVM lacks overflow intrinsics!");
+        let module = unsafe { &*self.module };
+        let declaration = intrinsic
+            .get_declaration(module, &[int_a.get_type().into()])
+            .expect("Could not declare an overflow intrinsic!");
+        let res = self
+            .builder
+            .build_call(declaration, &[int_a.into(), int_b.into()], "")
+            .try_as_basic_value()
+            .left()?
+            .into_struct_value();
+        let value = self.builder.build_extract_value(res, 0, "")?;
+        let overflowed = self.builder.build_extract_value(res, 1, "")?;
+        self.trap_if(overflowed.into_int_value())?;
+        self.variables
+            .push(var_a.matching_int(value.into_int_value()));
+        Some(self.variables.len() - 1)
+    }
+    /// Converts a value to integer type `target`, trapping if it is out of range. If `unsigned`, integers are interpreted as unsigned.
+    pub(crate) fn checked_convert(
+        &mut self,
+        src_index: usize,
+        target: Type,
+        unsigned: bool,
+    ) -> Option<usize> {
+        let src = self.variables[src_index];
+        let BasicTypeEnum::IntType(target_int) = target.as_llvm_basic_type(self.ctx)? else {
+            panic!("Checked conversions only target integers, not {target:?}!");
+        };
+        let bits = target_int.get_bit_width();
+        let signed = target.is_signed();
+        let (min, max): (i128, i128) = if signed {
+            (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        } else {
+            (0, (1 << bits) - 1)
+        };
+        let res = match src {
+            Variable::Int(_) | Variable::UInt(_) => {
+                let src_int = self.stack_int(src);
+                //One bit wider than both types, so every source and target value is representable.
+                let wide = self
+                    .ctx
+                    .custom_width_int_type(src_int.get_type().get_bit_width().max(bits) + 1);
+                let wide_src = if unsigned {
+                    self.builder.build_int_z_extend(src_int, wide, "")
+                } else {
+                    self.builder.build_int_s_extend(src_int, wide, "")
+                };
+                let too_small = self.builder.build_int_compare(
+                    IntPredicate::SLT,
+                    wide_src,
+                    wide.const_int(min as u64, min < 0),
+                    "",
+                );
+                let too_big = self.builder.build_int_compare(
+                    IntPredicate::SGT,
+                    wide_src,
+                    wide.const_int(max as u64, false),
+                    "",
+                );
+                let overflowed = self.builder.build_or(too_small, too_big, "");
+                self.trap_if(overflowed)?;
+                self.builder.build_int_truncate(wide_src, target_int, "")
+            }
+            Variable::Float(float) => {
+                let wide_src = self
+                    .builder
+                    .build_float_cast(float, self.ctx.f64_type(), "");
+                //Values are truncated, so anything above `min - 1` fits. For i64, `min - 1` is not exact in f64, but `min` is the next value below.
+                let (lower_pred, lower) = if signed && bits == 64 {
+                    (FloatPredicate::OGE, min as f64)
+                } else {
+                    (FloatPredicate::OGT, (min - 1) as f64)
+                };
+                let above_min = self.builder.build_float_compare(
+                    lower_pred,
+                    wide_src,
+                    self.ctx.f64_type().const_float(lower),
+                    "",
+                );
+                let below_max = self.builder.build_float_compare(
+                    FloatPredicate::OLT,
+                    wide_src,
+                    self.ctx.f64_type().const_float((max + 1) as f64),
+                    "",
+                );
+                //NaN fails both comparisons, so it overflows too.
+                let in_range = self.builder.build_and(above_min, below_max, "");
+                let overflowed = self.builder.build_not(in_range, "");
+                self.trap_if(overflowed)?;
+                self.float_to_int(float, target_int, signed)
+            }
+            Variable::Pointer(_) => panic!("Can't convert a pointer to {target:?}!"),
+        };
+        Some(self.push_value(res.into(), &target))
+    }
     pub(crate) fn set_local(&mut self, index_a: usize, local_index: usize) -> Option<()> {
-        let var_a = &self.variables[index_a];
         let Variable::Pointer(ptr) = self.variables[self.get_local_index(local_index)] else {
             return None;
         };
-        match var_a {
-            Variable::Int(int) => {
-                self.builder.build_store(ptr, *int);
-                Some(())
-            }
-            _ => todo!("Can't store local!"),
-        }
+        let value = self.as_declared(index_a, self.method.get_local_type(local_index));
+        self.builder.build_store(ptr, value);
+        Some(())
     }
     pub(crate) fn load_local(&mut self, local_index: usize) -> Option<usize> {
         let Variable::Pointer(ptr) = self.variables[self.get_local_index(local_index)] else {
@@ -409,12 +702,23 @@ impl<'a> MethodCompiler<'a> {
             .as_llvm_basic_type(self.ctx)
             .expect("Invalid local var type!");
         let res = self.builder.build_load(t, ptr, "");
-        self.variables.push(Variable::from_bve(res));
-        Some(self.variables.len() - 1)
+        Some(self.push_value(res, self.method.get_local_type(local_index)))
     }
     pub(crate) fn block_ops(&mut self, src_block: &OpBlock, index: usize) -> Option<()> {
         let mut virt_stack = VirtualStack::new();
         self.set_at_end_block(index);
+        //Blocks no path reaches, e.g. code after a `br`, are left unresolved.
+        let Some(state_in) = src_block.state_in() else {
+            self.builder.build_unreachable();
+            return Some(());
+        };
+        for (phi, t) in self.entry_stacks[index].iter().zip(state_in.types()) {
+            self.variables.push(Variable::from_bve_typed(
+                phi.as_basic_value(),
+                &stack_type(t),
+            ));
+            virt_stack.push(self.variables.len() - 1);
+        }
         for op in &src_block.block {
             use super::op_compiler::compile_op;
             compile_op(self, op, &mut virt_stack);
@@ -424,6 +728,13 @@ impl<'a> MethodCompiler<'a> {
                 self.builder.get_insert_block()?.get_next_basic_block()?,
             );
         }
+        //Values left on the stack flow into the phi nodes of every successor.
+        let current = self.builder.get_insert_block()?;
+        for successor in self.method.successors(index) {
+            for (phi, var) in self.entry_stacks[successor].iter().zip(&virt_stack.state) {
+                phi.add_incoming(&[(&self.variables[*var].as_bve(), current)]);
+            }
+        }
         Some(())
     }
     pub(crate) fn new(
@@ -455,11 +766,27 @@ impl<'a> MethodCompiler<'a> {
             blocks.push(ctx.append_basic_block(fnc, ""));
         }
         builder.build_unconditional_branch(blocks[0]);
+        let mut entry_stacks = Vec::with_capacity(method.blocks.len());
+        for (block, llvm_block) in method.blocks.iter().zip(&blocks) {
+            builder.position_at_end(*llvm_block);
+            let types = block.state_in().map_or(&[][..], |state| state.types());
+            let phis = types
+                .iter()
+                .map(|t| {
+                    let t = stack_type(t)
+                        .as_llvm_basic_type(ctx)
+                        .expect("Invalid stack value type!");
+                    builder.build_phi(t, "")
+                })
+                .collect();
+            entry_stacks.push(phis);
+        }
         let mut res = Self {
             ctx,
             method,
             variables,
             blocks,
+            entry_stacks,
             builder,
             module,
         };
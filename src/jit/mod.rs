@@ -1,6 +1,7 @@
 pub(crate) mod compile_variable;
 pub(crate) mod method_compiler;
 pub(crate) mod op_compiler;
+pub(crate) mod trap;
 #[derive(Debug)]
 pub enum MethodCompileError {
     VerificationFaliure,
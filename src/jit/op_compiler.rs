@@ -21,6 +21,7 @@ pub(crate) fn compile_arthm(
         OpKind::XOr => virt_stack.push(compiler.xor(a, b).unwrap()),
         OpKind::Div => virt_stack.push(compiler.div(a, b).unwrap()),
         OpKind::SHL => virt_stack.push(compiler.shl(a, b).unwrap()),
+        OpKind::SHR => virt_stack.push(compiler.shr(a, b).unwrap()),
         OpKind::Rem => virt_stack.push(compiler.rem(a, b).unwrap()),
         _ => panic!("INTERNAL LOGIC ERROR: compile_arthm recived non-arthemeic op!"),
     }
@@ -40,6 +41,7 @@ pub(crate) fn compile_op(
         | OpKind::Mul
         | OpKind::Rem
         | OpKind::SHL
+        | OpKind::SHR
         | OpKind::XOr
         | OpKind::Sub => compile_arthm(compiler, op, virt_stack).unwrap(),
         OpKind::Not => {
@@ -47,11 +49,26 @@ pub(crate) fn compile_op(
             virt_stack.push(compiler.not(a).unwrap());
         }
         OpKind::LDArg(arg_index) => {
-            virt_stack.push(*arg_index);
+            virt_stack.push(compiler.load_arg(*arg_index));
         }
         OpKind::LDCI32(val) => {
             virt_stack.push(compiler.add_const_i32(*val));
         }
+        OpKind::LDCI64(val) => {
+            virt_stack.push(compiler.add_const_i64(*val));
+        }
+        OpKind::LDCF32(val) => {
+            virt_stack.push(compiler.add_const_f32(*val));
+        }
+        OpKind::LDCF64(val) => {
+            virt_stack.push(compiler.add_const_f64(*val));
+        }
+        OpKind::LDNull => {
+            virt_stack.push(compiler.add_null());
+        }
+        OpKind::LDStr(chars) => {
+            virt_stack.push(compiler.add_const_str(chars));
+        }
         OpKind::Ret => {
             if *op.resolved_type().unwrap() == Type::Void {
                 compiler.ret(None);
@@ -105,37 +122,79 @@ pub(crate) fn compile_op(
             virt_stack.pop().unwrap();
         }
         OpKind::Nop => (),
+        OpKind::ConvF32 => {
+            let a = virt_stack.pop().unwrap();
+            virt_stack.push(compiler.convert(a, Type::F32, false).unwrap());
+        }
+        OpKind::ConvF64 => {
+            let a = virt_stack.pop().unwrap();
+            virt_stack.push(compiler.convert(a, Type::F64, false).unwrap());
+        }
+        OpKind::ConvRUn => {
+            let a = virt_stack.pop().unwrap();
+            virt_stack.push(compiler.convert(a, Type::F64, true).unwrap());
+        }
+        OpKind::AddOvf
+        | OpKind::AddOvfUn
+        | OpKind::SubOvf
+        | OpKind::SubOvfUn
+        | OpKind::MulOvf
+        | OpKind::MulOvfUn => {
+            let b = virt_stack.pop().unwrap();
+            let a = virt_stack.pop().unwrap();
+            virt_stack.push(compiler.checked_arithmetic(a, b, op.kind()).unwrap());
+        }
+        OpKind::ConvOvfU8
+        | OpKind::ConvOvfI8
+        | OpKind::ConvOvfU16
+        | OpKind::ConvOvfI16
+        | OpKind::ConvOvfU32
+        | OpKind::ConvOvfI32
+        | OpKind::ConvOvfU64
+        | OpKind::ConvOvfI64
+        | OpKind::ConvOvfU8Un
+        | OpKind::ConvOvfI8Un
+        | OpKind::ConvOvfU16Un
+        | OpKind::ConvOvfI16Un
+        | OpKind::ConvOvfU32Un
+        | OpKind::ConvOvfI32Un
+        | OpKind::ConvOvfU64Un
+        | OpKind::ConvOvfI64Un => {
+            let (target, unsigned) = op.kind().checked_conv().unwrap();
+            let a = virt_stack.pop().unwrap();
+            virt_stack.push(compiler.checked_convert(a, target, unsigned).unwrap());
+        }
         OpKind::ConvU8 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::U8).unwrap());
+            virt_stack.push(compiler.convert(a, Type::U8, false).unwrap());
         }
         OpKind::ConvI8 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::I8).unwrap());
+            virt_stack.push(compiler.convert(a, Type::I8, false).unwrap());
         }
         OpKind::ConvU16 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::U16).unwrap());
+            virt_stack.push(compiler.convert(a, Type::U16, false).unwrap());
         }
         OpKind::ConvI16 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::I16).unwrap());
+            virt_stack.push(compiler.convert(a, Type::I16, false).unwrap());
         }
         OpKind::ConvU32 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::U32).unwrap());
+            virt_stack.push(compiler.convert(a, Type::U32, false).unwrap());
         }
         OpKind::ConvI32 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::I32).unwrap());
+            virt_stack.push(compiler.convert(a, Type::I32, false).unwrap());
         }
         OpKind::ConvU64 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::U64).unwrap());
+            virt_stack.push(compiler.convert(a, Type::U64, false).unwrap());
         }
         OpKind::ConvI64 => {
             let a = virt_stack.pop().unwrap();
-            virt_stack.push(compiler.convert(a, Type::I64).unwrap());
+            virt_stack.push(compiler.convert(a, Type::I64, false).unwrap());
         }
         OpKind::Call(target, sig) => {
             let mut args = Vec::with_capacity(sig.args().len());
@@ -147,7 +206,6 @@ pub(crate) fn compile_op(
                 virt_stack.push(index);
             }
         }
-        _ => todo!("Unsuported OpKind:{:?}", op.kind()),
     }
     Some(())
 }
@@ -0,0 +1,22 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+/// Called by compiled code when a checked op overflows. If it returns, execution continues with the wrapped result.
+pub type OverflowHandler = extern "C" fn();
+/// Address of the current [`OverflowHandler`], or 0 if none is set.
+static OVERFLOW_HANDLER: AtomicUsize = AtomicUsize::new(0);
+/// Sets the function called when a checked op (`add.ovf`, `conv.ovf.*`, ...) overflows.
+/// Without a handler, an overflow aborts the process.
+pub fn set_overflow_handler(handler: OverflowHandler) {
+    OVERFLOW_HANDLER.store(handler as usize, Ordering::SeqCst);
+}
+/// Trap called by compiled code on overflow. Forwards to the overflow handler, if there is one.
+pub(crate) extern "C" fn overflow_trap() {
+    let handler = OVERFLOW_HANDLER.load(Ordering::SeqCst);
+    if handler == 0 {
+        eprintln!(
+            "Unhandled System.OverflowException: Arithmetic operation resulted in an overflow."
+        );
+        std::process::abort();
+    }
+    let handler: OverflowHandler = unsafe { std::mem::transmute(handler) };
+    handler();
+}
@@ -5,13 +5,21 @@ mod jit;
 pub mod type_system;
 mod utilis;
 #[doc(inline)]
-pub use crate::ir::method::Method;
+pub use crate::importer::disassembler::{disassemble, disassemble_method};
+#[doc(inline)]
+pub use crate::ir::method::{Handler, Method, ProtectedRegion};
 #[doc(inline)]
 pub use crate::ir::op::OpKind;
 #[doc(inline)]
 pub use crate::ir::r#type::Type;
+#[doc(inline)]
+pub use crate::ir::text::{
+    parse_method, parse_method_with_regions, ParseError, ParseErrorKind, ParsedMethod,
+};
 use crate::ir::Signature;
 #[doc(inline)]
+pub use crate::jit::trap::{set_overflow_handler, OverflowHandler};
+#[doc(inline)]
 pub use crate::type_system::{
     paths::{ClassPath, MethodPath},
     runtime::Runtime,
@@ -96,7 +104,13 @@ fn test_wrong_return() {
     let sig: (&[Type], Type) = (&args, Type::F32);
     let ops = [OpKind::LDArg(0), OpKind::LDArg(1), OpKind::Add, OpKind::Ret];
     if let Err(kind) = Method::from_ops(Signature::new(&sig), &ops, &[]) {
-        if let MethodIRError::WrongReturnType { expected, got } = kind {
+        if let MethodIRError::WrongReturnType {
+            index,
+            expected,
+            got,
+        } = kind
+        {
+            assert_eq!(index, 3, "Error should point at the `ret`");
             assert_eq!(
                 expected,
                 Type::F32,
@@ -253,3 +267,31 @@ fn test_factorial() {
         assert_eq!(f.call(6), 2 * 3 * 4 * 5 * 6, "Factorial 6");
     }
 }
+#[test]
+fn test_ldstr() {
+    let hello: Box<[u16]> = "Hello, World!".encode_utf16().collect();
+    let sig: (&[Type], Type) = (&[], Type::ObjRef);
+    let ops = [OpKind::LDStr(hello.clone()), OpKind::Ret];
+    let method = Method::from_ops(Signature::new(&sig), &ops, &[])
+        .expect("Could not compile method `Hello`");
+    let ctx = Context::create();
+    let module = ctx.create_module("my_mod");
+    let fn_type = method.as_fn_type(&ctx);
+    for name in ["hello_a", "hello_b"] {
+        let fn_value = module.add_function(name, fn_type, None);
+        let _mc = MethodCompiler::new(&ctx, fn_value, &method, &module);
+    }
+    module.verify().expect("Could not verify module!");
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::None)
+        .unwrap();
+    unsafe {
+        type Hello = unsafe extern "C" fn() -> *const gc::ManagedString;
+        let hello_a = execution_engine.get_function::<Hello>("hello_a").unwrap();
+        let hello_b = execution_engine.get_function::<Hello>("hello_b").unwrap();
+        let string = hello_a.call();
+        assert_eq!((*string).chars(), &hello[..]);
+        //Literals with the same contents are the same object.
+        assert_eq!(string, hello_b.call());
+    }
+}
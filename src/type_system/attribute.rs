@@ -0,0 +1,92 @@
+use super::paths::ClassPath;
+/// A value passed to the constructor of a custom attribute, or assigned to one of its fields or properties.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Bool(bool),
+    /// A UTF-16 code unit.
+    Char(u16),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    String(Option<String>),
+    /// A `System.Type`, e.g. the result of `typeof(T)`.
+    Type(Option<ClassPath>),
+    /// A value of enum `ClassPath`, widened from its underlying type.
+    Enum(ClassPath, i64),
+    Array(Option<Vec<AttributeValue>>),
+}
+/// A field or property of an attribute, set by name when the attribute is applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedArgument {
+    name: String,
+    is_property: bool,
+    value: AttributeValue,
+}
+impl NamedArgument {
+    pub(crate) fn new(name: String, is_property: bool, value: AttributeValue) -> Self {
+        Self {
+            name,
+            is_property,
+            value,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Whether a property, rather than a field, is set.
+    pub fn is_property(&self) -> bool {
+        self.is_property
+    }
+    pub fn value(&self) -> &AttributeValue {
+        &self.value
+    }
+}
+/// An attribute applied to a type or method, e.g. `[HostExport("add")]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomAttribute {
+    class: ClassPath,
+    fixed_args: Vec<AttributeValue>,
+    named_args: Vec<NamedArgument>,
+}
+impl CustomAttribute {
+    pub(crate) fn new(
+        class: ClassPath,
+        fixed_args: Vec<AttributeValue>,
+        named_args: Vec<NamedArgument>,
+    ) -> Self {
+        Self {
+            class,
+            fixed_args,
+            named_args,
+        }
+    }
+    /// Class of the attribute.
+    pub fn class(&self) -> &ClassPath {
+        &self.class
+    }
+    /// Arguments passed to the constructor, in order.
+    pub fn fixed_args(&self) -> &[AttributeValue] {
+        &self.fixed_args
+    }
+    pub fn named_args(&self) -> &[NamedArgument] {
+        &self.named_args
+    }
+    /// Value assigned to field or property `name`, if it was set.
+    pub fn named_arg(&self, name: &str) -> Option<&AttributeValue> {
+        self.named_args
+            .iter()
+            .find(|arg| arg.name == name)
+            .map(|arg| &arg.value)
+    }
+    /// Checks if this is an attribute of class `class_name` in `namespace`, regardless of the assembly defining it.
+    pub fn is(&self, namespace: &str, class_name: &str) -> bool {
+        self.class.namespace() == namespace && self.class.class_name() == class_name
+    }
+}
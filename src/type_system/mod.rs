@@ -1,6 +1,9 @@
+pub mod attribute;
 mod class;
 pub mod paths;
+pub mod resolver;
 pub mod runtime;
+pub(crate) mod statics;
 use crate::ir::method::Method as IRMethod;
 use crate::ir::r#type::{AsArgTypeList, GetType, InteropRecive, InteropSend};
 use crate::jit::{method_compiler::MethodCompiler, MethodCompileError};
@@ -1,5 +1,6 @@
 const IDENT_SPLIT: &str = "*";
 use crate::ir::Signature;
+#[derive(Clone, Hash, PartialEq, Eq)]
 pub struct ClassPath {
     identifier: String,
     assembly_end: usize,
@@ -107,7 +108,18 @@ impl MethodPath {
         &self.identifier
     }
 }
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
+impl Debug for ClassPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "ClassPath{{asm:{},namespace:{},class:{}}}",
+            self.assembly_name(),
+            self.namespace(),
+            self.class_name()
+        )
+    }
+}
 impl Debug for MethodPath {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         write!(
@@ -121,6 +133,26 @@ impl Debug for MethodPath {
         )
     }
 }
+/// Formats the path like ILDasm does, e.g. `[SimpleFunctions]SimpleFunctions.Class1`.
+impl Display for ClassPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "[{}]", self.assembly_name())?;
+        if !self.namespace().is_empty() {
+            write!(f, "{}.", self.namespace())?;
+        }
+        write!(f, "{}", self.class_name())
+    }
+}
+/// Formats the path like ILDasm does, e.g. `[SimpleFunctions]SimpleFunctions.Class1::Add`. The signature is not included.
+impl Display for MethodPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "[{}]", self.assembly_name())?;
+        if !self.namespace().is_empty() {
+            write!(f, "{}.", self.namespace())?;
+        }
+        write!(f, "{}::{}", self.class_name(), self.method_name())
+    }
+}
 #[cfg(test)]
 fn rnd_name() -> String {
     const NAMES: [&str; 10] = [
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+/// Finds the images of assemblies referenced by the ones being loaded.
+pub trait AssemblyResolver {
+    /// Gets the image of assembly `name`. Returns `Ok(None)` if this resolver does not know about it.
+    fn resolve(&mut self, name: &str) -> std::io::Result<Option<Vec<u8>>>;
+}
+/// Looks for `<name>.dll` and `<name>.exe` in a list of directories, in order.
+#[derive(Debug, Default)]
+pub struct ProbingResolver {
+    directories: Vec<PathBuf>,
+}
+impl ProbingResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds `directory` to the end of the probing list.
+    pub fn add_directory(&mut self, directory: impl Into<PathBuf>) {
+        self.directories.push(directory.into());
+    }
+}
+impl AssemblyResolver for ProbingResolver {
+    fn resolve(&mut self, name: &str) -> std::io::Result<Option<Vec<u8>>> {
+        for directory in &self.directories {
+            for extension in ["dll", "exe"] {
+                let path = directory.join(format!("{name}.{extension}"));
+                match std::fs::read(path) {
+                    Ok(image) => return Ok(Some(image)),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+/// Serves assembly images registered ahead of time, e.g. ones embedded in the host.
+#[derive(Debug, Default)]
+pub struct InMemoryResolver {
+    assemblies: HashMap<String, Vec<u8>>,
+}
+impl InMemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers `image` as the assembly `name`, replacing any previous one.
+    pub fn add_assembly(&mut self, name: &str, image: Vec<u8>) {
+        self.assemblies.insert(name.to_owned(), image);
+    }
+}
+impl AssemblyResolver for InMemoryResolver {
+    fn resolve(&mut self, name: &str) -> std::io::Result<Option<Vec<u8>>> {
+        Ok(self.assemblies.get(name).cloned())
+    }
+}
+#[test]
+fn probing_resolver() {
+    let mut resolver = ProbingResolver::new();
+    resolver.add_directory("test_asm/missing");
+    resolver.add_directory("test_asm/prebuilt");
+    let image = resolver
+        .resolve("SimpleFunctions")
+        .expect("Could not probe directories!")
+        .expect("Could not find `SimpleFunctions`!");
+    assert_eq!(&image[..2], b"MZ");
+    assert!(resolver.resolve("System.Runtime").unwrap().is_none());
+}
@@ -1,7 +1,12 @@
 use super::{
+    attribute::CustomAttribute,
     paths::{ClassPath, MethodPath},
+    resolver::AssemblyResolver,
+    statics::ClassStatics,
     Method, MethodCompileError, MethodRef, SingularRuntimeGuard,
 };
+use crate::importer::assembly::SkippedMethod;
+use crate::importer::signature::ElementType;
 use crate::ir::{
     method::Method as IRMethod,
     r#type::{AsArgTypeList, GetType, InteropRecive, InteropSend},
@@ -11,6 +16,7 @@ use core::marker::PhantomData;
 use inkwell::{
     context::Context, execution_engine::ExecutionEngine, module::Module, OptimizationLevel,
 };
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 
 struct InnerRuntime<'a> {
@@ -50,6 +56,9 @@ impl<'a> InnerRuntime<'a> {
         ExecutionEngine::link_in_mc_jit();
         Ok(())
     }
+    fn has_method(&self, path: &MethodPath) -> bool {
+        self.methods.lookup(path).is_some()
+    }
     fn verify(&self) -> Result<(), String> {
         match self.module.verify() {
             Ok(_) => Ok(()),
@@ -85,6 +94,14 @@ pub struct Runtime {
     ctx: Pin<Box<Context>>,
     //This is a hack. In reality, inner runtime only lives as long as `ctx` lives, but a struct can't hold a reference to its filed, so this is the only way to do it.
     runtime: Option<InnerRuntime<'static>>,
+    resolver: Option<Box<dyn AssemblyResolver>>,
+    loaded_assemblies: HashSet<String>,
+    statics: HashMap<ClassPath, ClassStatics>,
+    class_attributes: HashMap<ClassPath, Vec<CustomAttribute>>,
+    method_attributes: HashMap<MethodPath, Vec<CustomAttribute>>,
+    /// Underlying types of the enums of loaded assemblies, used to decode attributes of the assemblies depending on them.
+    enum_types: HashMap<ClassPath, ElementType>,
+    skipped_methods: Vec<SkippedMethod>,
     _guard: SingularRuntimeGuard,
 }
 impl Runtime {
@@ -117,12 +134,98 @@ impl Runtime {
             _rtime: PhantomData,
         })
     }
-    pub fn load_asm<R: std::io::Read>(
+    pub fn load_asm<R: std::io::Read + std::io::Seek>(
         &mut self,
         asm: &mut R,
     ) -> Result<(), crate::importer::assembly::ImportError> {
         crate::importer::assembly::import_assembly(asm, self)
     }
+    /// Sets the resolver used to find the dependencies of loaded assemblies.
+    pub fn set_resolver(&mut self, resolver: impl AssemblyResolver + 'static) {
+        self.resolver = Some(Box::new(resolver));
+    }
+    pub fn is_assembly_loaded(&self, name: &str) -> bool {
+        self.loaded_assemblies.contains(name)
+    }
+    /// Marks assembly `name` as loaded. Returns `false` if it already was, in which case it must not be imported again.
+    pub(crate) fn begin_assembly_load(&mut self, name: &str) -> bool {
+        self.loaded_assemblies.insert(name.to_owned())
+    }
+    pub(crate) fn abort_assembly_load(&mut self, name: &str) {
+        self.loaded_assemblies.remove(name);
+    }
+    pub(crate) fn resolve_assembly(&mut self, name: &str) -> std::io::Result<Option<Vec<u8>>> {
+        match &mut self.resolver {
+            Some(resolver) => resolver.resolve(name),
+            None => Ok(None),
+        }
+    }
+    pub(crate) fn has_method(&self, path: &MethodPath) -> bool {
+        self.runtime
+            .as_ref()
+            .is_some_and(|runtime| runtime.has_method(path))
+    }
+    /// Sets up the static fields of a freshly loaded class.
+    pub(crate) fn add_class_statics(&mut self, class: ClassPath, statics: ClassStatics) {
+        self.statics.insert(class, statics);
+    }
+    /// Current contents of static field `field` of class `class`.
+    pub fn get_static_data(&self, class: &ClassPath, field: &str) -> Option<&[u8]> {
+        self.statics.get(class)?.field(field)
+    }
+    pub(crate) fn add_class_attribute(&mut self, class: ClassPath, attribute: CustomAttribute) {
+        self.class_attributes
+            .entry(class)
+            .or_default()
+            .push(attribute);
+    }
+    pub(crate) fn add_method_attribute(&mut self, method: MethodPath, attribute: CustomAttribute) {
+        self.method_attributes
+            .entry(method)
+            .or_default()
+            .push(attribute);
+    }
+    /// Custom attributes applied to class `class`, in declaration order.
+    pub fn get_class_attributes(&self, class: &ClassPath) -> &[CustomAttribute] {
+        self.class_attributes
+            .get(class)
+            .map_or(&[], |attributes| &attributes[..])
+    }
+    /// Custom attributes applied to a method, looked up like in [`Runtime::get_method_ref`].
+    pub fn get_method_attributes<Args: AsArgTypeList, Ret: GetType>(
+        &self,
+        assembly: &str,
+        namespace: &str,
+        class_name: &str,
+        method_name: &str,
+    ) -> &[CustomAttribute] {
+        let sig = crate::Signature::from_types::<Args, Ret>();
+        let path = MethodPath::new(assembly, namespace, class_name, method_name, &sig);
+        self.method_attributes
+            .get(&path)
+            .map_or(&[], |attributes| &attributes[..])
+    }
+    pub(crate) fn add_enum_type(&mut self, class: ClassPath, underlying: ElementType) {
+        self.enum_types.insert(class, underlying);
+    }
+    /// Underlying type of enum `class`, if it is defined by a loaded assembly.
+    pub(crate) fn enum_type(&self, class: &ClassPath) -> Option<&ElementType> {
+        self.enum_types.get(class)
+    }
+    pub(crate) fn add_skipped_method(&mut self, method: SkippedMethod) {
+        self.skipped_methods.push(method);
+    }
+    /// Static methods of loaded assemblies that were left out, because they use something the runtime does not support yet.
+    pub fn skipped_methods(&self) -> &[SkippedMethod] {
+        &self.skipped_methods
+    }
+    /// Loads an assembly already in memory, e.g. a memory-mapped file.
+    pub fn load_asm_bytes(
+        &mut self,
+        asm: &[u8],
+    ) -> Result<(), crate::importer::assembly::ImportError> {
+        crate::importer::assembly::import_assembly_bytes(asm, self)
+    }
 }
 #[derive(Debug)]
 pub enum RuntimeInitError {
@@ -142,6 +245,13 @@ impl Runtime {
             ctx,
             _guard: guard,
             runtime,
+            resolver: None,
+            loaded_assemblies: HashSet::new(),
+            statics: HashMap::new(),
+            class_attributes: HashMap::new(),
+            method_attributes: HashMap::new(),
+            enum_types: HashMap::new(),
+            skipped_methods: Vec::new(),
         };
         //Init
         Ok(res)
@@ -190,9 +300,326 @@ fn import() {
     use std::fs::File;
     let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
     let mut src =
-        File::open("test_asm/SimpleFunctions/bin/Any CPU/Debug/net7.0/SimpleFunctions.dll")
-            .expect("Could not open test file!");
+        File::open("test_asm/prebuilt/SimpleFunctions.dll").expect("Could not open test file!");
     runtime
         .load_asm(&mut src)
         .expect("Could not load assembly!");
+    runtime.compile_all().expect("Could not compile methods!");
+    runtime
+        .verify()
+        .expect("Could not verify the runtimes llvm module!");
+    let add = runtime
+        .get_method_ref::<(i32, i32), i32>("SimpleFunctions", "SimpleFunctions", "Class1", "Add")
+        .expect("Could not find method `Add`");
+    let sum3 = runtime
+        .get_method_ref::<(i32, i32, i32), i32>(
+            "SimpleFunctions",
+            "SimpleFunctions",
+            "Class1",
+            "Sum3",
+        )
+        .expect("Could not find method `Sum3`");
+    let (add, sum3) = unsafe {
+        (
+            std::mem::transmute::<_, unsafe extern "C" fn(i32, i32) -> i32>(add.get_ptr()),
+            std::mem::transmute::<_, unsafe extern "C" fn(i32, i32, i32) -> i32>(sum3.get_ptr()),
+        )
+    };
+    assert_eq!(unsafe { add(40, 2) }, 42);
+    assert_eq!(unsafe { sum3(1, 2, 3) }, 6);
+}
+#[test]
+fn import_string_literal() {
+    use crate::gc::ManagedString;
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let asm =
+        std::fs::read("test_asm/prebuilt/SimpleFunctions.dll").expect("Could not open test file!");
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    runtime.compile_all().expect("Could not compile methods!");
+    let greeting: MethodRef<(), *const ManagedString> = runtime
+        .get_method_ref::<(), _>("SimpleFunctions", "SimpleFunctions", "Class1", "Greeting")
+        .expect("Could not find method `Greeting`");
+    let greeting = unsafe {
+        std::mem::transmute::<_, unsafe extern "C" fn() -> *const ManagedString>(greeting.get_ptr())
+    };
+    let string = unsafe { greeting() };
+    let hello: Vec<u16> = "Hello, World!".encode_utf16().collect();
+    assert_eq!(unsafe { (*string).chars() }, &hello[..]);
+    //The literal is interned, so every call returns the same object.
+    assert_eq!(string, unsafe { greeting() });
+}
+#[test]
+fn import_bytes() {
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let asm =
+        std::fs::read("test_asm/prebuilt/SimpleFunctions.dll").expect("Could not open test file!");
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    runtime.compile_all().expect("Could not compile methods!");
+    let add = runtime
+        .get_method_ref::<(i32, i32), i32>("SimpleFunctions", "SimpleFunctions", "Class1", "Add")
+        .expect("Could not find method `Add`");
+    let add =
+        unsafe { std::mem::transmute::<_, unsafe extern "C" fn(i32, i32) -> i32>(add.get_ptr()) };
+    assert_eq!(unsafe { add(40, 2) }, 42);
+}
+#[test]
+fn skip_unsupported_methods() {
+    use crate::importer::assembly::ImportError;
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let asm =
+        std::fs::read("test_asm/prebuilt/SimpleFunctions.dll").expect("Could not open test file!");
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    //`IsZero` uses `ceq`, which is not supported yet.
+    let [is_zero] = runtime.skipped_methods() else {
+        panic!(
+            "Expected one skipped method, got {:?}",
+            runtime.skipped_methods()
+        );
+    };
+    assert_eq!(is_zero.name(), "IsZero");
+    assert_eq!(
+        is_zero.class(),
+        &ClassPath::new("SimpleFunctions", "SimpleFunctions", "Class1")
+    );
+    let ImportError::InvalidMethodBody { error, .. } = is_zero.error() else {
+        panic!("Unexpected error {:?}", is_zero.error());
+    };
+    assert!(matches!(
+        **error,
+        ImportError::UnsupportedOpcode { opcode: "ceq", .. }
+    ));
+    runtime.compile_all().expect("Could not compile methods!");
+    assert!(runtime
+        .get_method_ref::<(i32,), bool>("SimpleFunctions", "SimpleFunctions", "Class1", "IsZero")
+        .is_none());
+    assert!(runtime
+        .get_method_ref::<(i32, i32), i32>("SimpleFunctions", "SimpleFunctions", "Class1", "Add")
+        .is_some());
+}
+#[test]
+fn import_dependencies() {
+    use super::resolver::ProbingResolver;
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let mut resolver = ProbingResolver::new();
+    resolver.add_directory("test_asm/prebuilt");
+    runtime.set_resolver(resolver);
+    let mut src = std::fs::File::open("test_asm/prebuilt/MathExtensions.dll")
+        .expect("Could not open test file!");
+    runtime
+        .load_asm(&mut src)
+        .expect("Could not load assembly!");
+    assert!(runtime.is_assembly_loaded("SimpleFunctions"));
+    runtime.compile_all().expect("Could not compile methods!");
+    let add4 = runtime
+        .get_method_ref::<(i32, i32, i32, i32), i32>(
+            "MathExtensions",
+            "MathExtensions",
+            "Extensions",
+            "Add4",
+        )
+        .expect("Could not find method `Add4`");
+    let add4 = unsafe {
+        std::mem::transmute::<_, unsafe extern "C" fn(i32, i32, i32, i32) -> i32>(add4.get_ptr())
+    };
+    assert_eq!(unsafe { add4(1, 2, 3, 4) }, 10);
+}
+#[test]
+fn unresolved_dependencies() {
+    use super::resolver::InMemoryResolver;
+    use crate::importer::assembly::ImportError;
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let asm =
+        std::fs::read("test_asm/prebuilt/MathExtensions.dll").expect("Could not open test file!");
+    //Only the method calling into the missing assembly is skipped.
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    assert!(runtime.is_assembly_loaded("MathExtensions"));
+    assert!(!runtime.is_assembly_loaded("SimpleFunctions"));
+    let [add4] = runtime.skipped_methods() else {
+        panic!(
+            "Expected one skipped method, got {:?}",
+            runtime.skipped_methods()
+        );
+    };
+    assert_eq!(add4.name(), "Add4");
+    let ImportError::InvalidMethodBody { error, .. } = add4.error() else {
+        panic!("Unexpected error {:?}", add4.error());
+    };
+    assert!(
+        matches!(**error, ImportError::UnresolvedAssembly(ref name) if name == "SimpleFunctions")
+    );
+    runtime.compile_all().expect("Could not compile methods!");
+    let twice = runtime
+        .get_method_ref::<(i32,), i32>("MathExtensions", "MathExtensions", "Extensions", "Twice")
+        .expect("Could not find method `Twice`");
+    let twice =
+        unsafe { std::mem::transmute::<_, unsafe extern "C" fn(i32) -> i32>(twice.get_ptr()) };
+    assert_eq!(unsafe { twice(21) }, 42);
+    //Once the dependency can be found, nothing is skipped. Only one runtime may exist at a time.
+    drop(runtime);
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let mut resolver = InMemoryResolver::new();
+    resolver.add_assembly(
+        "SimpleFunctions",
+        std::fs::read("test_asm/prebuilt/SimpleFunctions.dll").expect("Could not open test file!"),
+    );
+    runtime.set_resolver(resolver);
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    assert!(runtime.is_assembly_loaded("SimpleFunctions"));
+    assert!(runtime
+        .skipped_methods()
+        .iter()
+        .all(|method| method.name() != "Add4"));
+}
+#[test]
+fn static_data() {
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let asm = std::fs::read("test_asm/prebuilt/StaticData.dll").expect("Could not open test file!");
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    let tables = ClassPath::new("StaticData", "StaticData", "Tables");
+    assert_eq!(
+        runtime.get_static_data(&tables, "Answer"),
+        Some(&42_i32.to_le_bytes()[..])
+    );
+    let primes: Vec<u8> = [2_i32, 3, 5, 7]
+        .iter()
+        .flat_map(|prime| prime.to_le_bytes())
+        .collect();
+    assert_eq!(
+        runtime.get_static_data(&tables, "Primes"),
+        Some(&primes[..])
+    );
+    assert_eq!(
+        runtime.get_static_data(&tables, "Counter"),
+        Some(&[0; 8][..])
+    );
+    assert_eq!(
+        runtime.get_static_data(&tables, "Scratch"),
+        Some(&[0; 16][..])
+    );
+    //Literals have no storage.
+    assert_eq!(runtime.get_static_data(&tables, "Max"), None);
+    //The size of `Block16` comes from its ClassLayout row, so it can't be trusted.
+    let layout = [1, 0, 16, 0, 0, 0, 3, 0];
+    let layout_at = asm
+        .windows(layout.len())
+        .position(|row| row == layout)
+        .expect("Could not find the layout of `Block16`");
+    let mut asm = asm;
+    asm[layout_at + 2..layout_at + 6].copy_from_slice(&u32::MAX.to_le_bytes());
+    drop(runtime);
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    let error = runtime.load_asm_bytes(&asm);
+    assert!(
+        matches!(
+            error,
+            Err(crate::importer::assembly::ImportError::StaticFieldTooLarge { ref name, size: u32::MAX }) if name == "Scratch"
+        ),
+        "{error:?}"
+    );
+}
+#[test]
+fn custom_attributes() {
+    use super::attribute::AttributeValue;
+    use super::resolver::InMemoryResolver;
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    //`AttributeTargets` is defined by the core library, which has to be loaded to decode `AttributeUsage`.
+    let mut resolver = InMemoryResolver::new();
+    resolver.add_assembly(
+        "System.Runtime",
+        std::fs::read("test_asm/prebuilt/stubs/System.Runtime.dll")
+            .expect("Could not open test file!"),
+    );
+    runtime.set_resolver(resolver);
+    let asm = std::fs::read("test_asm/prebuilt/Exports.dll").expect("Could not open test file!");
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    let kind = ClassPath::new("Exports", "Exports", "ExportKind");
+    let host = runtime.get_class_attributes(&ClassPath::new("Exports", "Exports", "Host"));
+    assert_eq!(host.len(), 1);
+    assert!(host[0].is("Exports", "HostExportAttribute"));
+    assert_eq!(
+        host[0].fixed_args(),
+        &[
+            AttributeValue::String(Some("host".into())),
+            AttributeValue::Enum(kind.clone(), 2)
+        ]
+    );
+    let usage =
+        runtime.get_class_attributes(&ClassPath::new("Exports", "Exports", "HostExportAttribute"));
+    assert_eq!(
+        usage[0].fixed_args(),
+        &[AttributeValue::Enum(
+            ClassPath::new("System.Runtime", "System", "AttributeTargets"),
+            4 | 64
+        )]
+    );
+    assert_eq!(
+        usage[0].named_arg("AllowMultiple"),
+        Some(&AttributeValue::Bool(true))
+    );
+    let add = runtime.get_method_attributes::<(i32, i32), i32>("Exports", "Exports", "Host", "Add");
+    assert_eq!(add.len(), 2);
+    assert_eq!(add[0].fixed_args()[1], AttributeValue::Enum(kind, 0));
+    assert_eq!(
+        add[0].named_arg("Marshaller"),
+        Some(&AttributeValue::Type(Some(ClassPath::new(
+            "System.Runtime",
+            "System",
+            "String"
+        ))))
+    );
+    assert_eq!(
+        add[0].named_arg("Ids"),
+        Some(&AttributeValue::Array(Some(vec![
+            AttributeValue::I32(1),
+            AttributeValue::I32(2)
+        ])))
+    );
+    assert!(add[1].is("System", "ObsoleteAttribute"));
+    assert!(runtime
+        .get_method_attributes::<(), i32>("Exports", "Exports", "Host", "Add")
+        .is_empty());
+    //`[MethodImpl]` is stored in the flags of the method.
+    let twice = runtime.get_method_attributes::<(i32,), i32>("Exports", "Exports", "Host", "Twice");
+    assert_eq!(twice.len(), 1);
+    assert!(twice[0].is("System.Runtime.CompilerServices", "MethodImplAttribute"));
+    assert_eq!(
+        twice[0].fixed_args(),
+        &[AttributeValue::Enum(
+            ClassPath::new(
+                "System.Runtime",
+                "System.Runtime.CompilerServices",
+                "MethodImplOptions"
+            ),
+            0x100
+        )]
+    );
+    //Without the core library, `AttributeUsage` can't be decoded, so it is left out.
+    drop(runtime);
+    let mut runtime = Runtime::init_await().expect("Coud not initialise the runtime!");
+    runtime
+        .load_asm_bytes(&asm)
+        .expect("Could not load assembly!");
+    assert!(runtime
+        .get_class_attributes(&ClassPath::new("Exports", "Exports", "HostExportAttribute"))
+        .is_empty());
+    assert_eq!(
+        runtime
+            .get_class_attributes(&ClassPath::new("Exports", "Exports", "Host"))
+            .len(),
+        1
+    );
 }
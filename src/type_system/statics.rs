@@ -0,0 +1,21 @@
+use std::collections::HashMap;
+/// Storage of the static fields of a single class.
+#[derive(Debug, Default)]
+pub(crate) struct ClassStatics {
+    fields: HashMap<String, Box<[u8]>>,
+}
+impl ClassStatics {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+    /// Allocates storage for field `name`, initialised with `data`. Boxed, so that the address of a field never changes.
+    pub(crate) fn add_field(&mut self, name: &str, data: Box<[u8]>) {
+        self.fields.insert(name.to_owned(), data);
+    }
+    pub(crate) fn field(&self, name: &str) -> Option<&[u8]> {
+        self.fields.get(name).map(|data| &data[..])
+    }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
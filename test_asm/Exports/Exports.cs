@@ -0,0 +1,29 @@
+using System.Runtime.CompilerServices;
+namespace Exports;
+public enum ExportKind : ushort
+{
+	Function,
+	Callback = 2,
+}
+[AttributeUsage(AttributeTargets.Class | AttributeTargets.Method, AllowMultiple = true)]
+public sealed class HostExportAttribute : Attribute
+{
+	public HostExportAttribute(string name, ExportKind kind)
+	{
+		Name = name;
+		Kind = kind;
+	}
+	public readonly string Name;
+	public readonly ExportKind Kind;
+	public Type Marshaller;
+	public int[] Ids;
+}
+[HostExport("host", ExportKind.Callback)]
+public static class Host
+{
+	[HostExport("add", ExportKind.Function, Marshaller = typeof(string), Ids = new[] { 1, 2 })]
+	[Obsolete("Use Sum instead")]
+	public static int Add(int a, int b) => a + b;
+	[MethodImpl(MethodImplOptions.AggressiveInlining)]
+	public static int Twice(int a) => a + a;
+}
@@ -0,0 +1,7 @@
+namespace MathExtensions;
+using SimpleFunctions;
+public static class Extensions
+{
+	public static int Add4(int a, int b, int c, int d)=>Class1.Add(Class1.Sum3(a, b, c), d);
+	public static int Twice(int a)=>a + a;
+}
@@ -2,4 +2,7 @@ namespace SimpleFunctions;
 public static class Class1
 {
 	public static int Add(int a, int b)=>a + b;
+	public static int Sum3(int a, int b, int c)=>Add(Add(a, b), c);
+	public static string Greeting()=>"Hello, World!";
+	public static bool IsZero(int a)=>a == 0;
 }
@@ -0,0 +1,10 @@
+// Stub of the core library, defining only the types the tests need.
+namespace System;
+public class Object { }
+public abstract class ValueType { }
+public abstract class Enum : ValueType { }
+public enum AttributeTargets
+{
+	Class = 4,
+	Method = 64,
+}